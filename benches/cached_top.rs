@@ -0,0 +1,302 @@
+// Compares the size and speed of the code generated by each of the translator's stack
+// strategies, using the example programs from the test suite. Run with `cargo bench`.
+//
+// The programs rely on the Jack OS, so a minimal version of it is compiled along with them.
+// Everything but Sys is written in Jack, so it benefits from the strategy in the same way as the
+// programs themselves.
+
+use jackc::asm;
+use jackc::hack::Emulator;
+use jackc::jack::{Compiler, Parser, Tokenizer};
+use jackc::vm::{self, Module, Strategy, Translator};
+use std::path::PathBuf;
+use std::{env, fs};
+
+const PROGRAMS: &[(&str, &[&str])] = &[
+    ("Average", &["Average.jack"]),
+    ("ConvertToBin", &["ConvertToBin.jack"]),
+    (
+        "Square",
+        &["SquareMain.jack", "SquareGame.jack", "Square.jack"],
+    ),
+];
+
+// Sys.init sets temp 7 once Main.main returns.
+const DONE: u16 = 12;
+const MAX_CYCLES: usize = 50_000_000;
+
+const SYS: &str = "function Sys.init 0
+call Main.main 0
+pop temp 0
+push constant 1
+pop temp 7
+label HALT
+goto HALT
+function Sys.wait 0
+push constant 0
+return";
+
+const OS: &[&str] = &[
+    "class Math {
+        function int multiply(int x, int y) {
+            var int sum, bit;
+            let bit = 1;
+            while (~(bit = 0)) {
+                if (~((y & bit) = 0)) {
+                    let sum = sum + x;
+                }
+                let x = x + x;
+                let bit = bit + bit;
+            }
+            return sum;
+        }
+
+        function int divide(int x, int y) {
+            var int q;
+            if (x < 0) {
+                return -Math.divide(-x, y);
+            }
+            if (y < 0) {
+                return -Math.divide(x, -y);
+            }
+            if ((y > x) | (y > 16383)) {
+                return -((y = x) | (y < x));
+            }
+            let q = Math.divide(x, y + y);
+            if ((x - ((q + q) * y)) < y) {
+                return q + q;
+            }
+            return q + q + 1;
+        }
+    }",
+    "class Memory {
+        static int free = 2048;
+
+        function int peek(int address) {
+            var Array ram;
+            let ram = 0;
+            return ram[address];
+        }
+
+        function void poke(int address, int value) {
+            var Array ram;
+            let ram = 0;
+            let ram[address] = value;
+            return;
+        }
+
+        function int alloc(int size) {
+            let free = free + size;
+            return free - size;
+        }
+
+        function void deAlloc(Array object) {
+            return;
+        }
+    }",
+    "class Array {
+        function Array new(int size) {
+            return Memory.alloc(size);
+        }
+    }",
+    "class String {
+        field Array chars;
+        field int length;
+
+        constructor String new(int maxLength) {
+            let chars = Array.new(maxLength + 1);
+            return this;
+        }
+
+        method String appendChar(char c) {
+            let chars[length] = c;
+            let length = length + 1;
+            return this;
+        }
+
+        method int length() {
+            return length;
+        }
+
+        method char charAt(int i) {
+            return chars[i];
+        }
+    }",
+    // Keeps a running total of everything printed, rather than drawing it on the screen.
+    "class Output {
+        static int total;
+
+        function void printInt(int i) {
+            let total = total + i;
+            return;
+        }
+
+        function void printChar(char c) {
+            let total = total + c;
+            return;
+        }
+
+        function void printString(String s) {
+            var int i;
+            while (i < s.length()) {
+                do Output.printChar(s.charAt(i));
+                let i = i + 1;
+            }
+            return;
+        }
+
+        function void println() {
+            do Output.printChar(128);
+            return;
+        }
+    }",
+    // Types in the same numbers and key presses on every run.
+    "class Keyboard {
+        static int calls;
+
+        function char keyPressed() {
+            let calls = calls + 1;
+            if (calls = 60) {
+                return 81;
+            }
+            if ((calls < 60) & ((calls & 15) < 4)) {
+                return 132 + ((calls / 16) & 1);
+            }
+            return 0;
+        }
+
+        function int readInt(String message) {
+            do Output.printString(message);
+            let calls = calls + 1;
+            if (calls = 1) {
+                return 20;
+            }
+            return (calls * 37) - 100;
+        }
+    }",
+    // The emulator's RAM stops short of the screen, so draws into the 8K words below it instead.
+    "class Screen {
+        static boolean color;
+
+        function void setColor(boolean b) {
+            let color = b;
+            return;
+        }
+
+        function void drawRectangle(int x1, int y1, int x2, int y2) {
+            var int row, first, last, address;
+            let row = 8192 + (y1 * 32);
+            let first = x1 / 16;
+            let last = x2 / 16;
+            while (~(y1 > y2)) {
+                let address = row + first;
+                while (~(address > (row + last))) {
+                    do Memory.poke(address, color);
+                    let address = address + 1;
+                }
+                let row = row + 32;
+                let y1 = y1 + 1;
+            }
+            return;
+        }
+    }",
+];
+
+struct Run {
+    rom: usize,
+    cycles: usize,
+    emulator: Emulator<'static>,
+}
+
+fn main() {
+    println!(
+        "{:<14} {:>12} {:>12} {:>8} {:>15} {:>15} {:>8}",
+        "Program",
+        "ROM (Memory)",
+        "ROM (Cached)",
+        "Change",
+        "Cycles (Memory)",
+        "Cycles (Cached)",
+        "Change"
+    );
+
+    for (name, files) in PROGRAMS {
+        let modules = program(files);
+        let memory = run(&modules, Strategy::Memory);
+        let cached = run(&modules, Strategy::CachedTop);
+
+        // Both versions of the program must have left behind the same statics and heap.
+        for addr in (16..256).chain(2048..16384) {
+            assert_eq!(
+                memory.emulator.ram.get(addr),
+                cached.emulator.ram.get(addr),
+                "{}: RAM[{}] differs between strategies",
+                name,
+                addr
+            );
+        }
+
+        println!(
+            "{:<14} {:>12} {:>12} {:>8} {:>15} {:>15} {:>8}",
+            name,
+            memory.rom,
+            cached.rom,
+            change(memory.rom, cached.rom),
+            memory.cycles,
+            cached.cycles,
+            change(memory.cycles, cached.cycles)
+        );
+    }
+}
+
+fn program(files: &[&str]) -> Vec<Module> {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("tests")
+        .join("testdata");
+
+    let mut modules = files
+        .iter()
+        .map(|file| compile(&fs::read_to_string(dir.join(file)).unwrap()))
+        .chain(OS.iter().map(|source| compile(source)))
+        .collect::<Vec<_>>();
+
+    modules.push(Module::new("Sys".into(), vm::parse(SYS).unwrap()));
+    modules
+}
+
+fn compile(source: &str) -> Module {
+    let tokens = Tokenizer::new(source).tokenize().unwrap();
+    let class = Parser::new(tokens).parse().unwrap();
+    Compiler::new(&class).compile().unwrap()
+}
+
+fn run(modules: &[Module], strategy: Strategy) -> Run {
+    let mut translator = Translator::new(modules);
+    translator.strategy(strategy);
+    let rom = asm::assemble(&translator.translate().unwrap()).unwrap();
+    let size = rom.len();
+
+    // The emulator borrows the ROM for as long as it lives.
+    let mut emulator = Emulator::new(Box::leak(rom.into_boxed_slice()));
+    emulator.ram.set(8000, 0xb00c);
+
+    let mut cycles = 0;
+    while emulator.ram.get(DONE) == 0 {
+        assert!(cycles < MAX_CYCLES, "program didn't finish");
+        emulator.step();
+        cycles += 1;
+    }
+
+    Run {
+        rom: size,
+        cycles,
+        emulator,
+    }
+}
+
+fn change(before: usize, after: usize) -> String {
+    format!(
+        "{:+.1}%",
+        (after as f64 - before as f64) / before as f64 * 100.0
+    )
+}
@@ -1,38 +1,18 @@
 use super::instruction::*;
+use super::layout::*;
 use anyhow::{anyhow, Error};
 use std::collections::HashMap;
 
-const DEFAULT_SYMBOLS: &[(&str, u16)] = &[
-    ("SP", 0),
-    ("LCL", 1),
-    ("ARG", 2),
-    ("THIS", 3),
-    ("THAT", 4),
-    ("R0", 0),
-    ("R1", 1),
-    ("R2", 2),
-    ("R3", 3),
-    ("R4", 4),
-    ("R5", 5),
-    ("R6", 6),
-    ("R7", 7),
-    ("R8", 8),
-    ("R9", 9),
-    ("R10", 10),
-    ("R11", 11),
-    ("R12", 12),
-    ("R13", 13),
-    ("R14", 14),
-    ("R15", 15),
-    ("SCREEN", 0x4000),
-    ("KBD", 0x6000),
-];
-
 pub fn assemble(prog: &[Instruction]) -> Result<Vec<u16>, Error> {
-    let mut symbols: HashMap<String, u16> = DEFAULT_SYMBOLS
-        .iter()
-        .map(|&(sym, idx)| (sym.to_owned(), idx))
-        .collect();
+    assemble_with_layout(prog, &MemoryLayout::new())
+}
+
+pub fn assemble_with_layout(
+    prog: &[Instruction],
+    layout: &MemoryLayout,
+) -> Result<Vec<u16>, Error> {
+    layout.validate()?;
+    let mut symbols: HashMap<String, u16> = layout.symbols().into_iter().collect();
 
     let mut unlabelled = Vec::new();
     let mut idx = 0;
@@ -53,7 +33,7 @@ pub fn assemble(prog: &[Instruction]) -> Result<Vec<u16>, Error> {
         }
     }
 
-    let mut next_static = 16;
+    let mut next_static = layout.statics.start;
 
     unlabelled
         .iter()
@@ -62,12 +42,19 @@ pub fn assemble(prog: &[Instruction]) -> Result<Vec<u16>, Error> {
             Instruction::A(Load::Symbol(symbol)) => match symbols.get(symbol) {
                 Some(idx) => Ok(*idx),
                 None => {
-                    let idx = symbols.entry(symbol.to_owned()).or_insert_with(|| {
-                        let idx = next_static;
-                        next_static += 1;
-                        idx
-                    });
-                    Ok(*idx)
+                    if next_static >= layout.statics.end {
+                        return Err(anyhow!(
+                            "no room for static variable `{}`, as only {} fit in RAM[{}..{}]",
+                            symbol,
+                            layout.statics.len(),
+                            layout.statics.start,
+                            layout.statics.end
+                        ));
+                    }
+
+                    symbols.insert(symbol.to_owned(), next_static);
+                    next_static += 1;
+                    Ok(next_static - 1)
                 }
             },
             Instruction::C(dest, comp, jump) => {
@@ -26,6 +26,7 @@ macro_rules! asm {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Instruction {
     A(Load),
     C(Option<Dest>, Comp, Option<Jump>),
@@ -46,6 +47,7 @@ impl fmt::Display for Instruction {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Load {
     Constant(u16),
     Symbol(String),
@@ -85,6 +87,7 @@ impl fmt::Display for Load {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Dest {
     M,
     D,
@@ -110,6 +113,7 @@ impl fmt::Display for Dest {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Comp {
     Zero,
     One,
@@ -176,6 +180,7 @@ impl fmt::Display for Comp {
     }
 }
 
+// Commutative operations can be written with their operands either way round.
 impl FromStr for Comp {
     type Err = ();
 
@@ -194,27 +199,28 @@ impl FromStr for Comp {
             "A+1" => Ok(Comp::APlusOne),
             "D-1" => Ok(Comp::DMinusOne),
             "A-1" => Ok(Comp::AMinusOne),
-            "D+A" => Ok(Comp::DPlusA),
+            "D+A" | "A+D" => Ok(Comp::DPlusA),
             "D-A" => Ok(Comp::DMinusA),
             "A-D" => Ok(Comp::AMinusD),
-            "D&A" => Ok(Comp::DAndA),
-            "D|A" => Ok(Comp::DOrA),
+            "D&A" | "A&D" => Ok(Comp::DAndA),
+            "D|A" | "A|D" => Ok(Comp::DOrA),
             "M" => Ok(Comp::M),
             "!M" => Ok(Comp::NotM),
             "-M" => Ok(Comp::NegM),
             "M+1" => Ok(Comp::MPlusOne),
             "M-1" => Ok(Comp::MMinusOne),
-            "D+M" => Ok(Comp::DPlusM),
+            "D+M" | "M+D" => Ok(Comp::DPlusM),
             "D-M" => Ok(Comp::DMinusM),
             "M-D" => Ok(Comp::MMinusD),
-            "D&M" => Ok(Comp::DAndM),
-            "D|M" => Ok(Comp::DOrM),
+            "D&M" | "M&D" => Ok(Comp::DAndM),
+            "D|M" | "M|D" => Ok(Comp::DOrM),
             _ => Err(()),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Jump {
     JGT,
     JEQ,
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Error};
+use std::ops::Range;
+use std::str::FromStr;
+
+// The segment pointers and the temp segment always live at the bottom of RAM.
+const REGISTERS: Range<u16> = 0..13;
+
+// The number of consecutive registers set aside for generated code to use as scratch space.
+pub const SCRATCH_REGISTERS: u16 = 3;
+
+// Where each area of a program's data lives in RAM. The default is the layout of the standard
+// Hack machine, as used by the Nand to Tetris course.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemoryLayout {
+    // The stack grows upwards from here until it runs into the heap.
+    pub stack_base: u16,
+    // Exported to programs as the `HEAP_BASE` symbol.
+    pub heap_base: u16,
+    // Static variables are allocated by the assembler from the start of this range.
+    pub statics: Range<u16>,
+    // The first of the scratch registers.
+    pub scratch: u16,
+    pub screen: u16,
+    pub keyboard: u16,
+}
+
+impl MemoryLayout {
+    pub fn new() -> Self {
+        MemoryLayout {
+            stack_base: 256,
+            heap_base: 2048,
+            statics: 16..256,
+            scratch: 13,
+            screen: 0x4000,
+            keyboard: 0x6000,
+        }
+    }
+
+    // Checks that none of the areas overlap, and that the heap, screen and keyboard are in the
+    // order which the OS expects.
+    pub fn validate(&self) -> Result<(), Error> {
+        let areas = [
+            ("registers", REGISTERS),
+            ("scratch registers", self.scratch_registers()),
+            ("static area", self.statics.clone()),
+            ("stack", self.stack_base..self.heap_base),
+        ];
+
+        if self.stack_base >= self.heap_base {
+            return Err(anyhow!(
+                "the stack base ({}) must be below the heap base ({})",
+                self.stack_base,
+                self.heap_base
+            ));
+        }
+
+        if self.heap_base >= self.screen || self.screen >= self.keyboard {
+            return Err(anyhow!(
+                "the heap base ({}), screen ({}) and keyboard ({}) must be in ascending order",
+                self.heap_base,
+                self.screen,
+                self.keyboard
+            ));
+        }
+
+        for (i, (name, area)) in areas.iter().enumerate() {
+            for (other_name, other) in &areas[i + 1..] {
+                if area.start < other.end && other.start < area.end {
+                    return Err(anyhow!(
+                        "the {} at RAM[{}..{}] overlap the {} at RAM[{}..{}]",
+                        name,
+                        area.start,
+                        area.end,
+                        other_name,
+                        other.start,
+                        other.end
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn scratch_registers(&self) -> Range<u16> {
+        self.scratch..self.scratch.saturating_add(SCRATCH_REGISTERS)
+    }
+
+    // The symbols which the assembler predefines for this layout.
+    pub fn symbols(&self) -> Vec<(String, u16)> {
+        let mut symbols = vec![
+            ("SP".to_owned(), 0),
+            ("LCL".to_owned(), 1),
+            ("ARG".to_owned(), 2),
+            ("THIS".to_owned(), 3),
+            ("THAT".to_owned(), 4),
+        ];
+
+        symbols.extend((0..16).map(|n| (format!("R{}", n), n)));
+        symbols.extend(vec![
+            ("SCREEN".to_owned(), self.screen),
+            ("KBD".to_owned(), self.keyboard),
+            ("HEAP_BASE".to_owned(), self.heap_base),
+        ]);
+
+        symbols
+    }
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        MemoryLayout::new()
+    }
+}
+
+// Parses a comma-separated list of changes to the default layout, such as
+// `stack=256,heap=4096,statics=16..256,scratch=13,screen=0x5000,keyboard=0x7000`.
+impl FromStr for MemoryLayout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = MemoryLayout::new();
+
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected `key=value`, found `{}`", setting))?;
+
+            match key.trim() {
+                "stack" => layout.stack_base = parse_addr(value)?,
+                "heap" => layout.heap_base = parse_addr(value)?,
+                "statics" => {
+                    let (start, end) = value
+                        .split_once("..")
+                        .ok_or_else(|| anyhow!("expected `start..end`, found `{}`", value))?;
+                    layout.statics = parse_addr(start)?..parse_addr(end)?;
+                }
+                "scratch" => layout.scratch = parse_addr(value)?,
+                "screen" => layout.screen = parse_addr(value)?,
+                "keyboard" => layout.keyboard = parse_addr(value)?,
+                key => return Err(anyhow!("unknown memory layout setting `{}`", key)),
+            }
+        }
+
+        layout.validate()?;
+        Ok(layout)
+    }
+}
+
+fn parse_addr(s: &str) -> Result<u16, Error> {
+    let s = s.trim();
+    let addr = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    addr.map_err(|_| anyhow!("`{}` is not a valid address", s))
+}
@@ -1,5 +1,11 @@
 mod assembler;
 mod instruction;
+mod layout;
+mod optimizer;
+mod parser;
 
 pub use assembler::*;
 pub use instruction::*;
+pub use layout::*;
+pub use optimizer::*;
+pub use parser::*;
@@ -0,0 +1,276 @@
+use super::instruction::*;
+use crate::asm;
+
+// Gives up after this many rounds. Every pass makes the program shorter whenever it changes
+// anything, so this is only reached by very long chains of improvements.
+const MAX_ROUNDS: usize = 16;
+
+// Removes the waste left behind by translating each VM command on its own, such as a push which
+// is immediately popped again. The program behaves exactly as before, except that values are no
+// longer written to the free RAM just above the top of the stack.
+pub fn optimize(mut prog: Vec<Instruction>) -> Vec<Instruction> {
+    for _ in 0..MAX_ROUNDS {
+        let len = prog.len();
+
+        prog = remove_push_pops(prog);
+        prog = remove_jumps_to_next(prog);
+        prog = remove_unreachable(prog);
+        prog = remove_redundant_loads(prog);
+        prog = remove_dead_stores(prog);
+
+        if prog.len() == len {
+            break;
+        }
+    }
+
+    prog
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Register {
+    A,
+    D,
+    M,
+}
+
+// Whether an instruction uses the value of a register. Anything which touches M, and any jump,
+// uses A as an address.
+fn reads(instr: &Instruction, reg: Register) -> bool {
+    match instr {
+        Instruction::C(dest, comp, jump) => {
+            let addressed = reads_comp(*comp, Register::M)
+                || dest.is_some_and(|dest| writes_dest(dest, Register::M))
+                || jump.is_some();
+
+            reads_comp(*comp, reg) || (reg == Register::A && addressed)
+        }
+        Instruction::A(_) | Instruction::Label(_) => false,
+    }
+}
+
+fn writes(instr: &Instruction, reg: Register) -> bool {
+    match instr {
+        Instruction::A(_) => reg == Register::A,
+        Instruction::C(Some(dest), _, _) => writes_dest(*dest, reg),
+        Instruction::C(None, _, _) | Instruction::Label(_) => false,
+    }
+}
+
+fn reads_comp(comp: Comp, reg: Register) -> bool {
+    match reg {
+        Register::A => matches!(
+            comp,
+            Comp::A
+                | Comp::NotA
+                | Comp::NegA
+                | Comp::APlusOne
+                | Comp::AMinusOne
+                | Comp::DPlusA
+                | Comp::DMinusA
+                | Comp::AMinusD
+                | Comp::DAndA
+                | Comp::DOrA
+        ),
+        Register::D => matches!(
+            comp,
+            Comp::D
+                | Comp::NotD
+                | Comp::NegD
+                | Comp::DPlusOne
+                | Comp::DMinusOne
+                | Comp::DPlusA
+                | Comp::DMinusA
+                | Comp::AMinusD
+                | Comp::DAndA
+                | Comp::DOrA
+                | Comp::DPlusM
+                | Comp::DMinusM
+                | Comp::MMinusD
+                | Comp::DAndM
+                | Comp::DOrM
+        ),
+        Register::M => matches!(
+            comp,
+            Comp::M
+                | Comp::NotM
+                | Comp::NegM
+                | Comp::MPlusOne
+                | Comp::MMinusOne
+                | Comp::DPlusM
+                | Comp::DMinusM
+                | Comp::MMinusD
+                | Comp::DAndM
+                | Comp::DOrM
+        ),
+    }
+}
+
+fn writes_dest(dest: Dest, reg: Register) -> bool {
+    match reg {
+        Register::A => matches!(dest, Dest::A | Dest::AM | Dest::AD | Dest::AMD),
+        Register::D => matches!(dest, Dest::D | Dest::MD | Dest::AD | Dest::AMD),
+        Register::M => matches!(dest, Dest::M | Dest::MD | Dest::AM | Dest::AMD),
+    }
+}
+
+// Replaces a push followed by a pop, or a pop followed by a push, with the few instructions
+// needed to leave A, D and the stack as they would have been.
+fn remove_push_pops(prog: Vec<Instruction>) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(prog.len());
+    let mut i = 0;
+
+    while i < prog.len() {
+        let rest = &prog[i..];
+
+        if let Some(value) = pushed_value(rest) {
+            if is_pop(&rest[4..]) {
+                out.extend(vec![asm!(@"SP"), asm!(A = M)]);
+                if value != Comp::D {
+                    out.push(Instruction::C(Some(Dest::D), value, None));
+                }
+                i += 7;
+                continue;
+            }
+        }
+
+        if is_pop(rest) && pushed_value(&rest[3..]) == Some(Comp::D) {
+            out.extend(vec![asm!(@"SP"), asm!(A = M - 1), asm!(D = M)]);
+            i += 7;
+            continue;
+        }
+
+        out.push(prog[i].clone());
+        i += 1;
+    }
+
+    out
+}
+
+// Matches `@SP; AM=M+1; A=A-1; M=X`, returning X.
+fn pushed_value(prog: &[Instruction]) -> Option<Comp> {
+    match prog {
+        [Instruction::A(Load::Symbol(sp)), Instruction::C(Some(Dest::AM), Comp::MPlusOne, None), Instruction::C(Some(Dest::A), Comp::AMinusOne, None), Instruction::C(Some(Dest::M), value, None), ..]
+            if sp == "SP" && matches!(value, Comp::D | Comp::Zero | Comp::One | Comp::NegOne) =>
+        {
+            Some(*value)
+        }
+        _ => None,
+    }
+}
+
+// Matches `@SP; AM=M-1; D=M`.
+fn is_pop(prog: &[Instruction]) -> bool {
+    matches!(
+        prog,
+        [Instruction::A(Load::Symbol(sp)), Instruction::C(Some(Dest::AM), Comp::MMinusOne, None), Instruction::C(Some(Dest::D), Comp::M, None), ..]
+            if sp == "SP"
+    )
+}
+
+// Removes any jump to a label which comes straight after it. The `@label` before it is left for
+// `remove_dead_stores` to deal with.
+fn remove_jumps_to_next(prog: Vec<Instruction>) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(prog.len());
+
+    for (i, instr) in prog.iter().enumerate() {
+        if let (Some(Instruction::A(Load::Symbol(target))), Instruction::C(None, _, Some(_))) =
+            (i.checked_sub(1).map(|prev| &prog[prev]), instr)
+        {
+            let lands_next = prog[i + 1..]
+                .iter()
+                .map_while(|instr| match instr {
+                    Instruction::Label(label) => Some(label),
+                    _ => None,
+                })
+                .any(|label| label == target);
+
+            if lands_next {
+                continue;
+            }
+        }
+
+        out.push(instr.clone());
+    }
+
+    out
+}
+
+// Removes the instructions after an unconditional jump, up to the next label.
+fn remove_unreachable(prog: Vec<Instruction>) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(prog.len());
+    let mut reachable = true;
+
+    for instr in prog {
+        match instr {
+            Instruction::Label(_) => reachable = true,
+            _ if !reachable => continue,
+            Instruction::C(_, _, Some(Jump::JMP)) => reachable = false,
+            _ => {}
+        }
+
+        out.push(instr);
+    }
+
+    out
+}
+
+// Removes any `@X` when A is already known to hold X. Nothing is known about A after a label, as
+// it may have been reached from anywhere.
+fn remove_redundant_loads(prog: Vec<Instruction>) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(prog.len());
+    let mut known = None;
+
+    for instr in prog {
+        match &instr {
+            Instruction::A(load) if known.as_ref() == Some(load) => continue,
+            Instruction::A(load) => known = Some(load.clone()),
+            Instruction::Label(_) => known = None,
+            instr if writes(instr, Register::A) => known = None,
+            _ => {}
+        }
+
+        out.push(instr);
+    }
+
+    out
+}
+
+// Removes instructions which only write to A or D, when the value written is always overwritten
+// before it's used.
+fn remove_dead_stores(prog: Vec<Instruction>) -> Vec<Instruction> {
+    let registers = [Register::A, Register::D];
+
+    prog.iter()
+        .enumerate()
+        .filter(|(i, instr)| {
+            let only_registers = match instr {
+                Instruction::A(_) => true,
+                Instruction::C(Some(dest), _, None) => !writes_dest(*dest, Register::M),
+                _ => false,
+            };
+
+            let dead = registers
+                .iter()
+                .filter(|reg| writes(instr, **reg))
+                .all(|reg| is_dead(&prog[i + 1..], *reg));
+
+            !(only_registers && dead)
+        })
+        .map(|(_, instr)| instr.clone())
+        .collect()
+}
+
+// Whether a register is always written before it's read, following the instructions in order
+// until reaching a jump. Labels don't matter here, as they only affect how a point is reached.
+fn is_dead(prog: &[Instruction], reg: Register) -> bool {
+    for instr in prog {
+        if reads(instr, reg) || matches!(instr, Instruction::C(_, _, Some(_))) {
+            return false;
+        }
+        if writes(instr, reg) {
+            return true;
+        }
+    }
+
+    false
+}
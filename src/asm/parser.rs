@@ -0,0 +1,127 @@
+use super::instruction::*;
+use anyhow::{anyhow, Error};
+use std::str::FromStr;
+
+// Parses the text of a `.asm` file. Each line holds at most one instruction or label, which may
+// be surrounded by whitespace and followed by a `//` comment. Whitespace is also allowed around
+// the `=` and `;` of a C-instruction and within its computation, but not within a symbol or
+// number.
+pub fn parse(s: &str) -> Result<Vec<Instruction>, Error> {
+    let mut prog = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let instr = line
+            .parse()
+            .map_err(|err| anyhow!("line {}: {}", i + 1, err))?;
+        prog.push(instr);
+    }
+
+    Ok(prog)
+}
+
+impl FromStr for Instruction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(load) = s.strip_prefix('@') {
+            return match load.parse::<u32>() {
+                Ok(n) if n < 0x8000 => Ok(Instruction::A(Load::Constant(n as u16))),
+                Ok(_) => Err(anyhow!(
+                    "`{}` is out of range, as an A-instruction can only load up to 32767",
+                    s
+                )),
+                Err(_) => Ok(Instruction::A(Load::Symbol(parse_symbol(load)?))),
+            };
+        }
+
+        if let Some(label) = s.strip_prefix('(') {
+            return match label.strip_suffix(')') {
+                Some(label) => Ok(Instruction::Label(parse_symbol(label)?)),
+                None => Err(anyhow!("expected `)` at the end of label `{}`", s)),
+            };
+        }
+
+        let (dest, rest) = match s.split_once('=') {
+            Some((dest, rest)) => (Some(dest.trim_end().parse()?), rest.trim_start()),
+            None => (None, s),
+        };
+
+        let (comp, jump) = match rest.split_once(';') {
+            Some((comp, jump)) => (comp.trim_end(), Some(jump.trim_start().parse()?)),
+            None => (rest, None),
+        };
+
+        let comp = comp
+            .parse()
+            .map_err(|_| anyhow!("`{}` is not a valid computation", comp))?;
+
+        Ok(Instruction::C(dest, comp, jump))
+    }
+}
+
+// Symbols are made up of letters, digits, `_`, `.`, `$` and `:`, and can't start with a digit.
+fn parse_symbol(s: &str) -> Result<String, Error> {
+    let valid = s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c))
+        && s.chars().next().is_some_and(|c| !c.is_ascii_digit());
+
+    if valid {
+        Ok(s.to_owned())
+    } else {
+        Err(anyhow!("`{}` is not a valid symbol", s))
+    }
+}
+
+// The registers may be given in any order, such as `DM` for `MD`.
+impl FromStr for Dest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let has = |c| s.contains(c);
+        let len = has('A') as usize + has('D') as usize + has('M') as usize;
+
+        if len != s.len() {
+            return Err(anyhow!("`{}` is not a valid destination", s));
+        }
+
+        match (has('A'), has('D'), has('M')) {
+            (false, false, true) => Ok(Dest::M),
+            (false, true, false) => Ok(Dest::D),
+            (false, true, true) => Ok(Dest::MD),
+            (true, false, false) => Ok(Dest::A),
+            (true, false, true) => Ok(Dest::AM),
+            (true, true, false) => Ok(Dest::AD),
+            (true, true, true) => Ok(Dest::AMD),
+            (false, false, false) => Err(anyhow!("expected a destination before `=`")),
+        }
+    }
+}
+
+impl FromStr for Jump {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "JGT" => Ok(Jump::JGT),
+            "JEQ" => Ok(Jump::JEQ),
+            "JGE" => Ok(Jump::JGE),
+            "JLT" => Ok(Jump::JLT),
+            "JNE" => Ok(Jump::JNE),
+            "JLE" => Ok(Jump::JLE),
+            "JMP" => Ok(Jump::JMP),
+            _ => Err(anyhow!("`{}` is not a valid jump", s)),
+        }
+    }
+}
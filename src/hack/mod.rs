@@ -1,4 +1,4 @@
-const RAM_SIZE: usize = 16 * 1024; // 32KiB
+pub(crate) const RAM_SIZE: usize = 16 * 1024; // 32KiB
 
 pub struct Emulator<'a> {
     pub ram: RAM,
@@ -73,7 +73,7 @@ impl<'a> Emulator<'a> {
 pub struct RAM([u16; RAM_SIZE]);
 
 impl RAM {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self([0; RAM_SIZE])
     }
 
@@ -105,6 +105,7 @@ impl Alu {
         let x = x as i16;
         let y = y as i16;
 
+        // Like the Hack hardware, arithmetic silently wraps around on overflow.
         let result: i16 = match comp {
             [1, 0, 1, 0, 1, 0] => 0,
             [1, 1, 1, 1, 1, 1] => 1,
@@ -113,15 +114,15 @@ impl Alu {
             [1, 1, 0, 0, 0, 0] => y,
             [0, 0, 1, 1, 0, 1] => !x,
             [1, 1, 0, 0, 0, 1] => !y,
-            [0, 0, 1, 1, 1, 1] => -x,
-            [1, 1, 0, 0, 1, 1] => -y,
-            [0, 1, 1, 1, 1, 1] => x + 1,
-            [1, 1, 0, 1, 1, 1] => y + 1,
-            [0, 0, 1, 1, 1, 0] => x - 1,
-            [1, 1, 0, 0, 1, 0] => y - 1,
-            [0, 0, 0, 0, 1, 0] => x + y,
-            [0, 1, 0, 0, 1, 1] => x - y,
-            [0, 0, 0, 1, 1, 1] => y - x,
+            [0, 0, 1, 1, 1, 1] => x.wrapping_neg(),
+            [1, 1, 0, 0, 1, 1] => y.wrapping_neg(),
+            [0, 1, 1, 1, 1, 1] => x.wrapping_add(1),
+            [1, 1, 0, 1, 1, 1] => y.wrapping_add(1),
+            [0, 0, 1, 1, 1, 0] => x.wrapping_sub(1),
+            [1, 1, 0, 0, 1, 0] => y.wrapping_sub(1),
+            [0, 0, 0, 0, 1, 0] => x.wrapping_add(y),
+            [0, 1, 0, 0, 1, 1] => x.wrapping_sub(y),
+            [0, 0, 0, 1, 1, 1] => y.wrapping_sub(x),
             [0, 0, 0, 0, 0, 0] => x & y,
             [0, 1, 0, 1, 0, 1] => x | y,
             _ => panic!("invalid comp fields: {:?}", comp),
@@ -1,15 +1,28 @@
 use super::parser::*;
 use super::symbol_table::{Kind, SymbolTable, Type};
+use super::visit::*;
 use crate::labels::Labeller;
 use crate::vm;
 use anyhow::{anyhow, Error};
 
 type Result<T> = std::result::Result<T, Error>;
 
+// Error codes passed to `Sys.error` by the runtime checks emitted when debug checks are enabled.
+// The standard OS uses codes 1 to 20, so these are kept well clear of that range.
+pub const ERR_NULL_RECEIVER: u16 = 100;
+pub const ERR_NULL_ARRAY: u16 = 101;
+pub const ERR_INDEX_OUT_OF_BOUNDS: u16 = 102;
+
 pub struct Compiler<'a> {
     class: &'a Class,
     symbols: SymbolTable,
     labels: Labeller,
+    debug_checks: bool,
+    annotate: bool,
+    // Set while compiling the static initialisers, which run outside of any object.
+    static_init: bool,
+    cmds: Vec<vm::Command>,
+    comments: Vec<(usize, String)>,
 }
 
 impl<'a> Compiler<'a> {
@@ -18,134 +31,96 @@ impl<'a> Compiler<'a> {
             class,
             symbols: SymbolTable::new(),
             labels: Labeller::new(),
+            debug_checks: false,
+            annotate: false,
+            static_init: false,
+            cmds: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
-    pub fn compile(&mut self) -> Result<vm::Module> {
-        self.symbols.reset();
+    pub fn debug_checks(&mut self, enabled: bool) {
+        self.debug_checks = enabled;
+    }
 
-        for vars in &self.class.vars {
-            for name in &vars.names {
-                self.symbols
-                    .define(name.clone(), Type::from(&vars.typ), Kind::from(&vars.kind))?;
-            }
-        }
+    // Annotates the compiled module with a comment before the code for each statement, showing
+    // the Jack source it was compiled from.
+    pub fn annotate(&mut self, enabled: bool) {
+        self.annotate = enabled;
+    }
 
-        let mut cmds = Vec::new();
+    pub fn compile(&mut self) -> Result<vm::Module> {
+        self.cmds.clear();
+        self.comments.clear();
 
-        for sub in &self.class.subs {
-            cmds.extend(self.compile_subroutine(sub)?);
-        }
+        let class = self.class;
+        self.visit_class(class)?;
 
-        Ok(vm::Module::new(self.class.name.clone(), cmds))
+        Ok(vm::Module {
+            name: class.name.clone(),
+            cmds: std::mem::take(&mut self.cmds),
+            comments: std::mem::take(&mut self.comments),
+        })
     }
 
-    fn compile_subroutine(&mut self, sub: &Subroutine) -> Result<Vec<vm::Command>> {
-        self.symbols.start_subroutine();
-        self.labels.reset();
-
-        let name = format!("{}.{}", self.class.name, sub.name);
-
-        let locals = sub
-            .body
+    fn compile_static_init(&mut self, class: &Class) -> Result<()> {
+        // Classes without any static initialisers don't need an init function at all.
+        if !class
             .vars
             .iter()
-            .map(|vars| vars.names.len() as u16)
-            .sum();
+            .flat_map(|vars| &vars.inits)
+            .any(Option::is_some)
+        {
+            return Ok(());
+        }
 
-        let mut cmds = vec![vm::Command::Function(name, locals)];
+        self.symbols.start_subroutine();
+        self.labels.reset();
 
-        match sub.kind {
-            SubroutineKind::Constructor => {
-                let fields = self
-                    .class
-                    .vars
-                    .iter()
-                    .filter(|vars| vars.kind == ClassVarKind::Field)
-                    .map(|vars| vars.names.len() as u16)
-                    .sum();
+        self.cmds
+            .push(vm::Command::Function(vm::static_init_name(&class.name), 0));
 
-                cmds.extend(vec![
-                    vm::Command::Push(vm::Segment::Constant, fields),
-                    vm::Command::Call("Memory.alloc".to_owned(), 1),
-                    vm::Command::Pop(vm::Segment::Pointer, 0),
-                ]);
-            }
-            SubroutineKind::Method => {
-                // If this is a method, the symbol table must be pre-filled with "this", which
-                // would have been passed in as the first argument. As "this" is a keyword rather
-                // than an identifier, it will never be looked up in the symbol table, but defining
-                // it in the symbol table will have the desired side-effect of causing subsequent
-                // method arguments to start from the index 1 rather than 0.
-                self.symbols.define(
-                    "this".to_owned(),
-                    Type::ClassName(self.class.name.clone()),
-                    Kind::Argument,
-                )?;
+        self.static_init = true;
+        let result = self.compile_static_inits(class);
+        self.static_init = false;
+        result?;
 
-                cmds.extend(vec![
-                    vm::Command::Push(vm::Segment::Argument, 0),
-                    vm::Command::Pop(vm::Segment::Pointer, 0),
-                ]);
-            }
-            _ => {}
-        }
+        self.cmds.extend(vec![
+            vm::Command::Push(vm::Segment::Constant, 0),
+            vm::Command::Return,
+        ]);
 
-        for param in &sub.params {
-            self.symbols
-                .define(param.name.clone(), Type::from(&param.typ), Kind::Argument)?;
-        }
+        Ok(())
+    }
 
-        for vars in &sub.body.vars {
-            for name in &vars.names {
-                self.symbols
-                    .define(name.clone(), Type::from(&vars.typ), Kind::LocalVar)?;
+    fn compile_static_inits(&mut self, class: &Class) -> Result<()> {
+        for vars in &class.vars {
+            for (name, init) in vars.names.iter().zip(&vars.inits) {
+                if let Some(expr) = init {
+                    self.visit_expr(expr)?;
+                    self.emit_var(vm::Command::Pop, name)?;
+                }
             }
         }
 
-        cmds.extend(self.compile_statements(&sub.body.statements)?);
-        Ok(cmds)
+        Ok(())
     }
 
-    fn compile_statements(&mut self, stmts: &[Statement]) -> Result<Vec<vm::Command>> {
-        let mut cmds = Vec::new();
-        for stmt in stmts {
-            cmds.extend(self.compile_statement(stmt)?);
-        }
-        Ok(cmds)
-    }
-
-    fn compile_statement(&mut self, stmt: &Statement) -> Result<Vec<vm::Command>> {
-        match stmt {
-            Statement::Let { lhs, index, rhs } => self.compile_let(lhs, index.as_ref(), rhs),
-            Statement::If {
-                condition,
-                if_body,
-                else_body,
-            } => self.compile_if(condition, if_body, else_body.as_ref()),
-            Statement::While { condition, body } => self.compile_while(condition, body),
-            Statement::Do(call) => self.compile_do(call),
-            Statement::Return(value) => self.compile_return(value.as_ref()),
-        }
-    }
-
-    fn compile_let(&self, lhs: &str, index: Option<&Expr>, rhs: &Expr) -> Result<Vec<vm::Command>> {
-        let mut cmds = self.compile_expr(rhs)?;
+    fn compile_let(&mut self, lhs: &str, index: Option<&Expr>, rhs: &Expr) -> Result<()> {
+        self.visit_expr(rhs)?;
 
         match index {
             Some(expr) => {
-                cmds.push(self.compile_var(vm::Command::Push, lhs)?);
-                cmds.extend(self.compile_expr(expr)?);
-                cmds.extend(vec![
-                    vm::Command::Add,
+                self.compile_index_addr(lhs, expr)?;
+                self.cmds.extend(vec![
                     vm::Command::Pop(vm::Segment::Pointer, 1),
                     vm::Command::Pop(vm::Segment::That, 0),
                 ]);
             }
-            None => cmds.push(self.compile_var(vm::Command::Pop, lhs)?),
+            None => self.emit_var(vm::Command::Pop, lhs)?,
         }
 
-        Ok(cmds)
+        Ok(())
     }
 
     fn compile_if(
@@ -153,137 +128,72 @@ impl<'a> Compiler<'a> {
         condition: &Expr,
         if_body: &[Statement],
         else_body: Option<&Vec<Statement>>,
-    ) -> Result<Vec<vm::Command>> {
+    ) -> Result<()> {
         let true_label = self.labels.generate("IF_TRUE");
         let false_label = self.labels.generate("IF_FALSE");
         let end_label = self.labels.generate("IF_END");
 
-        let mut cmds = self.compile_expr(condition)?;
+        self.visit_expr(condition)?;
 
-        cmds.extend(vec![
+        self.cmds.extend(vec![
             vm::Command::IfGoto(true_label.clone()),
             vm::Command::Goto(false_label.clone()),
             vm::Command::Label(true_label),
         ]);
 
-        cmds.extend(self.compile_statements(if_body)?);
+        self.visit_statements(if_body)?;
 
         match else_body {
             Some(body) => {
-                cmds.extend(vec![
+                self.cmds.extend(vec![
                     vm::Command::Goto(end_label.clone()),
                     vm::Command::Label(false_label),
                 ]);
-                cmds.extend(self.compile_statements(body)?);
-                cmds.push(vm::Command::Label(end_label));
+                self.visit_statements(body)?;
+                self.cmds.push(vm::Command::Label(end_label));
             }
             None => {
-                cmds.push(vm::Command::Label(false_label));
+                self.cmds.push(vm::Command::Label(false_label));
             }
         }
 
-        Ok(cmds)
+        Ok(())
     }
 
-    fn compile_while(&mut self, condition: &Expr, body: &[Statement]) -> Result<Vec<vm::Command>> {
+    fn compile_while(&mut self, condition: &Expr, body: &[Statement]) -> Result<()> {
         let exp_label = self.labels.generate("WHILE_EXP");
         let end_label = self.labels.generate("WHILE_END");
 
-        let mut cmds = vec![vm::Command::Label(exp_label.clone())];
+        self.cmds.push(vm::Command::Label(exp_label.clone()));
 
-        cmds.extend(self.compile_expr(condition)?);
-        cmds.extend(vec![
+        self.visit_expr(condition)?;
+        self.cmds.extend(vec![
             vm::Command::Not,
             vm::Command::IfGoto(end_label.clone()),
         ]);
 
-        cmds.extend(self.compile_statements(body)?);
-        cmds.extend(vec![
+        self.visit_statements(body)?;
+        self.cmds.extend(vec![
             vm::Command::Goto(exp_label),
             vm::Command::Label(end_label),
         ]);
 
-        Ok(cmds)
-    }
-
-    fn compile_do(&self, call: &SubroutineCall) -> Result<Vec<vm::Command>> {
-        let mut cmds = self.compile_subroutine_call(call)?;
-        cmds.push(vm::Command::Pop(vm::Segment::Temp, 0));
-        Ok(cmds)
-    }
-
-    fn compile_return(&self, value: Option<&Expr>) -> Result<Vec<vm::Command>> {
-        let mut cmds = match value {
-            Some(expr) => self.compile_expr(expr)?,
-            None => vec![vm::Command::Push(vm::Segment::Constant, 0)],
-        };
-        cmds.push(vm::Command::Return);
-        Ok(cmds)
-    }
-
-    fn compile_subroutine_call(&self, call: &SubroutineCall) -> Result<Vec<vm::Command>> {
-        let mut cmds = Vec::new();
-        let mut args = call.args.len() as u16;
-        let receiver: &str;
-
-        match call.receiver.as_ref() {
-            Some(recv) => match self.symbols.get(recv) {
-                Some(sym) => {
-                    if let Type::ClassName(class) = &sym.typ {
-                        receiver = class;
-                    } else {
-                        return Err(anyhow!(
-                            "can't call method `{}` on primitive type receiver `{}`",
-                            call.subroutine,
-                            recv
-                        ));
-                    }
-
-                    cmds.push(self.compile_var(vm::Command::Push, recv)?);
-                    args += 1;
-                }
-                None => receiver = recv,
-            },
-            None => {
-                receiver = &self.class.name;
-                cmds.push(vm::Command::Push(vm::Segment::Pointer, 0));
-                args += 1;
-            }
-        }
-
-        for arg in &call.args {
-            cmds.extend(self.compile_expr(arg)?);
-        }
-
-        let name = format!("{}.{}", receiver, call.subroutine);
-        cmds.push(vm::Command::Call(name, args));
-
-        Ok(cmds)
+        Ok(())
     }
 
-    fn compile_expr(&self, expr: &Expr) -> Result<Vec<vm::Command>> {
-        match expr {
-            Expr::Term(term) => self.compile_term(term),
-            Expr::Binary(op, left, right) => {
-                let mut cmds = self.compile_term(left)?;
-                cmds.extend(self.compile_expr(right)?);
-                cmds.push(self.compile_binary_op(*op));
-                Ok(cmds)
-            }
-        }
+    fn compile_do(&mut self, call: &SubroutineCall) -> Result<()> {
+        self.visit_subroutine_call(call)?;
+        self.cmds.push(vm::Command::Pop(vm::Segment::Temp, 0));
+        Ok(())
     }
 
-    fn compile_term(&self, term: &Term) -> Result<Vec<vm::Command>> {
-        match term {
-            Term::IntConst(n) => Ok(vec![self.compile_int_const(*n)]),
-            Term::StrConst(s) => Ok(self.compile_str_const(s)),
-            Term::KeywordConst(kw) => Ok(self.compile_keyword(kw)),
-            Term::Var(name) => Ok(vec![self.compile_var(vm::Command::Push, name)?]),
-            Term::IndexedVar(name, expr) => self.compile_indexed_var(name, expr),
-            Term::SubroutineCall(call) => self.compile_subroutine_call(call),
-            Term::Bracketed(expr) => self.compile_expr(expr),
-            Term::Unary(op, subterm) => self.compile_unary(*op, subterm),
+    fn compile_return(&mut self, value: Option<&Expr>) -> Result<()> {
+        match value {
+            Some(expr) => self.visit_expr(expr)?,
+            None => self.cmds.push(vm::Command::Push(vm::Segment::Constant, 0)),
         }
+        self.cmds.push(vm::Command::Return);
+        Ok(())
     }
 
     fn compile_int_const(&self, n: u16) -> vm::Command {
@@ -330,6 +240,13 @@ impl<'a> Compiler<'a> {
             .get(name)
             .ok_or_else(|| anyhow!("undefined symbol `{}`", name))?;
 
+        if self.static_init && symbol.kind == Kind::Field {
+            return Err(anyhow!(
+                "can't use field `{}` in a static initialiser",
+                name
+            ));
+        }
+
         let segment = match symbol.kind {
             Kind::Argument => vm::Segment::Argument,
             Kind::LocalVar => vm::Segment::Local,
@@ -340,21 +257,80 @@ impl<'a> Compiler<'a> {
         Ok(f(segment, symbol.index))
     }
 
-    fn compile_indexed_var(&self, name: &str, expr: &Expr) -> Result<Vec<vm::Command>> {
-        let mut cmds = vec![self.compile_var(vm::Command::Push, name)?];
-        cmds.extend(self.compile_expr(expr)?);
-        cmds.extend(vec![
-            vm::Command::Add,
+    fn emit_var<F>(&mut self, f: F, name: &str) -> Result<()>
+    where
+        F: Fn(vm::Segment, u16) -> vm::Command,
+    {
+        let cmd = self.compile_var(f, name)?;
+        self.cmds.push(cmd);
+        Ok(())
+    }
+
+    // Pushes the address of the array element `name[expr]` onto the stack.
+    fn compile_index_addr(&mut self, name: &str, expr: &Expr) -> Result<()> {
+        if !self.debug_checks {
+            self.emit_var(vm::Command::Push, name)?;
+            self.visit_expr(expr)?;
+            self.cmds.push(vm::Command::Add);
+            return Ok(());
+        }
+
+        self.compile_null_check(name, ERR_NULL_ARRAY)?;
+
+        // The index is evaluated exactly once and stashed in `temp 1`, as it's needed
+        // for both of the bounds checks and the final address computation. Nothing between the
+        // store and the last load can call out to another function, so it can't be clobbered.
+        let error_label = self.labels.generate("CHECK_ERROR");
+        let ok_label = self.labels.generate("CHECK_OK");
+        let base = self.compile_var(vm::Command::Push, name)?;
+
+        self.visit_expr(expr)?;
+        self.cmds.extend(vec![
+            vm::Command::Pop(vm::Segment::Temp, 1),
+            vm::Command::Push(vm::Segment::Temp, 1),
+            vm::Command::Push(vm::Segment::Constant, 0),
+            vm::Command::Lt,
+            vm::Command::IfGoto(error_label.clone()),
+            // As in the book's implementation, `Memory.alloc` stores the size of each block in
+            // the word immediately preceding it, counting that word too. So the array's length
+            // is `name[-1] - 1`.
+            vm::Command::Push(vm::Segment::Temp, 1),
+            base.clone(),
+            vm::Command::Push(vm::Segment::Constant, 1),
+            vm::Command::Sub,
             vm::Command::Pop(vm::Segment::Pointer, 1),
             vm::Command::Push(vm::Segment::That, 0),
+            vm::Command::Push(vm::Segment::Constant, 1),
+            vm::Command::Sub,
+            vm::Command::Lt,
+            vm::Command::IfGoto(ok_label.clone()),
+            vm::Command::Label(error_label),
+            vm::Command::Push(vm::Segment::Constant, ERR_INDEX_OUT_OF_BOUNDS),
+            vm::Command::Call("Sys.error".to_owned(), 1),
+            vm::Command::Pop(vm::Segment::Temp, 0),
+            vm::Command::Label(ok_label),
+            base,
+            vm::Command::Push(vm::Segment::Temp, 1),
+            vm::Command::Add,
         ]);
-        Ok(cmds)
+
+        Ok(())
     }
 
-    fn compile_unary(&self, op: UnaryOp, term: &Term) -> Result<Vec<vm::Command>> {
-        let mut cmds = self.compile_term(term)?;
-        cmds.push(self.compile_unary_op(op));
-        Ok(cmds)
+    // Calls `Sys.error` with the given code if the variable `name` is null.
+    fn compile_null_check(&mut self, name: &str, code: u16) -> Result<()> {
+        let ok_label = self.labels.generate("CHECK_OK");
+
+        self.emit_var(vm::Command::Push, name)?;
+        self.cmds.extend(vec![
+            vm::Command::IfGoto(ok_label.clone()),
+            vm::Command::Push(vm::Segment::Constant, code),
+            vm::Command::Call("Sys.error".to_owned(), 1),
+            vm::Command::Pop(vm::Segment::Temp, 0),
+            vm::Command::Label(ok_label),
+        ]);
+
+        Ok(())
     }
 
     fn compile_binary_op(&self, op: BinaryOp) -> vm::Command {
@@ -378,3 +354,209 @@ impl<'a> Compiler<'a> {
         }
     }
 }
+
+// The compiler is a visitor which emits VM commands into a buffer as it walks the class. Nodes
+// whose code isn't emitted in source order, such as the labels around loops and conditionals,
+// override the default traversal.
+impl<'a> Visitor for Compiler<'a> {
+    fn visit_class(&mut self, class: &Class) -> Result<()> {
+        self.symbols.reset();
+        walk_class(self, class)?;
+        self.compile_static_init(class)
+    }
+
+    // Static initialisers are compiled separately into the class's init function, so only the
+    // names are visited here.
+    fn visit_class_vars(&mut self, vars: &ClassVars) -> Result<()> {
+        for name in &vars.names {
+            self.symbols
+                .define(name.clone(), Type::from(&vars.typ), Kind::from(&vars.kind))?;
+        }
+        Ok(())
+    }
+
+    fn visit_subroutine(&mut self, sub: &Subroutine) -> Result<()> {
+        self.symbols.start_subroutine();
+        self.labels.reset();
+
+        let name = format!("{}.{}", self.class.name, sub.name);
+
+        let locals = sub
+            .body
+            .vars
+            .iter()
+            .map(|vars| vars.names.len() as u16)
+            .sum();
+
+        self.cmds.push(vm::Command::Function(name, locals));
+
+        match sub.kind {
+            SubroutineKind::Constructor => {
+                let fields = self
+                    .class
+                    .vars
+                    .iter()
+                    .filter(|vars| vars.kind == ClassVarKind::Field)
+                    .map(|vars| vars.names.len() as u16)
+                    .sum();
+
+                self.cmds.extend(vec![
+                    vm::Command::Push(vm::Segment::Constant, fields),
+                    vm::Command::Call("Memory.alloc".to_owned(), 1),
+                    vm::Command::Pop(vm::Segment::Pointer, 0),
+                ]);
+            }
+            SubroutineKind::Method => {
+                // If this is a method, the symbol table must be pre-filled with "this", which
+                // would have been passed in as the first argument. As "this" is a keyword rather
+                // than an identifier, it will never be looked up in the symbol table, but defining
+                // it in the symbol table will have the desired side-effect of causing subsequent
+                // method arguments to start from the index 1 rather than 0.
+                self.symbols.define(
+                    "this".to_owned(),
+                    Type::ClassName(self.class.name.clone()),
+                    Kind::Argument,
+                )?;
+
+                self.cmds.extend(vec![
+                    vm::Command::Push(vm::Segment::Argument, 0),
+                    vm::Command::Pop(vm::Segment::Pointer, 0),
+                ]);
+            }
+            _ => {}
+        }
+
+        walk_subroutine(self, sub)
+    }
+
+    fn visit_param(&mut self, param: &Param) -> Result<()> {
+        self.symbols
+            .define(param.name.clone(), Type::from(&param.typ), Kind::Argument)
+    }
+
+    fn visit_local_vars(&mut self, vars: &LocalVars) -> Result<()> {
+        for name in &vars.names {
+            self.symbols
+                .define(name.clone(), Type::from(&vars.typ), Kind::LocalVar)?;
+        }
+        Ok(())
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) -> Result<()> {
+        if self.annotate {
+            // Compound statements are summarised by their first line, as the statements in their
+            // bodies are annotated separately.
+            let source = stmt.to_string();
+            let line = source.lines().next().unwrap_or_default();
+            self.comments.push((self.cmds.len(), line.to_owned()));
+        }
+
+        match stmt {
+            Statement::Let { lhs, index, rhs } => self.compile_let(lhs, index.as_ref(), rhs),
+            Statement::If {
+                condition,
+                if_body,
+                else_body,
+            } => self.compile_if(condition, if_body, else_body.as_ref()),
+            Statement::While { condition, body } => self.compile_while(condition, body),
+            Statement::Do(call) => self.compile_do(call),
+            Statement::Return(value) => self.compile_return(value.as_ref()),
+        }
+    }
+
+    fn visit_subroutine_call(&mut self, call: &SubroutineCall) -> Result<()> {
+        let mut args = call.args.len() as u16;
+        let receiver: String;
+
+        match call.receiver.as_ref() {
+            Some(recv) => match self.symbols.get(recv) {
+                Some(sym) => {
+                    if let Type::ClassName(class) = &sym.typ {
+                        receiver = class.clone();
+                    } else {
+                        return Err(anyhow!(
+                            "can't call method `{}` on primitive type receiver `{}`",
+                            call.subroutine,
+                            recv
+                        ));
+                    }
+
+                    if self.debug_checks {
+                        self.compile_null_check(recv, ERR_NULL_RECEIVER)?;
+                    }
+
+                    self.emit_var(vm::Command::Push, recv)?;
+                    args += 1;
+                }
+                None => receiver = recv.clone(),
+            },
+            None if self.static_init => {
+                return Err(anyhow!(
+                    "can't call method `{}` in a static initialiser",
+                    call.subroutine
+                ));
+            }
+            None => {
+                receiver = self.class.name.clone();
+                self.cmds.push(vm::Command::Push(vm::Segment::Pointer, 0));
+                args += 1;
+            }
+        }
+
+        walk_subroutine_call(self, call)?;
+
+        let name = format!("{}.{}", receiver, call.subroutine);
+        self.cmds.push(vm::Command::Call(name, args));
+
+        Ok(())
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Result<()> {
+        walk_expr(self, expr)?;
+
+        // Operands are pushed in order by the walk, after which the operator can be applied.
+        if let Expr::Binary(op, _, _) = expr {
+            let cmd = self.compile_binary_op(*op);
+            self.cmds.push(cmd);
+        }
+
+        Ok(())
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Result<()> {
+        match term {
+            Term::IntConst(n) => {
+                let cmd = self.compile_int_const(*n);
+                self.cmds.push(cmd);
+            }
+            Term::StrConst(s) => {
+                let cmds = self.compile_str_const(s);
+                self.cmds.extend(cmds);
+            }
+            Term::KeywordConst(KeywordConst::This) if self.static_init => {
+                return Err(anyhow!("can't use `this` in a static initialiser"));
+            }
+            Term::KeywordConst(kw) => {
+                let cmds = self.compile_keyword(kw);
+                self.cmds.extend(cmds);
+            }
+            Term::Var(name) => self.emit_var(vm::Command::Push, name)?,
+            Term::IndexedVar(name, expr) => {
+                self.compile_index_addr(name, expr)?;
+                self.cmds.extend(vec![
+                    vm::Command::Pop(vm::Segment::Pointer, 1),
+                    vm::Command::Push(vm::Segment::That, 0),
+                ]);
+            }
+            Term::SubroutineCall(call) => self.visit_subroutine_call(call)?,
+            Term::Bracketed(expr) => self.visit_expr(expr)?,
+            Term::Unary(op, subterm) => {
+                self.visit_term(subterm)?;
+                let cmd = self.compile_unary_op(*op);
+                self.cmds.push(cmd);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use super::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxParser};
+use super::tokenizer::{Keyword, SourceToken, Token, Tokenizer, Trivia};
+use super::xml::escape;
+use anyhow::{anyhow, Error};
+
+// A titled group of class members.
+type Section<'a> = (&'static str, Vec<&'a MemberDoc>);
+
+// A class along with the doc comments attached to it and to each of its members. A doc comment
+// is a block comment starting with `/**` which immediately precedes a declaration.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ClassDoc {
+    pub name: String,
+    pub doc: Option<String>,
+    pub vars: Vec<MemberDoc>,
+    pub subs: Vec<MemberDoc>,
+}
+
+// A class variable or subroutine declaration, along with its doc comment.
+#[derive(Debug, Eq, PartialEq)]
+pub struct MemberDoc {
+    // The keyword the declaration starts with, such as `field` or `method`.
+    pub kind: Keyword,
+    // The declaration as it's shown in the reference, such as `method void add(int n)`, without
+    // any initialisers or the subroutine's body.
+    pub signature: String,
+    pub doc: Option<String>,
+}
+
+impl ClassDoc {
+    // Only the syntax tree keeps hold of comments, so the docs are built from it alone rather
+    // than from the AST.
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let (tokens, trailing) = Tokenizer::new(source).tokenize_with_trivia()?;
+        let tree = SyntaxParser::new(tokens, trailing).parse()?;
+
+        let name = tree
+            .root
+            .children
+            .iter()
+            .find_map(|child| match child {
+                SyntaxElement::Token(SourceToken {
+                    token: Token::Identifier(name),
+                    ..
+                }) => Some(name.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("missing class name"))?;
+
+        let members = |kind| {
+            tree.root
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    SyntaxElement::Node(node) if node.kind == kind => Some(member_doc(node)),
+                    _ => None,
+                })
+                .collect::<Result<_, _>>()
+        };
+
+        Ok(ClassDoc {
+            name,
+            doc: doc_comment(&tree.root),
+            vars: members(SyntaxKind::ClassVarDec)?,
+            subs: members(SyntaxKind::SubroutineDec)?,
+        })
+    }
+
+    // Groups the class's members into the sections of its reference page, leaving out any
+    // empty sections.
+    fn sections(&self) -> Vec<Section<'_>> {
+        let subs = |kind| {
+            self.subs
+                .iter()
+                .filter(|sub| sub.kind == kind)
+                .collect::<Vec<_>>()
+        };
+
+        vec![
+            ("Fields", self.vars.iter().collect()),
+            ("Constructors", subs(Keyword::Constructor)),
+            ("Methods", subs(Keyword::Method)),
+            ("Functions", subs(Keyword::Function)),
+        ]
+        .into_iter()
+        .filter(|(_, members)| !members.is_empty())
+        .collect()
+    }
+}
+
+pub fn docs_to_markdown(classes: &[ClassDoc]) -> String {
+    let mut out = String::new();
+
+    for (i, class) in classes.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+
+        out.push_str(&format!("# {}\n", class.name));
+
+        if let Some(doc) = &class.doc {
+            out.push_str(&format!("\n{}\n", doc));
+        }
+
+        for (title, members) in class.sections() {
+            out.push_str(&format!("\n## {}\n", title));
+
+            for member in members {
+                out.push_str(&format!("\n### `{}`\n", member.signature));
+
+                if let Some(doc) = &member.doc {
+                    out.push_str(&format!("\n{}\n", doc));
+                }
+            }
+        }
+    }
+
+    out
+}
+
+pub fn docs_to_html(classes: &[ClassDoc]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>API Reference</title>\n</head>\n<body>\n",
+    );
+
+    out.push_str("<nav>\n<ul>\n");
+    for class in classes {
+        let name = escape(&class.name);
+        out.push_str(&format!("<li><a href=\"#{}\">{}</a></li>\n", name, name));
+    }
+    out.push_str("</ul>\n</nav>\n");
+
+    for class in classes {
+        let name = escape(&class.name);
+        out.push_str(&format!("<section id=\"{}\">\n<h1>{}</h1>\n", name, name));
+
+        if let Some(doc) = &class.doc {
+            out.push_str(&html_paragraphs(doc));
+        }
+
+        for (title, members) in class.sections() {
+            out.push_str(&format!("<h2>{}</h2>\n<dl>\n", title));
+
+            for member in members {
+                out.push_str(&format!(
+                    "<dt><code>{}</code></dt>\n",
+                    escape(&member.signature)
+                ));
+
+                if let Some(doc) = &member.doc {
+                    out.push_str(&format!("<dd>\n{}</dd>\n", html_paragraphs(doc)));
+                }
+            }
+
+            out.push_str("</dl>\n");
+        }
+
+        out.push_str("</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn html_paragraphs(text: &str) -> String {
+    text.split("\n\n")
+        .filter(|para| !para.trim().is_empty())
+        .map(|para| format!("<p>{}</p>\n", escape(para)))
+        .collect()
+}
+
+fn member_doc(node: &SyntaxNode) -> Result<MemberDoc, Error> {
+    let kind = match node.tokens().first().map(|token| &token.token) {
+        Some(Token::Keyword(keyword)) => *keyword,
+        _ => return Err(anyhow!("expected a declaration")),
+    };
+
+    // A class variable's initialisers and a subroutine's body are left out of the signature.
+    let mut tokens = Vec::new();
+    for child in &node.children {
+        match child {
+            SyntaxElement::Token(token) if token.token != Token::Symbol('=') => tokens.push(token),
+            SyntaxElement::Node(node) if node.kind == SyntaxKind::ParameterList => {
+                tokens.extend(node.tokens())
+            }
+            _ => {}
+        }
+    }
+
+    Ok(MemberDoc {
+        kind,
+        signature: signature(&tokens),
+        doc: doc_comment(node),
+    })
+}
+
+// Joins the tokens of a declaration with single spaces, other than around brackets and before
+// commas, and without the closing semicolon.
+fn signature(tokens: &[&SourceToken]) -> String {
+    let mut sig = String::new();
+
+    for token in tokens {
+        match token.token {
+            Token::Symbol(';') => continue,
+            Token::Symbol('(') | Token::Symbol(')') | Token::Symbol(',') => {}
+            _ if sig.is_empty() || sig.ends_with('(') => {}
+            _ => sig.push(' '),
+        }
+        sig.push_str(&token.text);
+    }
+
+    sig
+}
+
+// Finds the doc comment immediately preceding the first token of a declaration, if there is one.
+// Any other comment in between means the doc comment belongs to something else.
+fn doc_comment(node: &SyntaxNode) -> Option<String> {
+    let token = node.tokens().into_iter().next()?;
+
+    let comment = match token
+        .leading
+        .iter()
+        .rev()
+        .find(|trivia| !matches!(trivia, Trivia::Whitespace(_)))?
+    {
+        Trivia::BlockComment(comment) => comment,
+        _ => return None,
+    };
+
+    let text = comment.strip_prefix("/**")?.strip_suffix("*/")?;
+
+    // Strip the asterisks conventionally used to line up each line of the comment.
+    let lines = text
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+
+    let text = lines.join("\n").trim().to_owned();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
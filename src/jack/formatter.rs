@@ -0,0 +1,243 @@
+use super::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxTree};
+use super::tokenizer::{Keyword, SourceToken, Token, Trivia};
+
+const INDENT: &str = "    ";
+
+// Jack source is formatted as a flat sequence of tokens, where the only structural information
+// needed from the syntax tree is whether an operator is unary and where class members begin.
+enum Item<'a> {
+    Token(&'a SourceToken, bool),
+    BlankLine,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Break {
+    None,
+    Space,
+    Newline,
+}
+
+pub struct Formatter<'a> {
+    tree: &'a SyntaxTree,
+    out: String,
+    indent: usize,
+    blank_line: bool,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(tree: &'a SyntaxTree) -> Self {
+        Formatter {
+            tree,
+            out: String::new(),
+            indent: 0,
+            blank_line: false,
+        }
+    }
+
+    pub fn format(&mut self) -> String {
+        self.out.clear();
+        self.indent = 0;
+
+        let tree = self.tree;
+        let mut items = Vec::new();
+        flatten(&tree.root, false, &mut items);
+
+        let mut prev: Option<(&Token, bool)> = None;
+
+        for item in items {
+            match item {
+                Item::BlankLine => self.blank_line = true,
+                Item::Token(source, unary) => {
+                    let token = &source.token;
+
+                    let brk = match prev {
+                        None => Break::None,
+                        Some(_) if *token == Token::Symbol('}') => Break::Newline,
+                        Some((Token::Symbol('{'), _)) | Some((Token::Symbol(';'), _)) => {
+                            Break::Newline
+                        }
+                        Some((Token::Symbol('}'), _))
+                            if *token != Token::Keyword(Keyword::Else) =>
+                        {
+                            Break::Newline
+                        }
+                        Some((prev, prev_unary)) => {
+                            if needs_space(prev, prev_unary, token) {
+                                Break::Space
+                            } else {
+                                Break::None
+                            }
+                        }
+                    };
+
+                    let closing = *token == Token::Symbol('}');
+                    self.trivia(&source.leading, brk, closing);
+                    self.write(&source.text);
+
+                    if *token == Token::Symbol('{') {
+                        self.indent += 1;
+                    }
+
+                    prev = Some((token, unary));
+                }
+            }
+        }
+
+        self.trivia(&tree.trailing, Break::Newline, true);
+
+        let mut out = std::mem::take(&mut self.out);
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+
+    // Writes out any comments found in the trivia preceding a token, followed by the break
+    // that separates the token from whatever was written before it. Comments before a closing
+    // brace are still inside the block, so the block is only dedented after them.
+    fn trivia(&mut self, trivia: &[Trivia], mut brk: Break, closing: bool) {
+        let mut newlines = 0;
+
+        for trivia in trivia {
+            match trivia {
+                Trivia::Whitespace(ws) => newlines += ws.matches('\n').count(),
+                Trivia::LineComment(comment) | Trivia::BlockComment(comment) => {
+                    if newlines == 0 && !self.out.is_empty() {
+                        // The comment sits on the same line as the preceding token, so keep it
+                        // there rather than moving it onto a line of its own.
+                        if brk != Break::None {
+                            self.out.push(' ');
+                        }
+                        self.out.push_str(comment.trim_end());
+
+                        if let Trivia::LineComment(_) = trivia {
+                            brk = Break::Newline;
+                        } else if brk == Break::None {
+                            brk = Break::Space;
+                        }
+                    } else {
+                        self.line_break(newlines, false);
+                        self.write_comment(comment);
+                        brk = Break::Newline;
+                    }
+
+                    newlines = 0;
+                }
+            }
+        }
+
+        if closing {
+            self.indent = self.indent.saturating_sub(1);
+        }
+
+        match brk {
+            Break::Newline => self.line_break(newlines, closing),
+            Break::Space => self.out.push(' '),
+            Break::None => {}
+        }
+    }
+
+    fn line_break(&mut self, newlines: usize, closing: bool) {
+        if self.out.is_empty() {
+            return;
+        }
+
+        self.out.push('\n');
+
+        // Preserve a single blank line wherever the source had one or more, except at the very
+        // start or end of a block.
+        let blank = self.blank_line || (newlines > 1 && !closing);
+        if blank && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+
+        self.blank_line = false;
+    }
+
+    fn write_comment(&mut self, comment: &str) {
+        for (i, line) in comment.lines().enumerate() {
+            if i == 0 {
+                self.write(line.trim_end());
+                continue;
+            }
+
+            self.out.push('\n');
+
+            // Continuation lines of block comments are re-indented to line up with the first,
+            // with leading asterisks offset by a single space.
+            let line = line.trim();
+            if !line.is_empty() {
+                if line.starts_with('*') {
+                    self.write(&format!(" {}", line));
+                } else {
+                    self.write(line);
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, s: &str) {
+        if self.out.is_empty() || self.out.ends_with('\n') {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+        }
+        self.out.push_str(s);
+    }
+}
+
+fn flatten<'a>(node: &'a SyntaxNode, unary: bool, items: &mut Vec<Item<'a>>) {
+    let mut members = 0;
+
+    for child in &node.children {
+        match child {
+            SyntaxElement::Token(token) => items.push(Item::Token(token, unary)),
+            SyntaxElement::Node(child) => {
+                // Subroutines are always separated from the class members before them by a
+                // blank line.
+                if child.kind == SyntaxKind::SubroutineDec && members > 0 {
+                    items.push(Item::BlankLine);
+                }
+
+                if let SyntaxKind::ClassVarDec | SyntaxKind::SubroutineDec = child.kind {
+                    members += 1;
+                }
+
+                flatten(child, is_unary_term(child), items);
+            }
+        }
+    }
+}
+
+// A term starting with an operator is a unary operation, and only its leading operator should
+// be marked as unary.
+fn is_unary_term(node: &SyntaxNode) -> bool {
+    node.kind == SyntaxKind::Term
+        && matches!(
+            node.children.first(),
+            Some(SyntaxElement::Token(SourceToken {
+                token: Token::Symbol('-'),
+                ..
+            })) | Some(SyntaxElement::Token(SourceToken {
+                token: Token::Symbol('~'),
+                ..
+            }))
+        )
+}
+
+fn needs_space(prev: &Token, prev_unary: bool, next: &Token) -> bool {
+    match (prev, next) {
+        (_, Token::Symbol(';'))
+        | (_, Token::Symbol(','))
+        | (_, Token::Symbol(')'))
+        | (_, Token::Symbol(']'))
+        | (_, Token::Symbol('.'))
+        | (Token::Symbol('('), _)
+        | (Token::Symbol('['), _)
+        | (Token::Symbol('.'), _) => false,
+        (Token::Symbol('-'), _) | (Token::Symbol('~'), _) if prev_unary => false,
+        (Token::Identifier(_), Token::Symbol('(')) | (Token::Identifier(_), Token::Symbol('[')) => {
+            false
+        }
+        _ => true,
+    }
+}
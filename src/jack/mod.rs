@@ -1,9 +1,19 @@
 mod compiler;
+mod doc;
+mod formatter;
 mod parser;
 mod symbol_table;
+mod syntax;
 mod tokenizer;
+mod visit;
+mod xml;
 
 pub use compiler::*;
+pub use doc::*;
+pub use formatter::*;
 pub use parser::*;
 pub use symbol_table::*;
+pub use syntax::*;
 pub use tokenizer::*;
+pub use visit::*;
+pub use xml::*;
@@ -1,24 +1,69 @@
 use super::tokenizer::{Keyword, Token};
 use anyhow::{anyhow, Error};
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::iter::Peekable;
 use std::vec::IntoIter;
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Class {
     pub name: String,
     pub vars: Vec<ClassVars>,
     pub subs: Vec<Subroutine>,
 }
 
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "class {} {{", self.name)?;
+
+        for vars in &self.vars {
+            writeln!(f, "{}{}", INDENT, vars)?;
+        }
+
+        for (i, sub) in self.subs.iter().enumerate() {
+            if i > 0 || !self.vars.is_empty() {
+                writeln!(f)?;
+            }
+            write_subroutine(f, sub, 1)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ClassVars {
     pub kind: ClassVarKind,
     pub typ: VarType,
     pub names: Vec<String>,
+    // Initialisers for each of the declared names, in the same order. Only static variables
+    // may have an initialiser.
+    pub inits: Vec<Option<Expr>>,
+}
+
+impl fmt::Display for ClassVars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ", self.kind, self.typ)?;
+
+        for (i, (name, init)) in self.names.iter().zip(&self.inits).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            match init {
+                Some(expr) => write!(f, "{} = {}", name, expr)?,
+                None => write!(f, "{}", name)?,
+            }
+        }
+
+        write!(f, ";")
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Subroutine {
     pub kind: SubroutineKind,
     pub typ: SubroutineType,
@@ -27,37 +72,88 @@ pub struct Subroutine {
     pub body: SubroutineBody,
 }
 
+impl fmt::Display for Subroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_subroutine(f, self, 0)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Param {
     pub typ: VarType,
     pub name: String,
 }
 
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.typ, self.name)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SubroutineBody {
     pub vars: Vec<LocalVars>,
     pub statements: Vec<Statement>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LocalVars {
     pub typ: VarType,
     pub names: Vec<String>,
 }
 
+impl fmt::Display for LocalVars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "var {} {};", self.typ, self.names.join(", "))
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SubroutineCall {
     pub receiver: Option<String>,
     pub subroutine: String,
     pub args: Vec<Expr>,
 }
 
+impl fmt::Display for SubroutineCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(receiver) = &self.receiver {
+            write!(f, "{}.", receiver)?;
+        }
+
+        write!(f, "{}(", self.subroutine)?;
+
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ClassVarKind {
     Field,
     Static,
 }
 
+impl fmt::Display for ClassVarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassVarKind::Field => write!(f, "field"),
+            ClassVarKind::Static => write!(f, "static"),
+        }
+    }
+}
+
 impl TryFrom<Keyword> for ClassVarKind {
     type Error = Error;
 
@@ -74,6 +170,7 @@ impl TryFrom<Keyword> for ClassVarKind {
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum VarType {
     Int,
     Char,
@@ -81,6 +178,17 @@ pub enum VarType {
     ClassName(String),
 }
 
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarType::Int => write!(f, "int"),
+            VarType::Char => write!(f, "char"),
+            VarType::Boolean => write!(f, "boolean"),
+            VarType::ClassName(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 impl TryFrom<Token> for VarType {
     type Error = Error;
 
@@ -99,12 +207,23 @@ impl TryFrom<Token> for VarType {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SubroutineKind {
     Constructor,
     Function,
     Method,
 }
 
+impl fmt::Display for SubroutineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubroutineKind::Constructor => write!(f, "constructor"),
+            SubroutineKind::Function => write!(f, "function"),
+            SubroutineKind::Method => write!(f, "method"),
+        }
+    }
+}
+
 impl TryFrom<Keyword> for SubroutineKind {
     type Error = Error;
 
@@ -122,11 +241,21 @@ impl TryFrom<Keyword> for SubroutineKind {
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SubroutineType {
     Void,
     NonVoid(VarType),
 }
 
+impl fmt::Display for SubroutineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubroutineType::Void => write!(f, "void"),
+            SubroutineType::NonVoid(typ) => write!(f, "{}", typ),
+        }
+    }
+}
+
 impl TryFrom<Token> for SubroutineType {
     type Error = Error;
 
@@ -139,6 +268,7 @@ impl TryFrom<Token> for SubroutineType {
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Statement {
     Let {
         lhs: String,
@@ -158,7 +288,14 @@ pub enum Statement {
     Return(Option<Expr>),
 }
 
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_statement(f, self, 0)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Term {
     IntConst(u16),
     StrConst(String),
@@ -170,13 +307,42 @@ pub enum Term {
     Unary(UnaryOp, Box<Term>),
 }
 
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::IntConst(n) => write!(f, "{}", n),
+            // This is safe because Jack string literals can't contain escaped double quotes.
+            Term::StrConst(s) => write!(f, "\"{}\"", s),
+            Term::KeywordConst(kw) => write!(f, "{}", kw),
+            Term::Var(name) => write!(f, "{}", name),
+            Term::IndexedVar(name, index) => write!(f, "{}[{}]", name, index),
+            Term::SubroutineCall(call) => write!(f, "{}", call),
+            Term::Bracketed(expr) => write!(f, "({})", expr),
+            Term::Unary(op, term) => write!(f, "{}{}", op, term),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr {
     Term(Term),
     Binary(BinaryOp, Term, Box<Expr>),
 }
 
+// As Jack has no operator precedence, binary expressions are always parsed right-to-left, and
+// so a right-nested expression can be printed out without any additional brackets.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Term(term) => write!(f, "{}", term),
+            Expr::Binary(op, term, rest) => write!(f, "{} {} {}", term, op, rest),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum KeywordConst {
     True,
     False,
@@ -184,6 +350,17 @@ pub enum KeywordConst {
     This,
 }
 
+impl fmt::Display for KeywordConst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeywordConst::True => write!(f, "true"),
+            KeywordConst::False => write!(f, "false"),
+            KeywordConst::Null => write!(f, "null"),
+            KeywordConst::This => write!(f, "this"),
+        }
+    }
+}
+
 impl TryFrom<Keyword> for KeywordConst {
     type Error = Error;
 
@@ -202,11 +379,21 @@ impl TryFrom<Keyword> for KeywordConst {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryOp {
     Minus,
     Not,
 }
 
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Minus => write!(f, "-"),
+            UnaryOp::Not => write!(f, "~"),
+        }
+    }
+}
+
 impl TryFrom<Token> for UnaryOp {
     type Error = Error;
 
@@ -220,6 +407,7 @@ impl TryFrom<Token> for UnaryOp {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryOp {
     Add,
     Subtract,
@@ -232,6 +420,22 @@ pub enum BinaryOp {
     Equal,
 }
 
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Subtract => write!(f, "-"),
+            BinaryOp::Multiply => write!(f, "*"),
+            BinaryOp::Divide => write!(f, "/"),
+            BinaryOp::And => write!(f, "&"),
+            BinaryOp::Or => write!(f, "|"),
+            BinaryOp::LessThan => write!(f, "<"),
+            BinaryOp::GreaterThan => write!(f, ">"),
+            BinaryOp::Equal => write!(f, "="),
+        }
+    }
+}
+
 impl TryFrom<Token> for BinaryOp {
     type Error = Error;
 
@@ -279,15 +483,57 @@ impl Parser {
         while let Token::Keyword(Keyword::Field) | Token::Keyword(Keyword::Static) = self.peek()? {
             let kind = self.parse_from_keyword()?;
             let typ = self.parse_from_token()?;
-            let names = self.parse_identifiers_list()?;
+            let (names, inits) = self.parse_class_var_names(kind)?;
             self.expect_symbol(';')?;
 
-            vars.push(ClassVars { kind, typ, names });
+            vars.push(ClassVars {
+                kind,
+                typ,
+                names,
+                inits,
+            });
         }
 
         Ok(vars)
     }
 
+    fn parse_class_var_names(
+        &mut self,
+        kind: ClassVarKind,
+    ) -> Result<(Vec<String>, Vec<Option<Expr>>), Error> {
+        let mut names = Vec::new();
+        let mut inits = Vec::new();
+
+        loop {
+            let name = self.consume_identifier()?;
+
+            let init = match self.peek()? {
+                Token::Symbol('=') if kind == ClassVarKind::Static => {
+                    self.consume()?;
+                    Some(self.parse_expr()?)
+                }
+                Token::Symbol('=') => {
+                    return Err(anyhow!(
+                        "field `{}` can't have an initialiser, only static variables can",
+                        name
+                    ))
+                }
+                _ => None,
+            };
+
+            names.push(name);
+            inits.push(init);
+
+            match self.peek()? {
+                Token::Symbol(',') => self.consume()?,
+                Token::Symbol(';') => break,
+                token => return Err(anyhow!("expected either `,` or `;`, found `{}`", token)),
+            };
+        }
+
+        Ok((names, inits))
+    }
+
     fn parse_identifiers_list(&mut self) -> Result<Vec<String>, Error> {
         let mut names = vec![self.consume_identifier()?];
 
@@ -621,3 +867,84 @@ impl Parser {
             .ok_or_else(|| anyhow!("unexpected end of file"))
     }
 }
+
+const INDENT: &str = "    ";
+
+fn write_indent(f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        write!(f, "{}", INDENT)?;
+    }
+    Ok(())
+}
+
+fn write_subroutine(f: &mut fmt::Formatter<'_>, sub: &Subroutine, depth: usize) -> fmt::Result {
+    write_indent(f, depth)?;
+    write!(f, "{} {} {}(", sub.kind, sub.typ, sub.name)?;
+
+    for (i, param) in sub.params.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", param)?;
+    }
+
+    writeln!(f, ") {{")?;
+
+    for vars in &sub.body.vars {
+        write_indent(f, depth + 1)?;
+        writeln!(f, "{}", vars)?;
+    }
+
+    write_statements(f, &sub.body.statements, depth + 1)?;
+
+    write_indent(f, depth)?;
+    writeln!(f, "}}")
+}
+
+fn write_statements(f: &mut fmt::Formatter<'_>, stmts: &[Statement], depth: usize) -> fmt::Result {
+    for stmt in stmts {
+        write_indent(f, depth)?;
+        write_statement(f, stmt, depth)?;
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+// Writes out a statement, assuming the cursor is already indented to the given depth. Nested
+// blocks are indented one level deeper, and the closing brace is left without a newline.
+fn write_statement(f: &mut fmt::Formatter<'_>, stmt: &Statement, depth: usize) -> fmt::Result {
+    match stmt {
+        Statement::Let { lhs, index, rhs } => match index {
+            Some(index) => write!(f, "let {}[{}] = {};", lhs, index, rhs),
+            None => write!(f, "let {} = {};", lhs, rhs),
+        },
+        Statement::If {
+            condition,
+            if_body,
+            else_body,
+        } => {
+            writeln!(f, "if ({}) {{", condition)?;
+            write_statements(f, if_body, depth + 1)?;
+            write_indent(f, depth)?;
+            write!(f, "}}")?;
+
+            if let Some(body) = else_body {
+                writeln!(f, " else {{")?;
+                write_statements(f, body, depth + 1)?;
+                write_indent(f, depth)?;
+                write!(f, "}}")?;
+            }
+
+            Ok(())
+        }
+        Statement::While { condition, body } => {
+            writeln!(f, "while ({}) {{", condition)?;
+            write_statements(f, body, depth + 1)?;
+            write_indent(f, depth)?;
+            write!(f, "}}")
+        }
+        Statement::Do(call) => write!(f, "do {};", call),
+        Statement::Return(Some(expr)) => write!(f, "return {};", expr),
+        Statement::Return(None) => write!(f, "return;"),
+    }
+}
@@ -0,0 +1,484 @@
+use super::tokenizer::{Keyword, SourceToken, Token, Trivia};
+use anyhow::{anyhow, Error};
+use std::fmt;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+// A concrete syntax tree, which unlike `Class` keeps hold of every token in the source, along
+// with all of the whitespace and comments in between. Printing a `SyntaxTree` reproduces the
+// original source text exactly.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    pub trailing: Vec<Trivia>,
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)?;
+        for trivia in &self.trailing {
+            write!(f, "{}", trivia)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: SyntaxKind) -> Self {
+        SyntaxNode {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn tokens(&self) -> Vec<&SourceToken> {
+        let mut tokens = Vec::new();
+
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{}", node)?,
+                SyntaxElement::Token(token) => write!(f, "{}", token)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SourceToken),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyntaxKind {
+    Class,
+    ClassVarDec,
+    SubroutineDec,
+    ParameterList,
+    SubroutineBody,
+    VarDec,
+    Statements,
+    LetStatement,
+    IfStatement,
+    WhileStatement,
+    DoStatement,
+    ReturnStatement,
+    Expression,
+    Term,
+    ExpressionList,
+}
+
+pub struct SyntaxParser {
+    tokens: Peekable<IntoIter<SourceToken>>,
+    trailing: Vec<Trivia>,
+}
+
+impl SyntaxParser {
+    pub fn new(tokens: Vec<SourceToken>, trailing: Vec<Trivia>) -> Self {
+        let tokens = tokens.into_iter().peekable();
+        SyntaxParser { tokens, trailing }
+    }
+
+    pub fn parse(&mut self) -> Result<SyntaxTree, Error> {
+        let mut class = SyntaxNode::new(SyntaxKind::Class);
+
+        self.expect(&mut class, &Token::Keyword(Keyword::Class))?;
+        self.expect_identifier(&mut class)?;
+        self.expect_symbol(&mut class, '{')?;
+
+        while let Token::Keyword(Keyword::Field) | Token::Keyword(Keyword::Static) = self.peek()? {
+            let vars = self.parse_class_var_dec()?;
+            class.children.push(SyntaxElement::Node(vars));
+        }
+
+        while self.peek_symbol('}').is_none() {
+            let sub = self.parse_subroutine_dec()?;
+            class.children.push(SyntaxElement::Node(sub));
+        }
+
+        self.expect_symbol(&mut class, '}')?;
+
+        if let Some(token) = self.tokens.next() {
+            return Err(anyhow!("expected end of file, found `{}`", token.token));
+        }
+
+        Ok(SyntaxTree {
+            root: class,
+            trailing: std::mem::take(&mut self.trailing),
+        })
+    }
+
+    fn parse_class_var_dec(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::ClassVarDec);
+
+        self.consume_into(&mut node)?;
+        self.consume_type(&mut node)?;
+
+        loop {
+            self.expect_identifier(&mut node)?;
+
+            if self.peek_symbol('=').is_some() {
+                self.consume_into(&mut node)?;
+                let expr = self.parse_expr()?;
+                node.children.push(SyntaxElement::Node(expr));
+            }
+
+            match self.peek()? {
+                Token::Symbol(',') => self.consume_into(&mut node)?,
+                _ => break,
+            }
+        }
+
+        self.expect_symbol(&mut node, ';')?;
+        Ok(node)
+    }
+
+    fn parse_subroutine_dec(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::SubroutineDec);
+
+        match self.peek()? {
+            Token::Keyword(Keyword::Constructor)
+            | Token::Keyword(Keyword::Function)
+            | Token::Keyword(Keyword::Method) => self.consume_into(&mut node)?,
+            token => {
+                return Err(anyhow!(
+                    "expected one of `constructor`, `function`, `method`, found `{}`",
+                    token
+                ))
+            }
+        }
+
+        self.consume_type(&mut node)?;
+        self.expect_identifier(&mut node)?;
+
+        self.expect_symbol(&mut node, '(')?;
+        let params = self.parse_params()?;
+        node.children.push(SyntaxElement::Node(params));
+        self.expect_symbol(&mut node, ')')?;
+
+        let body = self.parse_subroutine_body()?;
+        node.children.push(SyntaxElement::Node(body));
+
+        Ok(node)
+    }
+
+    fn parse_params(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::ParameterList);
+
+        if self.peek_symbol(')').is_none() {
+            loop {
+                self.consume_type(&mut node)?;
+                self.expect_identifier(&mut node)?;
+
+                match self.peek()? {
+                    Token::Symbol(',') => self.consume_into(&mut node)?,
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn parse_subroutine_body(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::SubroutineBody);
+
+        self.expect_symbol(&mut node, '{')?;
+
+        while let Token::Keyword(Keyword::Var) = self.peek()? {
+            let vars = self.parse_var_dec()?;
+            node.children.push(SyntaxElement::Node(vars));
+        }
+
+        let statements = self.parse_statements()?;
+        node.children.push(SyntaxElement::Node(statements));
+        self.expect_symbol(&mut node, '}')?;
+
+        Ok(node)
+    }
+
+    fn parse_var_dec(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::VarDec);
+
+        self.consume_into(&mut node)?;
+        self.consume_type(&mut node)?;
+
+        loop {
+            self.expect_identifier(&mut node)?;
+
+            match self.peek()? {
+                Token::Symbol(',') => self.consume_into(&mut node)?,
+                _ => break,
+            }
+        }
+
+        self.expect_symbol(&mut node, ';')?;
+        Ok(node)
+    }
+
+    fn parse_block(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        self.expect_symbol(node, '{')?;
+        let statements = self.parse_statements()?;
+        node.children.push(SyntaxElement::Node(statements));
+        self.expect_symbol(node, '}')?;
+        Ok(())
+    }
+
+    fn parse_statements(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::Statements);
+
+        while self.peek_symbol('}').is_none() {
+            let statement = self.parse_statement()?;
+            node.children.push(SyntaxElement::Node(statement));
+        }
+
+        Ok(node)
+    }
+
+    fn parse_statement(&mut self) -> Result<SyntaxNode, Error> {
+        let kind = match self.peek()? {
+            Token::Keyword(Keyword::Let) => SyntaxKind::LetStatement,
+            Token::Keyword(Keyword::If) => SyntaxKind::IfStatement,
+            Token::Keyword(Keyword::While) => SyntaxKind::WhileStatement,
+            Token::Keyword(Keyword::Do) => SyntaxKind::DoStatement,
+            Token::Keyword(Keyword::Return) => SyntaxKind::ReturnStatement,
+            token => {
+                return Err(anyhow!(
+                    "expected one of `let`, `if`, `while`, `do`, or `return`, found `{}`",
+                    token
+                ))
+            }
+        };
+
+        let mut node = SyntaxNode::new(kind);
+        self.consume_into(&mut node)?;
+
+        match kind {
+            SyntaxKind::LetStatement => {
+                self.expect_identifier(&mut node)?;
+
+                if self.peek_symbol('[').is_some() {
+                    self.parse_index_expr(&mut node)?;
+                }
+
+                self.expect_symbol(&mut node, '=')?;
+                let rhs = self.parse_expr()?;
+                node.children.push(SyntaxElement::Node(rhs));
+                self.expect_symbol(&mut node, ';')?;
+            }
+            SyntaxKind::IfStatement => {
+                self.parse_condition(&mut node)?;
+                self.parse_block(&mut node)?;
+
+                if let Token::Keyword(Keyword::Else) = self.peek()? {
+                    self.consume_into(&mut node)?;
+                    self.parse_block(&mut node)?;
+                }
+            }
+            SyntaxKind::WhileStatement => {
+                self.parse_condition(&mut node)?;
+                self.parse_block(&mut node)?;
+            }
+            SyntaxKind::DoStatement => {
+                self.expect_identifier(&mut node)?;
+                self.parse_subroutine_call(&mut node)?;
+                self.expect_symbol(&mut node, ';')?;
+            }
+            _ => {
+                if self.peek_symbol(';').is_none() {
+                    let expr = self.parse_expr()?;
+                    node.children.push(SyntaxElement::Node(expr));
+                }
+                self.expect_symbol(&mut node, ';')?;
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn parse_condition(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        self.expect_symbol(node, '(')?;
+        let condition = self.parse_expr()?;
+        node.children.push(SyntaxElement::Node(condition));
+        self.expect_symbol(node, ')')?;
+        Ok(())
+    }
+
+    // Parses the remainder of a subroutine call, assuming its first identifier has already been
+    // consumed into the given node.
+    fn parse_subroutine_call(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        if self.peek_symbol('.').is_some() {
+            self.consume_into(node)?;
+            self.expect_identifier(node)?;
+        }
+
+        self.expect_symbol(node, '(')?;
+        let args = self.parse_expr_list()?;
+        node.children.push(SyntaxElement::Node(args));
+        self.expect_symbol(node, ')')?;
+
+        Ok(())
+    }
+
+    fn parse_expr_list(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::ExpressionList);
+
+        if self.peek_symbol(')').is_none() {
+            loop {
+                let expr = self.parse_expr()?;
+                node.children.push(SyntaxElement::Node(expr));
+
+                match self.peek()? {
+                    Token::Symbol(',') => self.consume_into(&mut node)?,
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn parse_expr(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::Expression);
+
+        let term = self.parse_term()?;
+        node.children.push(SyntaxElement::Node(term));
+
+        while let Token::Symbol('+')
+        | Token::Symbol('-')
+        | Token::Symbol('*')
+        | Token::Symbol('/')
+        | Token::Symbol('&')
+        | Token::Symbol('|')
+        | Token::Symbol('<')
+        | Token::Symbol('>')
+        | Token::Symbol('=') = self.peek()?
+        {
+            self.consume_into(&mut node)?;
+            let term = self.parse_term()?;
+            node.children.push(SyntaxElement::Node(term));
+        }
+
+        Ok(node)
+    }
+
+    fn parse_term(&mut self) -> Result<SyntaxNode, Error> {
+        let mut node = SyntaxNode::new(SyntaxKind::Term);
+
+        match self.peek()? {
+            Token::IntConst(_) | Token::StrConst(_) => self.consume_into(&mut node)?,
+            Token::Keyword(Keyword::True)
+            | Token::Keyword(Keyword::False)
+            | Token::Keyword(Keyword::Null)
+            | Token::Keyword(Keyword::This) => self.consume_into(&mut node)?,
+            Token::Identifier(_) => {
+                self.consume_into(&mut node)?;
+
+                match self.peek()? {
+                    Token::Symbol('.') | Token::Symbol('(') => {
+                        self.parse_subroutine_call(&mut node)?
+                    }
+                    Token::Symbol('[') => self.parse_index_expr(&mut node)?,
+                    _ => {}
+                }
+            }
+            Token::Symbol('(') => {
+                self.consume_into(&mut node)?;
+                let expr = self.parse_expr()?;
+                node.children.push(SyntaxElement::Node(expr));
+                self.expect_symbol(&mut node, ')')?;
+            }
+            Token::Symbol('-') | Token::Symbol('~') => {
+                self.consume_into(&mut node)?;
+                let term = self.parse_term()?;
+                node.children.push(SyntaxElement::Node(term));
+            }
+            token => return Err(anyhow!("expected a term, found `{}`", token)),
+        }
+
+        Ok(node)
+    }
+
+    fn parse_index_expr(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        self.expect_symbol(node, '[')?;
+        let index = self.parse_expr()?;
+        node.children.push(SyntaxElement::Node(index));
+        self.expect_symbol(node, ']')?;
+        Ok(())
+    }
+
+    fn consume_type(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        match self.peek()? {
+            Token::Keyword(Keyword::Int)
+            | Token::Keyword(Keyword::Char)
+            | Token::Keyword(Keyword::Boolean)
+            | Token::Keyword(Keyword::Void)
+            | Token::Identifier(_) => self.consume_into(node),
+            token => Err(anyhow!(
+                "expected one of `int`, `char`, `boolean`, or a class name, found `{}`",
+                token
+            )),
+        }
+    }
+
+    fn expect_symbol(&mut self, node: &mut SyntaxNode, want: char) -> Result<(), Error> {
+        self.expect(node, &Token::Symbol(want))
+    }
+
+    fn expect(&mut self, node: &mut SyntaxNode, want: &Token) -> Result<(), Error> {
+        match self.peek()? {
+            token if token == want => self.consume_into(node),
+            token => Err(anyhow!("expected `{}`, found `{}`", want, token)),
+        }
+    }
+
+    fn expect_identifier(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        match self.peek()? {
+            Token::Identifier(_) => self.consume_into(node),
+            token => Err(anyhow!("expected an identifier, found `{}`", token)),
+        }
+    }
+
+    fn consume_into(&mut self, node: &mut SyntaxNode) -> Result<(), Error> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| anyhow!("unexpected end of file"))?;
+
+        node.children.push(SyntaxElement::Token(token));
+        Ok(())
+    }
+
+    fn peek_symbol(&mut self, want: char) -> Option<&Token> {
+        self.peek()
+            .ok()
+            .filter(|&token| token == &Token::Symbol(want))
+    }
+
+    fn peek(&mut self) -> Result<&Token, Error> {
+        self.tokens
+            .peek()
+            .map(|source| &source.token)
+            .ok_or_else(|| anyhow!("unexpected end of file"))
+    }
+}
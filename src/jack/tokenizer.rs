@@ -1,9 +1,10 @@
 use anyhow::{anyhow, Error};
 use std::fmt;
 use std::iter::Peekable;
-use std::str::{Chars, FromStr};
+use std::str::{CharIndices, FromStr};
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Token {
     Keyword(Keyword),
     Identifier(String),
@@ -26,6 +27,7 @@ impl fmt::Display for Token {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Keyword {
     Boolean,
     Char,
@@ -109,47 +111,138 @@ impl FromStr for Keyword {
     }
 }
 
+// Whitespace and comments that appear between tokens. These are discarded by the parser, but
+// are needed to reproduce the source text exactly.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Trivia {
+    Whitespace(String),
+    LineComment(String),
+    BlockComment(String),
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trivia::Whitespace(s) | Trivia::LineComment(s) | Trivia::BlockComment(s) => {
+                write!(f, "{}", s)
+            }
+        }
+    }
+}
+
+// A token along with its original spelling in the source, and any trivia that precedes it.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SourceToken {
+    pub leading: Vec<Trivia>,
+    pub token: Token,
+    pub text: String,
+}
+
+impl fmt::Display for SourceToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(f, "{}", trivia)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+
+enum Lexeme {
+    Trivia(Trivia),
+    Token(Token),
+}
+
 pub struct Tokenizer<'a> {
-    chars: Peekable<Chars<'a>>,
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
-        let chars = input.chars().peekable();
-        Tokenizer { chars }
+        let chars = input.char_indices().peekable();
+        Tokenizer { input, chars }
     }
 
     pub fn tokenize(&mut self) -> Result<Vec<Token>, Error> {
         self.collect()
     }
+
+    // Tokenizes the input without discarding any whitespace or comments. Any trivia following
+    // the last token in the input is returned separately.
+    pub fn tokenize_with_trivia(&mut self) -> Result<(Vec<SourceToken>, Vec<Trivia>), Error> {
+        let mut tokens = Vec::new();
+        let mut leading = Vec::new();
+
+        loop {
+            let start = self.pos();
+
+            match self.next_lexeme() {
+                Some(Ok(Lexeme::Trivia(trivia))) => leading.push(trivia),
+                Some(Ok(Lexeme::Token(token))) => tokens.push(SourceToken {
+                    leading: std::mem::take(&mut leading),
+                    token,
+                    text: self.input[start..self.pos()].to_owned(),
+                }),
+                Some(Err(err)) => return Err(err),
+                None => return Ok((tokens, leading)),
+            }
+        }
+    }
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
     type Item = Result<Token, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
-
-        self.read_char().and_then(|ch| match ch {
-            '/' => {
-                if self.skip_comment() {
-                    // If we've skipped a comment, call next() recursively to move on to the
-                    // next token and pretend the comment wasn't there.
-                    self.next()
-                } else {
-                    Some(Ok(Token::Symbol(ch)))
-                }
+        loop {
+            match self.next_lexeme()? {
+                Ok(Lexeme::Trivia(_)) => continue,
+                Ok(Lexeme::Token(token)) => return Some(Ok(token)),
+                Err(err) => return Some(Err(err)),
             }
-            '"' => self.read_str_const().map(Token::StrConst).map(Ok),
-            _ if is_symbol(ch) => Some(Ok(Token::Symbol(ch))),
-            _ if ch.is_ascii_digit() => self.read_int_const(ch).map(Token::IntConst).map(Ok),
-            _ if is_identifier(ch) => self.read_word(ch).map(parse_keyword_or_identifier).map(Ok),
-            _ => Some(Err(anyhow!("`{}` is not a valid token", ch))),
-        })
+        }
     }
 }
 
 impl<'a> Tokenizer<'a> {
+    fn next_lexeme(&mut self) -> Option<Result<Lexeme, Error>> {
+        let start = self.pos();
+
+        self.read_char().map(|ch| match ch {
+            _ if ch.is_whitespace() => {
+                self.skip_whitespace();
+                Ok(Lexeme::Trivia(Trivia::Whitespace(self.text_from(start))))
+            }
+            '/' => match self.peek_char() {
+                Some('/') => {
+                    self.skip_line();
+                    Ok(Lexeme::Trivia(Trivia::LineComment(self.text_from(start))))
+                }
+                Some('*') => {
+                    self.advance();
+                    self.skip_block_comment();
+                    Ok(Lexeme::Trivia(Trivia::BlockComment(self.text_from(start))))
+                }
+                _ => Ok(Lexeme::Token(Token::Symbol(ch))),
+            },
+            '"' => self
+                .read_str_const()
+                .map(|s| Lexeme::Token(Token::StrConst(s)))
+                .ok_or_else(|| anyhow!("unterminated string constant")),
+            _ if is_symbol(ch) => Ok(Lexeme::Token(Token::Symbol(ch))),
+            _ if ch.is_ascii_digit() => {
+                let num = self.read_while(ch, |ch| ch.is_ascii_digit());
+                num.parse()
+                    .map(|n| Lexeme::Token(Token::IntConst(n)))
+                    .map_err(|_| anyhow!("`{}` is not a valid integer constant", num))
+            }
+            _ if is_identifier(ch) => Ok(Lexeme::Token(parse_keyword_or_identifier(
+                self.read_word(ch),
+            ))),
+            _ => Err(anyhow!("`{}` is not a valid token", ch)),
+        })
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.peek_char() {
             if ch.is_whitespace() {
@@ -160,25 +253,13 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
-    fn skip_comment(&mut self) -> bool {
-        match self.peek_char() {
-            Some('/') => {
-                self.skip_line();
-                true
-            }
-            Some('*') => {
-                self.skip_block_comment();
-                true
-            }
-            _ => false,
-        }
-    }
-
+    // Skips to the end of the current line, leaving the newline itself to be read as whitespace.
     fn skip_line(&mut self) {
-        while let Some(ch) = self.read_char() {
+        while let Some(ch) = self.peek_char() {
             if ch == '\n' {
                 return;
             }
+            self.advance();
         }
     }
 
@@ -207,18 +288,13 @@ impl<'a> Tokenizer<'a> {
         None
     }
 
-    fn read_word(&mut self, first: char) -> Option<String> {
+    fn read_word(&mut self, first: char) -> String {
         // Identifiers may contain digits, as long as they're not the first
         // character in the word.
         self.read_while(first, |ch| is_identifier(ch) || ch.is_ascii_digit())
     }
 
-    fn read_int_const(&mut self, first: char) -> Option<u16> {
-        self.read_while(first, |ch| ch.is_ascii_digit())
-            .and_then(|num| num.parse().ok())
-    }
-
-    fn read_while<P>(&mut self, first: char, pred: P) -> Option<String>
+    fn read_while<P>(&mut self, first: char, pred: P) -> String
     where
         P: Fn(char) -> bool,
     {
@@ -233,11 +309,19 @@ impl<'a> Tokenizer<'a> {
             }
         }
 
-        Some(string)
+        string
+    }
+
+    fn text_from(&mut self, start: usize) -> String {
+        self.input[start..self.pos()].to_owned()
+    }
+
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |&(idx, _)| idx)
     }
 
     fn peek_char(&mut self) -> Option<char> {
-        self.chars.peek().copied()
+        self.chars.peek().map(|&(_, ch)| ch)
     }
 
     fn advance(&mut self) {
@@ -245,7 +329,7 @@ impl<'a> Tokenizer<'a> {
     }
 
     fn read_char(&mut self) -> Option<char> {
-        self.chars.next()
+        self.chars.next().map(|(_, ch)| ch)
     }
 }
 
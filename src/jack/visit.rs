@@ -0,0 +1,333 @@
+use super::parser::*;
+use anyhow::Error;
+
+type Result = std::result::Result<(), Error>;
+
+// A read-only traversal of the Jack AST. Every method has a default implementation which walks
+// the node's children in source order, so implementors only need to override the methods for
+// the nodes they're interested in. An overriding method can call the matching `walk_*` function
+// to carry on traversing the node's children.
+pub trait Visitor {
+    fn visit_class(&mut self, class: &Class) -> Result {
+        walk_class(self, class)
+    }
+
+    fn visit_class_vars(&mut self, vars: &ClassVars) -> Result {
+        walk_class_vars(self, vars)
+    }
+
+    fn visit_subroutine(&mut self, sub: &Subroutine) -> Result {
+        walk_subroutine(self, sub)
+    }
+
+    fn visit_param(&mut self, param: &Param) -> Result {
+        walk_param(self, param)
+    }
+
+    fn visit_subroutine_body(&mut self, body: &SubroutineBody) -> Result {
+        walk_subroutine_body(self, body)
+    }
+
+    fn visit_local_vars(&mut self, vars: &LocalVars) -> Result {
+        walk_local_vars(self, vars)
+    }
+
+    fn visit_statements(&mut self, stmts: &[Statement]) -> Result {
+        walk_statements(self, stmts)
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) -> Result {
+        walk_statement(self, stmt)
+    }
+
+    fn visit_subroutine_call(&mut self, call: &SubroutineCall) -> Result {
+        walk_subroutine_call(self, call)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Result {
+        walk_expr(self, expr)
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Result {
+        walk_term(self, term)
+    }
+
+    fn visit_var_type(&mut self, _typ: &VarType) -> Result {
+        Ok(())
+    }
+}
+
+pub fn walk_class<V: Visitor + ?Sized>(v: &mut V, class: &Class) -> Result {
+    for vars in &class.vars {
+        v.visit_class_vars(vars)?;
+    }
+    for sub in &class.subs {
+        v.visit_subroutine(sub)?;
+    }
+    Ok(())
+}
+
+pub fn walk_class_vars<V: Visitor + ?Sized>(v: &mut V, vars: &ClassVars) -> Result {
+    v.visit_var_type(&vars.typ)?;
+    for expr in vars.inits.iter().flatten() {
+        v.visit_expr(expr)?;
+    }
+    Ok(())
+}
+
+pub fn walk_subroutine<V: Visitor + ?Sized>(v: &mut V, sub: &Subroutine) -> Result {
+    if let SubroutineType::NonVoid(typ) = &sub.typ {
+        v.visit_var_type(typ)?;
+    }
+    for param in &sub.params {
+        v.visit_param(param)?;
+    }
+    v.visit_subroutine_body(&sub.body)
+}
+
+pub fn walk_param<V: Visitor + ?Sized>(v: &mut V, param: &Param) -> Result {
+    v.visit_var_type(&param.typ)
+}
+
+pub fn walk_subroutine_body<V: Visitor + ?Sized>(v: &mut V, body: &SubroutineBody) -> Result {
+    for vars in &body.vars {
+        v.visit_local_vars(vars)?;
+    }
+    v.visit_statements(&body.statements)
+}
+
+pub fn walk_local_vars<V: Visitor + ?Sized>(v: &mut V, vars: &LocalVars) -> Result {
+    v.visit_var_type(&vars.typ)
+}
+
+pub fn walk_statements<V: Visitor + ?Sized>(v: &mut V, stmts: &[Statement]) -> Result {
+    for stmt in stmts {
+        v.visit_statement(stmt)?;
+    }
+    Ok(())
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &Statement) -> Result {
+    match stmt {
+        Statement::Let { index, rhs, .. } => {
+            if let Some(index) = index {
+                v.visit_expr(index)?;
+            }
+            v.visit_expr(rhs)
+        }
+        Statement::If {
+            condition,
+            if_body,
+            else_body,
+        } => {
+            v.visit_expr(condition)?;
+            v.visit_statements(if_body)?;
+            if let Some(body) = else_body {
+                v.visit_statements(body)?;
+            }
+            Ok(())
+        }
+        Statement::While { condition, body } => {
+            v.visit_expr(condition)?;
+            v.visit_statements(body)
+        }
+        Statement::Do(call) => v.visit_subroutine_call(call),
+        Statement::Return(Some(expr)) => v.visit_expr(expr),
+        Statement::Return(None) => Ok(()),
+    }
+}
+
+pub fn walk_subroutine_call<V: Visitor + ?Sized>(v: &mut V, call: &SubroutineCall) -> Result {
+    for arg in &call.args {
+        v.visit_expr(arg)?;
+    }
+    Ok(())
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) -> Result {
+    match expr {
+        Expr::Term(term) => v.visit_term(term),
+        Expr::Binary(_, term, rest) => {
+            v.visit_term(term)?;
+            v.visit_expr(rest)
+        }
+    }
+}
+
+pub fn walk_term<V: Visitor + ?Sized>(v: &mut V, term: &Term) -> Result {
+    match term {
+        Term::IndexedVar(_, index) => v.visit_expr(index),
+        Term::SubroutineCall(call) => v.visit_subroutine_call(call),
+        Term::Bracketed(expr) => v.visit_expr(expr),
+        Term::Unary(_, term) => v.visit_term(term),
+        Term::IntConst(_) | Term::StrConst(_) | Term::KeywordConst(_) | Term::Var(_) => Ok(()),
+    }
+}
+
+// The mutable counterpart to `Visitor`, for passes which rewrite the AST in place, such as
+// desugaring or constant folding.
+pub trait VisitorMut {
+    fn visit_class_mut(&mut self, class: &mut Class) -> Result {
+        walk_class_mut(self, class)
+    }
+
+    fn visit_class_vars_mut(&mut self, vars: &mut ClassVars) -> Result {
+        walk_class_vars_mut(self, vars)
+    }
+
+    fn visit_subroutine_mut(&mut self, sub: &mut Subroutine) -> Result {
+        walk_subroutine_mut(self, sub)
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) -> Result {
+        walk_param_mut(self, param)
+    }
+
+    fn visit_subroutine_body_mut(&mut self, body: &mut SubroutineBody) -> Result {
+        walk_subroutine_body_mut(self, body)
+    }
+
+    fn visit_local_vars_mut(&mut self, vars: &mut LocalVars) -> Result {
+        walk_local_vars_mut(self, vars)
+    }
+
+    fn visit_statements_mut(&mut self, stmts: &mut Vec<Statement>) -> Result {
+        walk_statements_mut(self, stmts)
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) -> Result {
+        walk_statement_mut(self, stmt)
+    }
+
+    fn visit_subroutine_call_mut(&mut self, call: &mut SubroutineCall) -> Result {
+        walk_subroutine_call_mut(self, call)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) -> Result {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_term_mut(&mut self, term: &mut Term) -> Result {
+        walk_term_mut(self, term)
+    }
+
+    fn visit_var_type_mut(&mut self, _typ: &mut VarType) -> Result {
+        Ok(())
+    }
+}
+
+pub fn walk_class_mut<V: VisitorMut + ?Sized>(v: &mut V, class: &mut Class) -> Result {
+    for vars in &mut class.vars {
+        v.visit_class_vars_mut(vars)?;
+    }
+    for sub in &mut class.subs {
+        v.visit_subroutine_mut(sub)?;
+    }
+    Ok(())
+}
+
+pub fn walk_class_vars_mut<V: VisitorMut + ?Sized>(v: &mut V, vars: &mut ClassVars) -> Result {
+    v.visit_var_type_mut(&mut vars.typ)?;
+    for expr in vars.inits.iter_mut().flatten() {
+        v.visit_expr_mut(expr)?;
+    }
+    Ok(())
+}
+
+pub fn walk_subroutine_mut<V: VisitorMut + ?Sized>(v: &mut V, sub: &mut Subroutine) -> Result {
+    if let SubroutineType::NonVoid(typ) = &mut sub.typ {
+        v.visit_var_type_mut(typ)?;
+    }
+    for param in &mut sub.params {
+        v.visit_param_mut(param)?;
+    }
+    v.visit_subroutine_body_mut(&mut sub.body)
+}
+
+pub fn walk_param_mut<V: VisitorMut + ?Sized>(v: &mut V, param: &mut Param) -> Result {
+    v.visit_var_type_mut(&mut param.typ)
+}
+
+pub fn walk_subroutine_body_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    body: &mut SubroutineBody,
+) -> Result {
+    for vars in &mut body.vars {
+        v.visit_local_vars_mut(vars)?;
+    }
+    v.visit_statements_mut(&mut body.statements)
+}
+
+pub fn walk_local_vars_mut<V: VisitorMut + ?Sized>(v: &mut V, vars: &mut LocalVars) -> Result {
+    v.visit_var_type_mut(&mut vars.typ)
+}
+
+pub fn walk_statements_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    stmts: &mut Vec<Statement>,
+) -> Result {
+    for stmt in stmts {
+        v.visit_statement_mut(stmt)?;
+    }
+    Ok(())
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Statement) -> Result {
+    match stmt {
+        Statement::Let { index, rhs, .. } => {
+            if let Some(index) = index {
+                v.visit_expr_mut(index)?;
+            }
+            v.visit_expr_mut(rhs)
+        }
+        Statement::If {
+            condition,
+            if_body,
+            else_body,
+        } => {
+            v.visit_expr_mut(condition)?;
+            v.visit_statements_mut(if_body)?;
+            if let Some(body) = else_body {
+                v.visit_statements_mut(body)?;
+            }
+            Ok(())
+        }
+        Statement::While { condition, body } => {
+            v.visit_expr_mut(condition)?;
+            v.visit_statements_mut(body)
+        }
+        Statement::Do(call) => v.visit_subroutine_call_mut(call),
+        Statement::Return(Some(expr)) => v.visit_expr_mut(expr),
+        Statement::Return(None) => Ok(()),
+    }
+}
+
+pub fn walk_subroutine_call_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    call: &mut SubroutineCall,
+) -> Result {
+    for arg in &mut call.args {
+        v.visit_expr_mut(arg)?;
+    }
+    Ok(())
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) -> Result {
+    match expr {
+        Expr::Term(term) => v.visit_term_mut(term),
+        Expr::Binary(_, term, rest) => {
+            v.visit_term_mut(term)?;
+            v.visit_expr_mut(rest)
+        }
+    }
+}
+
+pub fn walk_term_mut<V: VisitorMut + ?Sized>(v: &mut V, term: &mut Term) -> Result {
+    match term {
+        Term::IndexedVar(_, index) => v.visit_expr_mut(index),
+        Term::SubroutineCall(call) => v.visit_subroutine_call_mut(call),
+        Term::Bracketed(expr) => v.visit_expr_mut(expr),
+        Term::Unary(_, term) => v.visit_term_mut(term),
+        Term::IntConst(_) | Term::StrConst(_) | Term::KeywordConst(_) | Term::Var(_) => Ok(()),
+    }
+}
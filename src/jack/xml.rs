@@ -0,0 +1,377 @@
+use super::parser::*;
+use super::tokenizer::{Keyword, Token};
+
+// Renders tokens in the format of the `xxxT.xml` files used by the Nand to Tetris course, which
+// can be compared against the course's own test files using its TextComparer tool.
+pub fn tokens_to_xml(tokens: &[Token]) -> String {
+    let mut out = String::from("<tokens>\n");
+
+    for token in tokens {
+        out.push_str(&token_element(token));
+        out.push('\n');
+    }
+
+    out.push_str("</tokens>\n");
+    out
+}
+
+// Renders a class in the format of the `xxx.xml` parse trees used by the Nand to Tetris course.
+pub fn class_to_xml(class: &Class) -> String {
+    let mut writer = XmlWriter::new();
+    writer.class(class);
+    writer.out
+}
+
+struct XmlWriter {
+    out: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        XmlWriter {
+            out: String::new(),
+            depth: 0,
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.open("class");
+        self.keyword(Keyword::Class);
+        self.identifier(&class.name);
+        self.symbol('{');
+
+        for vars in &class.vars {
+            self.class_vars(vars);
+        }
+
+        for sub in &class.subs {
+            self.subroutine(sub);
+        }
+
+        self.symbol('}');
+        self.close("class");
+    }
+
+    fn class_vars(&mut self, vars: &ClassVars) {
+        self.open("classVarDec");
+
+        self.keyword(match vars.kind {
+            ClassVarKind::Field => Keyword::Field,
+            ClassVarKind::Static => Keyword::Static,
+        });
+        self.var_type(&vars.typ);
+
+        for (i, (name, init)) in vars.names.iter().zip(&vars.inits).enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+
+            self.identifier(name);
+
+            if let Some(expr) = init {
+                self.symbol('=');
+                self.expr(expr);
+            }
+        }
+
+        self.symbol(';');
+        self.close("classVarDec");
+    }
+
+    fn subroutine(&mut self, sub: &Subroutine) {
+        self.open("subroutineDec");
+
+        self.keyword(match sub.kind {
+            SubroutineKind::Constructor => Keyword::Constructor,
+            SubroutineKind::Function => Keyword::Function,
+            SubroutineKind::Method => Keyword::Method,
+        });
+
+        match &sub.typ {
+            SubroutineType::Void => self.keyword(Keyword::Void),
+            SubroutineType::NonVoid(typ) => self.var_type(typ),
+        }
+
+        self.identifier(&sub.name);
+        self.symbol('(');
+        self.open("parameterList");
+
+        for (i, param) in sub.params.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+
+            self.var_type(&param.typ);
+            self.identifier(&param.name);
+        }
+
+        self.close("parameterList");
+        self.symbol(')');
+
+        self.open("subroutineBody");
+        self.symbol('{');
+
+        for vars in &sub.body.vars {
+            self.open("varDec");
+            self.keyword(Keyword::Var);
+            self.var_type(&vars.typ);
+            self.identifiers(&vars.names);
+            self.symbol(';');
+            self.close("varDec");
+        }
+
+        self.statements(&sub.body.statements);
+        self.symbol('}');
+        self.close("subroutineBody");
+
+        self.close("subroutineDec");
+    }
+
+    fn statements(&mut self, stmts: &[Statement]) {
+        self.open("statements");
+
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+
+        self.close("statements");
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let { lhs, index, rhs } => {
+                self.open("letStatement");
+                self.keyword(Keyword::Let);
+                self.identifier(lhs);
+
+                if let Some(index) = index {
+                    self.symbol('[');
+                    self.expr(index);
+                    self.symbol(']');
+                }
+
+                self.symbol('=');
+                self.expr(rhs);
+                self.symbol(';');
+                self.close("letStatement");
+            }
+            Statement::If {
+                condition,
+                if_body,
+                else_body,
+            } => {
+                self.open("ifStatement");
+                self.keyword(Keyword::If);
+                self.condition(condition);
+                self.block(if_body);
+
+                if let Some(body) = else_body {
+                    self.keyword(Keyword::Else);
+                    self.block(body);
+                }
+
+                self.close("ifStatement");
+            }
+            Statement::While { condition, body } => {
+                self.open("whileStatement");
+                self.keyword(Keyword::While);
+                self.condition(condition);
+                self.block(body);
+                self.close("whileStatement");
+            }
+            Statement::Do(call) => {
+                self.open("doStatement");
+                self.keyword(Keyword::Do);
+                self.subroutine_call(call);
+                self.symbol(';');
+                self.close("doStatement");
+            }
+            Statement::Return(value) => {
+                self.open("returnStatement");
+                self.keyword(Keyword::Return);
+
+                if let Some(expr) = value {
+                    self.expr(expr);
+                }
+
+                self.symbol(';');
+                self.close("returnStatement");
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr) {
+        self.symbol('(');
+        self.expr(condition);
+        self.symbol(')');
+    }
+
+    fn block(&mut self, stmts: &[Statement]) {
+        self.symbol('{');
+        self.statements(stmts);
+        self.symbol('}');
+    }
+
+    // The course doesn't have an element for subroutine calls, so their tokens are written out
+    // directly into the enclosing term or statement.
+    fn subroutine_call(&mut self, call: &SubroutineCall) {
+        if let Some(receiver) = &call.receiver {
+            self.identifier(receiver);
+            self.symbol('.');
+        }
+
+        self.identifier(&call.subroutine);
+        self.symbol('(');
+        self.open("expressionList");
+
+        for (i, arg) in call.args.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+
+            self.expr(arg);
+        }
+
+        self.close("expressionList");
+        self.symbol(')');
+    }
+
+    // Binary expressions are nested to the right in the AST, but the course's grammar treats
+    // an expression as a flat list of terms separated by operators.
+    fn expr(&mut self, expr: &Expr) {
+        self.open("expression");
+
+        let mut expr = expr;
+
+        loop {
+            match expr {
+                Expr::Term(term) => {
+                    self.term(term);
+                    break;
+                }
+                Expr::Binary(op, term, rest) => {
+                    self.term(term);
+                    self.element("symbol", &op.to_string());
+                    expr = rest;
+                }
+            }
+        }
+
+        self.close("expression");
+    }
+
+    fn term(&mut self, term: &Term) {
+        self.open("term");
+
+        match term {
+            Term::IntConst(n) => self.element("integerConstant", &n.to_string()),
+            Term::StrConst(s) => self.element("stringConstant", s),
+            Term::KeywordConst(kw) => self.keyword(match kw {
+                KeywordConst::True => Keyword::True,
+                KeywordConst::False => Keyword::False,
+                KeywordConst::Null => Keyword::Null,
+                KeywordConst::This => Keyword::This,
+            }),
+            Term::Var(name) => self.identifier(name),
+            Term::IndexedVar(name, index) => {
+                self.identifier(name);
+                self.symbol('[');
+                self.expr(index);
+                self.symbol(']');
+            }
+            Term::SubroutineCall(call) => self.subroutine_call(call),
+            Term::Bracketed(expr) => {
+                self.symbol('(');
+                self.expr(expr);
+                self.symbol(')');
+            }
+            Term::Unary(op, term) => {
+                self.element("symbol", &op.to_string());
+                self.term(term);
+            }
+        }
+
+        self.close("term");
+    }
+
+    fn var_type(&mut self, typ: &VarType) {
+        match typ {
+            VarType::Int => self.keyword(Keyword::Int),
+            VarType::Char => self.keyword(Keyword::Char),
+            VarType::Boolean => self.keyword(Keyword::Boolean),
+            VarType::ClassName(name) => self.identifier(name),
+        }
+    }
+
+    fn identifiers(&mut self, names: &[String]) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+
+            self.identifier(name);
+        }
+    }
+
+    fn keyword(&mut self, keyword: Keyword) {
+        self.token(&Token::Keyword(keyword));
+    }
+
+    fn identifier(&mut self, name: &str) {
+        self.element("identifier", name);
+    }
+
+    fn symbol(&mut self, ch: char) {
+        self.token(&Token::Symbol(ch));
+    }
+
+    fn token(&mut self, token: &Token) {
+        self.indent();
+        self.out.push_str(&token_element(token));
+        self.out.push('\n');
+    }
+
+    fn element(&mut self, tag: &str, text: &str) {
+        self.indent();
+        self.out
+            .push_str(&format!("<{}> {} </{}>\n", tag, escape(text), tag));
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.indent();
+        self.out.push_str(&format!("<{}>\n", tag));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.out.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+    }
+}
+
+fn token_element(token: &Token) -> String {
+    let (tag, text) = match token {
+        Token::Keyword(keyword) => ("keyword", keyword.to_string()),
+        Token::Identifier(id) => ("identifier", id.clone()),
+        Token::IntConst(n) => ("integerConstant", n.to_string()),
+        Token::StrConst(s) => ("stringConstant", s.clone()),
+        Token::Symbol(ch) => ("symbol", ch.to_string()),
+    };
+
+    format!("<{}> {} </{}>", tag, escape(&text), tag)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,13 +1,15 @@
 use anyhow::{anyhow, Error};
 use byteorder::{BigEndian, WriteBytesExt};
-use clap::{crate_version, App, Arg};
-use jackc::asm::{self, Instruction};
-use jackc::jack::{self, Compiler, Tokenizer};
-use jackc::vm::{self, Module, Translator};
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use jackc::asm::{self, Instruction, Load, MemoryLayout};
+use jackc::jack::{self, ClassDoc, Compiler, Formatter, SyntaxParser, Tokenizer};
+use jackc::vm::{
+    self, CallGraph, Module, OptimizeFor, Optimizer, StackDepth, Strategy, Translator, Validator,
+};
 use std::{
     fs::{self, File},
     io::{self, BufWriter, Write},
-    path::Path,
+    path::{Path, PathBuf},
     process,
 };
 
@@ -18,6 +20,122 @@ enum Format {
     Asm,
     Bin,
     Hack,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+// An intermediate representation which is written out for each `.jack` file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Emit {
+    TokensXml,
+    ParseXml,
+    #[cfg(feature = "serde")]
+    TokensJson,
+    #[cfg(feature = "serde")]
+    AstJson,
+    #[cfg(feature = "serde")]
+    VmJson,
+}
+
+const EMIT_NAMES: &[&str] = &[
+    "tokens-xml",
+    "parse-xml",
+    #[cfg(feature = "serde")]
+    "tokens-json",
+    #[cfg(feature = "serde")]
+    "ast-json",
+    #[cfg(feature = "serde")]
+    "vm-json",
+    #[cfg(feature = "serde")]
+    "asm-json",
+];
+
+impl Emit {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tokens-xml" => Some(Emit::TokensXml),
+            "parse-xml" => Some(Emit::ParseXml),
+            #[cfg(feature = "serde")]
+            "tokens-json" => Some(Emit::TokensJson),
+            #[cfg(feature = "serde")]
+            "ast-json" => Some(Emit::AstJson),
+            #[cfg(feature = "serde")]
+            "vm-json" => Some(Emit::VmJson),
+            _ => None,
+        }
+    }
+
+    // Assembly can only be emitted for a whole program rather than for individual files, so it
+    // goes through the same pipeline as the other output formats instead.
+    fn format(name: &str) -> Option<Format> {
+        match name {
+            #[cfg(feature = "serde")]
+            "asm-json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    // Follows the naming convention of the Nand to Tetris course, which distinguishes token
+    // files from parse trees with a `T` suffix.
+    fn filename(self, stem: &str) -> String {
+        match self {
+            Emit::TokensXml => format!("{}T.xml", stem),
+            Emit::ParseXml => format!("{}.xml", stem),
+            #[cfg(feature = "serde")]
+            Emit::TokensJson => format!("{}.tokens.json", stem),
+            #[cfg(feature = "serde")]
+            Emit::AstJson => format!("{}.ast.json", stem),
+            #[cfg(feature = "serde")]
+            Emit::VmJson => format!("{}.vm.json", stem),
+        }
+    }
+}
+
+// A single input file, once compiled to VM code or parsed as assembly.
+enum Input {
+    Module(Module),
+    Asm(String, Vec<Instruction>),
+}
+
+impl Input {
+    fn name(&self) -> &str {
+        match self {
+            Input::Module(module) => &module.name,
+            Input::Asm(name, _) => name,
+        }
+    }
+}
+
+// Everything making up a program. Assembly from every `.asm` file is concatenated in order.
+#[derive(Default)]
+struct Inputs {
+    modules: Vec<Module>,
+    asm: Vec<Instruction>,
+}
+
+impl Inputs {
+    fn add(&mut self, input: Input) {
+        match input {
+            Input::Module(module) => self.modules.push(module),
+            Input::Asm(_, prog) => self.asm.extend(prog),
+        }
+    }
+}
+
+// Settings which affect how each Jack class is compiled to VM code.
+#[derive(Copy, Clone, Debug, Default)]
+struct CompileOptions {
+    debug_checks: bool,
+    annotate: bool,
+}
+
+impl CompileOptions {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        CompileOptions {
+            debug_checks: matches.is_present("debug-checks"),
+            annotate: matches.is_present("annotate"),
+        }
+    }
 }
 
 impl Format {
@@ -26,6 +144,8 @@ impl Format {
             Format::Asm => "asm",
             Format::Bin => "bin",
             Format::Hack => "hack",
+            #[cfg(feature = "serde")]
+            Format::Json => "json",
         }
     }
 }
@@ -44,6 +164,7 @@ fn run() -> Result<()> {
     let matches = App::new("jackc")
         .version(crate_version!())
         .about("A compiler for the Jack programming language")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("file")
                 .help("Files or directories to be compiled")
@@ -54,19 +175,103 @@ fn run() -> Result<()> {
             Arg::with_name("asm")
                 .long("asm")
                 .help("Outputs an assembly file")
-                .conflicts_with_all(&["bin", "hack"]),
+                .conflicts_with_all(&["bin", "hack", "vm"]),
         )
         .arg(
             Arg::with_name("bin")
                 .long("bin")
                 .help("Outputs a binary file")
-                .conflicts_with_all(&["asm", "hack"]),
+                .conflicts_with_all(&["asm", "hack", "vm"]),
         )
         .arg(
             Arg::with_name("hack")
                 .long("hack")
                 .help("Outputs a Hack file")
-                .conflicts_with_all(&["asm", "bin"]),
+                .conflicts_with_all(&["asm", "bin", "vm"]),
+        )
+        .arg(
+            Arg::with_name("vm")
+                .long("vm")
+                .help("Outputs a VM file for each class into the output directory")
+                .conflicts_with_all(&["asm", "bin", "hack"]),
+        )
+        .arg(
+            Arg::with_name("annotate")
+                .long("annotate")
+                .help("Annotates VM output with the Jack statements it was compiled from")
+                .requires("vm"),
+        )
+        .arg(
+            Arg::with_name("debug-checks")
+                .long("debug-checks")
+                .help("Emits runtime checks for null dereferences and array bounds"),
+        )
+        .arg(
+            Arg::with_name("opt-level")
+                .short("O")
+                .help("Optimisation level for VM code (default: 0)")
+                .takes_value(true)
+                .possible_values(&["0", "1", "2"]),
+        )
+        .arg(
+            Arg::with_name("optimize-asm")
+                .long("optimize-asm")
+                .help("Removes redundant instructions from the generated assembly")
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("remove-unreachable")
+                .long("remove-unreachable")
+                .help("Leaves out functions that can't be reached from the init function")
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("tail-calls")
+                .long("tail-calls")
+                .help("Reuses the caller's stack frame for calls immediately followed by a return")
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("static-frames")
+                .long("static-frames")
+                .help("Gives the arguments and locals of functions which can't recurse fixed addresses in RAM")
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("stack-margin")
+                .long("stack-margin")
+                .help("Words of stack to leave below the static frames for recursive and external calls (default: 256)")
+                .takes_value(true)
+                .requires("static-frames"),
+        )
+        .arg(
+            Arg::with_name("opt-for")
+                .long("opt-for")
+                .help("Whether calls and returns favour speed or code size, or speed only for functions called in loops or recursively (default: size)")
+                .takes_value(true)
+                .possible_values(&["speed", "size", "auto"])
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("cache-stack-top")
+                .long("cache-stack-top")
+                .help("Keeps the value on top of the stack in the D register where possible")
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("memory-layout")
+                .long("memory-layout")
+                .help("Changes where the stack, heap and static variables live in RAM, e.g. `stack=256,heap=2048,statics=16..256`")
+                .takes_value(true)
+                .conflicts_with("vm"),
+        )
+        .arg(
+            Arg::with_name("emit")
+                .long("emit")
+                .help("Outputs an intermediate representation of each Jack file")
+                .takes_value(true)
+                .possible_values(EMIT_NAMES)
+                .conflicts_with_all(&["asm", "bin", "hack", "vm"]),
         )
         .arg(
             Arg::with_name("init")
@@ -81,10 +286,36 @@ fn run() -> Result<()> {
                 .help("Program execution does not start from an init function")
                 .conflicts_with("init"),
         )
+        .arg(
+            Arg::with_name("external")
+                .long("external")
+                .help("Allows calls to a function that isn't defined, or to any function in a class given as `Class.*`")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("no-validate")
+                .long("no-validate")
+                .help("Translates VM code without first checking it for mistakes")
+                .conflicts_with("external"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Reports calls to OS functions that aren't defined as errors rather than warnings")
+                .conflicts_with("no-validate"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Reports what the optimisations did, such as which unreachable functions were removed"),
+        )
         .arg(
             Arg::with_name("output")
                 .short("o")
-                .help("Writes the output to <file>")
+                .help("Writes the output to <file>, or to a directory when used with --vm or --emit")
                 .takes_value(true)
                 .conflicts_with("stdout"),
         )
@@ -94,42 +325,152 @@ fn run() -> Result<()> {
                 .help("Writes the output to stdout")
                 .conflicts_with("output"),
         )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about("Analyses a program without compiling it")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("callgraph")
+                        .about("Reports recursive cycles and the maximum stack depth of a program")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("Files or directories to be analysed")
+                                .multiple(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("dot")
+                                .long("dot")
+                                .help("Outputs the call graph in Graphviz's DOT language"),
+                        )
+                        .arg(
+                            Arg::with_name("init")
+                                .long("init")
+                                .help("Name of the program's entry point (default: Sys.init)")
+                                .takes_value(true)
+                                .conflicts_with("no-init"),
+                        )
+                        .arg(
+                            Arg::with_name("no-init")
+                                .long("no-init")
+                                .help("Program execution does not start from an init function")
+                                .conflicts_with("init"),
+                        )
+                        .arg(
+                            Arg::with_name("memory-layout")
+                                .long("memory-layout")
+                                .help("Changes where the stack, heap and static variables live in RAM")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .short("o")
+                                .help("Writes the output to <file>")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doc")
+                .about("Generates an API reference from the doc comments in Jack source files")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Files or directories to be documented")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("markdown")
+                        .long("markdown")
+                        .help("Outputs Markdown instead of HTML"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .help("Writes the output to <file>")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats Jack source files in place")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Files or directories to be formatted")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Lists unformatted files without modifying them"),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("analyze") {
+        if let Some(matches) = matches.subcommand_matches("callgraph") {
+            return analyze_callgraph(matches);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("doc") {
+        return document_files(matches);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("fmt") {
+        return format_files(matches);
+    }
+
     let paths = matches
         .values_of("file")
         .unwrap()
         .map(|p| Path::new(p).canonicalize())
         .collect::<io::Result<Vec<_>>>()?;
 
-    let mut modules = Vec::new();
+    let opts = CompileOptions::from_matches(&matches);
+    let emit = matches.value_of("emit");
 
-    for path in &paths {
-        if path.is_dir() {
-            modules.extend(compile_dir(path)?);
-        } else {
-            modules.push(
-                compile_file(path)
-                    .unwrap_or_else(|| Err(anyhow!("unsupported file extension")))?,
-            );
-        }
+    if let Some(emit) = emit.and_then(Emit::from_name) {
+        return emit_files(&matches, &paths, emit);
     }
 
-    if modules.is_empty() {
-        return Err(anyhow!("missing input files"));
+    let Inputs { mut modules, asm } = compile_paths(&paths, opts)?;
+    vm::link_static_inits(&mut modules)?;
+
+    let mut optimizer = Optimizer::new();
+    optimizer.level(matches.value_of("opt-level").unwrap_or("0").parse()?);
+
+    for module in &mut modules {
+        optimizer.optimize(module);
     }
 
-    let mut translator = Translator::new(&modules);
+    if matches.is_present("vm") {
+        if !asm.is_empty() {
+            return Err(anyhow!("`.asm` files can't be compiled to VM code"));
+        }
 
-    if let Some(init) = matches.value_of("init") {
-        translator.init(Some(init.to_owned()));
-    } else if matches.is_present("no-init") {
-        translator.init(None);
+        return write_vm_files(
+            &modules,
+            matches.value_of("output"),
+            matches.is_present("stdout"),
+        );
     }
 
-    let insts = translator.translate()?;
+    let layout = memory_layout(&matches)?;
+
+    // A program written entirely in assembly is assembled as it is, without any bootstrap code.
+    // Otherwise, any assembly is linked in after the translated VM code.
+    let mut insts = if modules.is_empty() {
+        Vec::new()
+    } else {
+        translate(&matches, &modules, &layout, &asm)?
+    };
+    insts.extend(asm);
 
-    let format = if matches.is_present("asm") {
+    let format = if let Some(format) = emit.and_then(Emit::format) {
+        format
+    } else if matches.is_present("asm") {
         Format::Asm
     } else if matches.is_present("bin") {
         Format::Bin
@@ -140,7 +481,7 @@ fn run() -> Result<()> {
     if matches.is_present("stdout") {
         let stdout = io::stdout();
         let mut out = stdout.lock();
-        write_output(&mut out, &insts, format)
+        write_output(&mut out, &insts, &layout, format)
     } else {
         let filename = matches
             .value_of("output")
@@ -148,7 +489,7 @@ fn run() -> Result<()> {
             .unwrap_or_else(|| {
                 // Use the stem of the first input path as the default output filename.
                 let stem = paths
-                    .get(0)
+                    .first()
                     .and_then(|p| p.file_stem())
                     .and_then(|s| s.to_str())
                     .unwrap_or("out");
@@ -158,44 +499,330 @@ fn run() -> Result<()> {
 
         let file = File::create(filename)?;
         let mut writer = BufWriter::new(file);
-        write_output(&mut writer, &insts, format)
+        write_output(&mut writer, &insts, &layout, format)
+    }
+}
+
+fn format_files(matches: &ArgMatches) -> Result<()> {
+    let check = matches.is_present("check");
+    let mut unformatted = 0;
+
+    let paths = matches
+        .values_of("file")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    for file in jack_files(&paths)? {
+        let source = fs::read_to_string(&file)?;
+        let formatted =
+            format_jack(&source).map_err(|err| anyhow!("{}: {}", file.display(), err))?;
+
+        if formatted != source {
+            if check {
+                println!("{}", file.display());
+                unformatted += 1;
+            } else {
+                fs::write(&file, formatted)?;
+            }
+        }
+    }
+
+    if unformatted > 0 {
+        return Err(anyhow!("{} file(s) need formatting", unformatted));
     }
+
+    Ok(())
 }
 
-fn compile_dir(dir: &Path) -> Result<Vec<Module>> {
-    let mut mods = Vec::new();
+fn document_files(matches: &ArgMatches) -> Result<()> {
+    let paths = matches
+        .values_of("file")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    let mut classes = Vec::new();
+
+    for file in jack_files(&paths)? {
+        let source = fs::read_to_string(&file)?;
+        classes
+            .push(ClassDoc::parse(&source).map_err(|err| anyhow!("{}: {}", file.display(), err))?);
+    }
+
+    if classes.is_empty() {
+        return Err(anyhow!("missing input files"));
+    }
+
+    let (output, default_filename) = if matches.is_present("markdown") {
+        (jack::docs_to_markdown(&classes), "doc.md")
+    } else {
+        (jack::docs_to_html(&classes), "doc.html")
+    };
+
+    let filename = matches.value_of("output").unwrap_or(default_filename);
+    fs::write(filename, output)?;
+
+    Ok(())
+}
+
+fn analyze_callgraph(matches: &ArgMatches) -> Result<()> {
+    let paths = matches
+        .values_of("file")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    let layout = memory_layout(matches)?;
+    let mut modules = compile_paths(&paths, CompileOptions::default())?.modules;
+    vm::link_static_inits(&mut modules)?;
+    let graph = CallGraph::new(&modules);
+
+    let output = if matches.is_present("dot") {
+        graph.to_dot()
+    } else {
+        callgraph_report(&graph, init_function(matches), &layout)
+    };
+
+    match matches.value_of("output") {
+        Some(filename) => fs::write(filename, output)?,
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
+
+fn callgraph_report(graph: &CallGraph, init: Option<String>, layout: &MemoryLayout) -> String {
+    let mut report = String::new();
+    let cycles = graph.cycles();
+
+    if cycles.is_empty() {
+        report.push_str("No recursive functions\n");
+    } else {
+        report.push_str("Recursive functions:\n");
+        for cycle in cycles {
+            report.push_str(&format!("    {}\n", cycle.join(", ")));
+        }
+    }
+
+    let (depth, entry) = match init {
+        Some(init) => (graph.stack_depth(&init), init),
+        None => (graph.top_level_stack_depth(), "top-level code".to_owned()),
+    };
+
+    let available = (layout.heap_base - layout.stack_base) as usize;
+    report.push_str(&format!(
+        "\nMaximum stack depth ({} words available):\n",
+        available
+    ));
+
+    let overflow = match depth {
+        StackDepth::Bounded(words) if words > available => " (overflows the stack)",
+        _ => "",
+    };
+    report.push_str(&format!("    {}: {}{}\n", entry, depth, overflow));
+
+    report
+}
+
+fn format_jack(source: &str) -> Result<String> {
+    let (tokens, trailing) = Tokenizer::new(source).tokenize_with_trivia()?;
+    let tree = SyntaxParser::new(tokens, trailing).parse()?;
+
+    Ok(Formatter::new(&tree).format())
+}
+
+fn emit_files(matches: &ArgMatches, paths: &[PathBuf], emit: Emit) -> Result<()> {
+    let files = jack_files(paths)?;
+
+    if files.is_empty() {
+        return Err(anyhow!("missing input files"));
+    }
+
+    // Each file is written next to its source, unless a directory is given.
+    let dir = matches.value_of("output").map(Path::new);
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    for file in files {
+        let source = fs::read_to_string(&file)?;
+        let tokens = Tokenizer::new(&source).tokenize()?;
+
+        let output = match emit {
+            Emit::TokensXml => jack::tokens_to_xml(&tokens),
+            Emit::ParseXml => jack::class_to_xml(&jack::Parser::new(tokens).parse()?),
+            #[cfg(feature = "serde")]
+            Emit::TokensJson => to_json(&tokens)?,
+            #[cfg(feature = "serde")]
+            Emit::AstJson => to_json(&jack::Parser::new(tokens).parse()?)?,
+            #[cfg(feature = "serde")]
+            Emit::VmJson => to_json(&compile_jack(&file, CompileOptions::from_matches(matches))?)?,
+        };
+
+        if matches.is_present("stdout") {
+            io::stdout().write_all(output.as_bytes())?;
+        } else {
+            let stem = file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| anyhow!("invalid file name"))?;
+
+            let path = match dir {
+                Some(dir) => dir.join(emit.filename(stem)),
+                None => file.with_file_name(emit.filename(stem)),
+            };
+            fs::write(path, output)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    let mut json = serde_json::to_string_pretty(value)?;
+    json.push('\n');
+    Ok(json)
+}
+
+// Expands any directories in the given list of paths into the Jack files they contain.
+fn jack_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            let mut dir_files = Vec::new();
+
+            for entry in path.read_dir()? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|ext| ext == "jack") {
+                    dir_files.push(file);
+                }
+            }
+
+            // Sort files by name to ensure reproducible output.
+            dir_files.sort();
+            files.extend(dir_files);
+        } else if path.extension().is_some_and(|ext| ext == "jack") {
+            files.push(path.clone());
+        } else {
+            return Err(anyhow!("`{}` is not a Jack file", path.display()));
+        }
+    }
+
+    Ok(files)
+}
+
+// Writes each module to a `.vm` file named after it, in the same layout as the output of the
+// Nand to Tetris course's own JackCompiler.
+fn write_vm_files(modules: &[Module], dir: Option<&str>, stdout: bool) -> Result<()> {
+    if stdout {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        for module in modules {
+            write!(out, "{}", module)?;
+        }
+
+        return Ok(());
+    }
+
+    let dir = Path::new(dir.unwrap_or("."));
+    fs::create_dir_all(dir)?;
+
+    for module in modules {
+        fs::write(dir.join(format!("{}.vm", module.name)), module.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn compile_paths(paths: &[PathBuf], opts: CompileOptions) -> Result<Inputs> {
+    let mut inputs = Inputs::default();
+
+    for path in paths {
+        if path.is_dir() {
+            for input in compile_dir(path, opts)? {
+                inputs.add(input);
+            }
+        } else {
+            inputs.add(
+                compile_file(path, opts)
+                    .unwrap_or_else(|| Err(anyhow!("unsupported file extension")))?,
+            );
+        }
+    }
+
+    if inputs.modules.is_empty() && inputs.asm.is_empty() {
+        return Err(anyhow!("missing input files"));
+    }
+
+    Ok(inputs)
+}
+
+// The function called to start the program, as given by the `--init` and `--no-init` flags.
+fn init_function(matches: &ArgMatches) -> Option<String> {
+    if matches.is_present("no-init") {
+        None
+    } else {
+        Some(
+            matches
+                .value_of("init")
+                .unwrap_or(vm::DEFAULT_INIT)
+                .to_owned(),
+        )
+    }
+}
+
+fn memory_layout(matches: &ArgMatches) -> Result<MemoryLayout> {
+    match matches.value_of("memory-layout") {
+        Some(spec) => spec
+            .parse()
+            .map_err(|err| anyhow!("invalid memory layout: {}", err)),
+        None => Ok(MemoryLayout::new()),
+    }
+}
+
+fn compile_dir(dir: &Path, opts: CompileOptions) -> Result<Vec<Input>> {
+    let mut inputs = Vec::new();
 
     for entry in dir.read_dir()? {
-        if let Some(module) = compile_file(&entry?.path()) {
-            mods.push(module?);
+        if let Some(input) = compile_file(&entry?.path(), opts) {
+            inputs.push(input?);
         }
     }
 
-    // Sort modules by name to ensure reproducible builds.
-    mods.sort_by(|a, b| a.name.cmp(&b.name));
+    // Sort inputs by name to ensure reproducible builds.
+    inputs.sort_by(|a, b| a.name().cmp(b.name()));
 
-    Ok(mods)
+    Ok(inputs)
 }
 
-fn compile_file(path: &Path) -> Option<Result<Module>> {
+fn compile_file(path: &Path, opts: CompileOptions) -> Option<Result<Input>> {
     path.extension().and_then(|ext| {
         if ext == "jack" {
-            Some(compile_jack(path))
+            Some(compile_jack(path, opts).map(Input::Module))
         } else if ext == "vm" {
-            Some(compile_vm(path))
+            Some(compile_vm(path).map(Input::Module))
+        } else if ext == "asm" {
+            Some(parse_asm(path))
         } else {
             None
         }
     })
 }
 
-fn compile_jack(path: &Path) -> Result<Module> {
+fn compile_jack(path: &Path, opts: CompileOptions) -> Result<Module> {
     let source = fs::read_to_string(path)?;
 
     let tokens = Tokenizer::new(&source).tokenize()?;
     let class = jack::Parser::new(tokens).parse()?;
 
-    Compiler::new(&class).compile()
+    let mut compiler = Compiler::new(&class);
+    compiler.debug_checks(opts.debug_checks);
+    compiler.annotate(opts.annotate);
+    compiler.compile()
 }
 
 fn compile_vm(path: &Path) -> Result<Module> {
@@ -206,12 +833,113 @@ fn compile_vm(path: &Path) -> Result<Module> {
         .into();
 
     let source = fs::read_to_string(path)?;
-    let cmds = vm::parse(&source)?;
+    let cmds = vm::parse(&source).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
 
     Ok(Module::new(name, cmds))
 }
 
-fn write_output<W>(out: &mut W, insts: &[Instruction], format: Format) -> Result<()>
+fn parse_asm(path: &Path) -> Result<Input> {
+    let name = path
+        .file_stem()
+        .ok_or_else(|| anyhow!("invalid file name"))?
+        .to_string_lossy()
+        .into();
+
+    let source = fs::read_to_string(path)?;
+    let prog = asm::parse(&source).map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+
+    Ok(Input::Asm(name, prog))
+}
+
+// Translates the program's VM code, allowing it to call any function defined by a label in the
+// assembly which will be linked alongside it.
+fn translate(
+    matches: &ArgMatches,
+    modules: &[Module],
+    layout: &MemoryLayout,
+    asm: &[Instruction],
+) -> Result<Vec<Instruction>> {
+    let init = init_function(matches);
+
+    if !matches.is_present("no-validate") {
+        let mut validator = Validator::new(modules);
+        validator.init(init.clone());
+        validator.layout(layout.clone());
+        for name in matches.values_of("external").into_iter().flatten() {
+            validator.external(name.to_owned());
+        }
+        for instr in asm {
+            if let Instruction::Label(label) = instr {
+                validator.external(label.clone());
+            }
+        }
+        // Like the course's own tools, programs may leave out the OS `.vm` files, though the
+        // output can't run without them.
+        validator.os_external(!matches.is_present("strict"));
+        validator.validate()?;
+
+        let missing = validator.undefined_os_functions();
+        if !matches.is_present("strict") && !missing.is_empty() {
+            eprintln!(
+                "Warning: the program calls OS functions which aren't defined, so the OS `.vm` \
+                 files need to be included for it to run: {}",
+                missing.join(", ")
+            );
+        }
+    }
+
+    let mut translator = Translator::new(modules);
+    translator.init(init);
+    translator.remove_unreachable(matches.is_present("remove-unreachable"));
+    // Functions which are only called from the linked assembly still have to be kept.
+    for instr in asm {
+        if let Instruction::A(Load::Symbol(symbol)) = instr {
+            translator.root(symbol.clone());
+        }
+    }
+    translator.layout(layout.clone())?;
+    translator.tail_calls(matches.is_present("tail-calls"));
+    translator.static_frames(matches.is_present("static-frames"));
+    if let Some(words) = matches.value_of("stack-margin") {
+        translator.stack_margin(words.parse()?);
+    }
+    match matches.value_of("opt-for") {
+        Some("speed") => translator.optimize_for(OptimizeFor::Speed),
+        Some("auto") => {
+            for func in CallGraph::new(modules).hot_functions() {
+                translator.optimize_function_for(func.to_owned(), OptimizeFor::Speed);
+            }
+        }
+        _ => translator.optimize_for(OptimizeFor::Size),
+    }
+    if matches.is_present("cache-stack-top") {
+        translator.strategy(Strategy::CachedTop);
+    }
+
+    let mut insts = translator.translate()?;
+    if matches.is_present("optimize-asm") {
+        insts = asm::optimize(insts);
+    }
+
+    let removed = translator.removed();
+    if matches.is_present("verbose") && !removed.is_empty() {
+        eprintln!(
+            "Removed {} unreachable function{}: {}",
+            removed.len(),
+            if removed.len() == 1 { "" } else { "s" },
+            removed.join(", ")
+        );
+    }
+
+    Ok(insts)
+}
+
+fn write_output<W>(
+    out: &mut W,
+    insts: &[Instruction],
+    layout: &MemoryLayout,
+    format: Format,
+) -> Result<()>
 where
     W: Write,
 {
@@ -222,15 +950,17 @@ where
             }
         }
         Format::Bin => {
-            for inst in asm::assemble(insts)? {
+            for inst in asm::assemble_with_layout(insts, layout)? {
                 out.write_u16::<BigEndian>(inst)?;
             }
         }
         Format::Hack => {
-            for inst in asm::assemble(insts)? {
+            for inst in asm::assemble_with_layout(insts, layout)? {
                 writeln!(out, "{:016b}", inst)?;
             }
         }
+        #[cfg(feature = "serde")]
+        Format::Json => out.write_all(to_json(&insts)?.as_bytes())?,
     };
 
     // As we're using a BufWriter, we need to ensure all writes are flushed to disk.
@@ -0,0 +1,473 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+// Every call pushes the return address and the caller's LCL, ARG, THIS and THAT.
+const CALL_FRAME: usize = 5;
+
+// The functions defined by a program, along with the functions each of them calls.
+pub struct CallGraph<'a> {
+    functions: Vec<&'a str>,
+    frames: HashMap<&'a str, Frame<'a>>,
+    // Commands outside of any function, which run when the program is translated without an
+    // init function.
+    top_level: Frame<'a>,
+    // The stack counted for a call which can't be bounded, if such calls aren't to give up.
+    margin: Option<usize>,
+}
+
+// An upper bound on the number of words of stack used by a call.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StackDepth {
+    Bounded(usize),
+    // The call can recurse through the named function, so there's no bound.
+    Recursive(String),
+    // The call can reach the named function, which isn't defined in the program.
+    Undefined(String),
+}
+
+impl fmt::Display for StackDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackDepth::Bounded(words) => write!(f, "{} words", words),
+            StackDepth::Recursive(func) => write!(f, "unbounded (`{}` is recursive)", func),
+            StackDepth::Undefined(func) => write!(f, "unknown (`{}` is not defined)", func),
+        }
+    }
+}
+
+// Where a function's arguments and locals live when they're given fixed addresses rather than
+// being kept on the stack. Slots are numbered from the start of the area set aside for frames.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StaticFrame {
+    // The slot of the first argument, which is followed by the rest of the arguments and then
+    // the locals.
+    pub base: usize,
+    pub args: usize,
+    pub locals: usize,
+}
+
+impl StaticFrame {
+    pub fn size(&self) -> usize {
+        self.args + self.locals
+    }
+}
+
+impl<'a> CallGraph<'a> {
+    pub fn new(modules: &'a [Module]) -> Self {
+        let mut functions = Vec::new();
+        let mut frames = HashMap::new();
+        let mut top_level = Frame::new(0);
+
+        for module in modules {
+            let mut current = &mut top_level;
+
+            for cmd in &module.cmds {
+                if let Command::Function(name, locals) = cmd {
+                    functions.push(name.as_str());
+                    current = frames
+                        .entry(name.as_str())
+                        .or_insert_with(|| Frame::new(*locals as usize));
+                } else {
+                    current.push(cmd);
+                }
+            }
+        }
+
+        CallGraph {
+            functions,
+            frames,
+            top_level,
+            margin: None,
+        }
+    }
+
+    // Counts each call which recurses, or which reaches a function that isn't defined, as using
+    // the given number of words of stack, so that every stack depth has a bound.
+    pub fn margin(&mut self, words: usize) {
+        self.margin = Some(words);
+    }
+
+    // All the functions in the program, in the order in which they're defined.
+    pub fn functions(&self) -> &[&'a str] {
+        &self.functions
+    }
+
+    // The distinct functions called by the given function, in the order they're first called.
+    pub fn callees(&self, func: &str) -> Vec<&'a str> {
+        self.frames
+            .get(func)
+            .map_or_else(Vec::new, |frame| frame.callees())
+    }
+
+    pub fn top_level(&self) -> Vec<&'a str> {
+        self.top_level.callees()
+    }
+
+    // Finds every function which can be called, directly or indirectly, from the given roots.
+    pub fn reachable<I>(&self, roots: I) -> HashSet<&'a str>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut reachable = HashSet::new();
+        let mut stack = roots.into_iter().collect::<Vec<_>>();
+
+        while let Some(func) = stack.pop() {
+            if reachable.insert(func) {
+                stack.extend(self.callees(func));
+            }
+        }
+
+        reachable
+    }
+
+    // Finds each group of mutually recursive functions, including any function which calls
+    // itself directly. Groups are listed in the order of their first function's definition.
+    pub fn cycles(&self) -> Vec<Vec<&'a str>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            components: Vec::new(),
+        };
+
+        for func in &self.functions {
+            if !tarjan.index.contains_key(func) {
+                tarjan.visit(func);
+            }
+        }
+
+        let position = |func: &str| self.functions.iter().position(|f| *f == func);
+
+        let mut cycles = tarjan
+            .components
+            .into_iter()
+            .filter(|component| match component.as_slice() {
+                [func] => self.callees(func).contains(func),
+                _ => true,
+            })
+            .map(|mut component| {
+                component.sort_by_key(|func| position(func));
+                component
+            })
+            .collect::<Vec<_>>();
+
+        cycles.sort_by_key(|component| position(component[0]));
+        cycles
+    }
+
+    // The functions which are likely to be called many times each time the program runs: any
+    // function called from inside a loop or which is recursive, and every function those call.
+    pub fn hot_functions(&self) -> HashSet<&'a str> {
+        let looped = self
+            .frames
+            .values()
+            .chain(Some(&self.top_level))
+            .flat_map(|frame| frame.looped_calls.iter().copied());
+
+        self.reachable(looped.chain(self.cycles().into_iter().flatten()))
+    }
+
+    // Gives a static frame to each function which can't recurse, as only one call to it can be
+    // running at any time. Functions which are never running at the same time as each other
+    // share slots, so the area only needs to be as big as the deepest chain of calls.
+    pub fn static_frames(&self) -> HashMap<&'a str, StaticFrame> {
+        let recursive = self.cycles().into_iter().flatten().collect::<HashSet<_>>();
+        let mut functions = self
+            .frames
+            .keys()
+            .copied()
+            .filter(|func| !recursive.contains(func))
+            .collect::<Vec<_>>();
+
+        // The functions which can still be running when each function is called.
+        let mut callers = HashMap::<&str, Vec<&str>>::new();
+        for caller in &functions {
+            for callee in self.reachable(self.callees(caller)) {
+                callers.entry(callee).or_default().push(caller);
+            }
+        }
+
+        // A function's callers have strictly fewer callers of their own, so taking functions in
+        // that order places every caller's frame before those of the functions it calls.
+        let count = |func: &str| callers.get(func).map_or(0, Vec::len);
+        functions.sort_by_key(|func| (count(func), *func));
+
+        let mut frames = HashMap::<&str, StaticFrame>::new();
+        for func in functions {
+            let base = callers
+                .get(func)
+                .into_iter()
+                .flatten()
+                .map(|caller| frames[caller].base + frames[caller].size())
+                .max()
+                .unwrap_or(0);
+
+            let frame = &self.frames[func];
+            let frame = StaticFrame {
+                base,
+                args: frame.args,
+                locals: frame.locals.max(frame.locals_used),
+            };
+            frames.insert(func, frame);
+        }
+
+        frames
+    }
+
+    // The number of words of stack used by calling the given function, from the arguments
+    // already pushed by the caller through to the deepest point of any call it makes.
+    pub fn stack_depth(&self, func: &str) -> StackDepth {
+        self.call_depth(func, &mut Vec::new(), &mut HashMap::new())
+    }
+
+    // The number of words of stack used by the commands outside of any function.
+    pub fn top_level_stack_depth(&self) -> StackDepth {
+        self.frame_depth(&self.top_level, &mut Vec::new(), &mut HashMap::new())
+    }
+
+    // Writes out the graph in Graphviz's DOT language. Recursive calls are drawn in red, and
+    // functions which aren't defined in the program are dashed.
+    pub fn to_dot(&self) -> String {
+        let cycles = self.cycles();
+        let cycle = |func: &str| cycles.iter().position(|cycle| cycle.contains(&func));
+
+        let mut dot = String::from("digraph callgraph {\n    node [shape=box];\n");
+        let mut undefined = Vec::new();
+
+        for func in &self.functions {
+            let attrs = if cycle(func).is_some() {
+                " [color=red]"
+            } else {
+                ""
+            };
+            writeln!(dot, "    \"{}\"{};", func, attrs).unwrap();
+
+            for callee in self.callees(func) {
+                if !self.frames.contains_key(callee) && !undefined.contains(&callee) {
+                    undefined.push(callee);
+                }
+            }
+        }
+
+        for func in undefined {
+            writeln!(dot, "    \"{}\" [style=dashed];", func).unwrap();
+        }
+
+        for func in &self.functions {
+            for callee in self.callees(func) {
+                let attrs = if cycle(func).is_some() && cycle(func) == cycle(callee) {
+                    " [color=red]"
+                } else {
+                    ""
+                };
+                writeln!(dot, "    \"{}\" -> \"{}\"{};", func, callee, attrs).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn call_depth(
+        &self,
+        func: &'a str,
+        active: &mut Vec<&'a str>,
+        memo: &mut HashMap<&'a str, StackDepth>,
+    ) -> StackDepth {
+        if let Some(depth) = memo.get(func) {
+            return depth.clone();
+        }
+
+        let frame = match self.frames.get(func) {
+            Some(frame) => frame,
+            None => return self.unbounded(StackDepth::Undefined(func.to_owned())),
+        };
+
+        if active.contains(&func) {
+            return self.unbounded(StackDepth::Recursive(func.to_owned()));
+        }
+
+        active.push(func);
+        let depth = match self.frame_depth(frame, active, memo) {
+            StackDepth::Bounded(words) => StackDepth::Bounded(CALL_FRAME + words),
+            depth => depth,
+        };
+        active.pop();
+
+        memo.insert(func, depth.clone());
+        depth
+    }
+
+    fn frame_depth(
+        &self,
+        frame: &Frame<'a>,
+        active: &mut Vec<&'a str>,
+        memo: &mut HashMap<&'a str, StackDepth>,
+    ) -> StackDepth {
+        let mut max = frame.max_depth;
+
+        for (callee, depth) in &frame.calls {
+            match self.call_depth(callee, active, memo) {
+                StackDepth::Bounded(words) => max = max.max(depth + words),
+                unbounded => return unbounded,
+            }
+        }
+
+        StackDepth::Bounded(frame.locals + max)
+    }
+
+    fn unbounded(&self, depth: StackDepth) -> StackDepth {
+        self.margin.map_or(depth, StackDepth::Bounded)
+    }
+}
+
+// The stack usage of a single function, as worked out from its commands.
+struct Frame<'a> {
+    locals: usize,
+    // The number of arguments and locals which are accessed, which may be more than the number
+    // of locals declared.
+    args: usize,
+    locals_used: usize,
+    // The deepest the operand stack gets, not counting any calls.
+    max_depth: usize,
+    // Each call made, along with the depth of the operand stack when it's made, including the
+    // arguments to the call.
+    calls: Vec<(&'a str, usize)>,
+    depth: usize,
+    // The deepest the operand stack is at any jump to each label.
+    labels: HashMap<&'a str, usize>,
+    // The number of calls made before each label, so that a jump back to the label can mark
+    // the calls after it as being inside a loop.
+    label_calls: HashMap<&'a str, usize>,
+    looped_calls: Vec<&'a str>,
+}
+
+impl<'a> Frame<'a> {
+    fn new(locals: usize) -> Self {
+        Frame {
+            locals,
+            args: 0,
+            locals_used: 0,
+            max_depth: 0,
+            calls: Vec::new(),
+            depth: 0,
+            labels: HashMap::new(),
+            label_calls: HashMap::new(),
+            looped_calls: Vec::new(),
+        }
+    }
+
+    // Works out the stack usage of a command. The operand stack is assumed to be the same depth
+    // every time a label is reached, which always holds for code generated from Jack, so the
+    // commands can be scanned in order and a label picks up the depth of any jump to it.
+    fn push(&mut self, cmd: &'a Command) {
+        match cmd {
+            Command::Push(segment, idx) | Command::Pop(segment, idx) => {
+                let used = *idx as usize + 1;
+                match segment {
+                    Segment::Argument => self.args = self.args.max(used),
+                    Segment::Local => self.locals_used = self.locals_used.max(used),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        match cmd {
+            Command::Push(..) => self.depth += 1,
+            Command::Add
+            | Command::Sub
+            | Command::And
+            | Command::Or
+            | Command::Eq
+            | Command::Gt
+            | Command::Lt
+            | Command::Pop(..) => self.depth = self.depth.saturating_sub(1),
+            Command::Neg | Command::Not | Command::Return | Command::Function(..) => {}
+            Command::Label(label) => {
+                if let Some(depth) = self.labels.get(label.as_str()) {
+                    self.depth = self.depth.max(*depth);
+                }
+                self.label_calls.insert(label, self.calls.len());
+            }
+            Command::Goto(label) => self.jump(label),
+            Command::IfGoto(label) => {
+                self.depth = self.depth.saturating_sub(1);
+                self.jump(label);
+            }
+            Command::Call(func, args) => {
+                self.calls.push((func, self.depth));
+                self.depth = self.depth.saturating_sub(*args as usize) + 1;
+            }
+        }
+
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+
+    fn jump(&mut self, label: &'a str) {
+        let depth = self.labels.entry(label).or_insert(0);
+        *depth = (*depth).max(self.depth);
+
+        if let Some(start) = self.label_calls.get(label) {
+            let calls = self.calls[*start..].iter().map(|(func, _)| *func);
+            self.looped_calls.extend(calls);
+        }
+    }
+
+    fn callees(&self) -> Vec<&'a str> {
+        let mut callees = Vec::new();
+        for (func, _) in &self.calls {
+            if !callees.contains(func) {
+                callees.push(*func);
+            }
+        }
+        callees
+    }
+}
+
+// Tarjan's algorithm for finding the strongly connected components of the call graph.
+struct Tarjan<'g, 'a> {
+    graph: &'g CallGraph<'a>,
+    index: HashMap<&'a str, usize>,
+    lowlink: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'_, 'a> {
+    fn visit(&mut self, func: &'a str) {
+        let index = self.index.len();
+        self.index.insert(func, index);
+        self.lowlink.insert(func, index);
+        self.stack.push(func);
+
+        for callee in self.graph.callees(func) {
+            if !self.graph.frames.contains_key(callee) {
+                continue;
+            }
+
+            if !self.index.contains_key(callee) {
+                self.visit(callee);
+                let low = self.lowlink[func].min(self.lowlink[callee]);
+                self.lowlink.insert(func, low);
+            } else if self.stack.contains(&callee) {
+                let low = self.lowlink[func].min(self.index[callee]);
+                self.lowlink.insert(func, low);
+            }
+        }
+
+        if self.lowlink[func] == index {
+            let mut component = Vec::new();
+            loop {
+                let member = self.stack.pop().unwrap();
+                component.push(member);
+                if member == func {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
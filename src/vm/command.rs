@@ -1,6 +1,7 @@
 use std::fmt;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Command {
     Add,
     Sub,
@@ -46,6 +47,7 @@ impl fmt::Display for Command {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Segment {
     Argument,
     Local,
@@ -57,6 +59,19 @@ pub enum Segment {
     Temp,
 }
 
+impl Segment {
+    // The highest valid index into segments which map onto a fixed number of registers. The
+    // size of the static segment depends on how many statics the other modules use, so it can
+    // only be checked once the whole program is known.
+    pub fn max_index(self) -> Option<u16> {
+        match self {
+            Segment::Pointer => Some(1),
+            Segment::Temp => Some(7),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Segment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -0,0 +1,430 @@
+use super::*;
+use crate::asm::MemoryLayout;
+use crate::hack::{RAM, RAM_SIZE};
+use anyhow::{anyhow, Error};
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// A function implemented natively rather than in VM code, which is given the arguments to the
+// call and returns its result.
+pub type Native<'a> = Box<dyn FnMut(&mut RAM, &[u16]) -> Result<u16> + 'a>;
+
+// The number of commands the interpreter will run before giving up on a call, unless told
+// otherwise.
+const DEFAULT_STEP_LIMIT: usize = 10_000_000;
+
+const SP: u16 = 0;
+const LCL: u16 = 1;
+const ARG: u16 = 2;
+const THIS: u16 = 3;
+const THAT: u16 = 4;
+const TEMP: u16 = 5;
+
+// Runs VM commands directly, using the same memory layout as the code generated by the
+// translator: the stack pointer and segment registers live at the bottom of RAM, static
+// variables are allocated from the start of the static area in the order in which they're first
+// used, and the stack grows upwards from its base.
+pub struct Interpreter<'a> {
+    pub ram: RAM,
+    program: Vec<Instr<'a>>,
+    functions: HashMap<&'a str, usize>,
+    labels: HashMap<(Option<&'a str>, &'a str), usize>,
+    // The position of each module's static variables within the static area.
+    statics: HashMap<(usize, u16), u16>,
+    layout: MemoryLayout,
+    natives: HashMap<&'a str, Native<'a>>,
+    init: Option<String>,
+    step_limit: usize,
+    // The function and return address of each call in progress.
+    frames: Vec<(&'a str, usize)>,
+    pc: usize,
+    started: bool,
+}
+
+struct Instr<'a> {
+    module: usize,
+    // The function which the command belongs to, which is the scope of any label it uses.
+    function: Option<&'a str>,
+    cmd: &'a Command,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(modules: &'a [Module]) -> Self {
+        let mut program = Vec::new();
+        let mut functions = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut statics = HashMap::new();
+        let mut function = None;
+
+        for (module, m) in modules.iter().enumerate() {
+            for cmd in &m.cmds {
+                match cmd {
+                    Command::Function(name, _) => {
+                        function = Some(name.as_str());
+                        functions.entry(name.as_str()).or_insert(program.len());
+                    }
+                    Command::Label(label) => {
+                        labels
+                            .entry((function, label.as_str()))
+                            .or_insert(program.len());
+                    }
+                    Command::Push(Segment::Static, idx) | Command::Pop(Segment::Static, idx) => {
+                        let next = statics.len() as u16;
+                        statics.entry((module, *idx)).or_insert(next);
+                    }
+                    _ => {}
+                }
+
+                program.push(Instr {
+                    module,
+                    function,
+                    cmd,
+                });
+            }
+        }
+
+        Interpreter {
+            ram: RAM::new(),
+            program,
+            functions,
+            labels,
+            statics,
+            layout: MemoryLayout::new(),
+            natives: HashMap::new(),
+            init: Some(DEFAULT_INIT.to_owned()),
+            step_limit: DEFAULT_STEP_LIMIT,
+            frames: Vec::new(),
+            pc: 0,
+            started: false,
+        }
+    }
+
+    pub fn init(&mut self, init: Option<String>) {
+        self.init = init;
+    }
+
+    pub fn layout(&mut self, layout: MemoryLayout) {
+        self.layout = layout;
+    }
+
+    // Calls to the given function run the native implementation instead, whether or not the
+    // function is also defined in VM code.
+    pub fn native<F>(&mut self, name: &'a str, func: F)
+    where
+        F: FnMut(&mut RAM, &[u16]) -> Result<u16> + 'a,
+    {
+        self.natives.insert(name, Box::new(func));
+    }
+
+    pub fn step_limit(&mut self, limit: usize) {
+        self.step_limit = limit;
+    }
+
+    // The functions currently being called, outermost first.
+    pub fn call_stack(&self) -> Vec<&'a str> {
+        self.frames.iter().map(|(func, _)| *func).collect()
+    }
+
+    // Runs the program from its entry point for at most the given number of commands, or until
+    // the init function returns. Returns whether the program is still running.
+    pub fn run(&mut self, steps: usize) -> Result<bool> {
+        if !self.started {
+            self.start()?;
+        }
+
+        for _ in 0..steps {
+            if !self.step().map_err(|err| self.trace(err))? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Calls a single function with the given arguments and runs it until it returns, without
+    // running the program's entry point.
+    pub fn call(&mut self, func: &str, args: &[u16]) -> Result<u16> {
+        if self.ram.get(SP) == 0 {
+            self.ram.set(SP, self.layout.stack_base);
+        }
+
+        let sp = self.ram.get(SP);
+        for arg in args {
+            self.push(*arg)?;
+        }
+
+        let (depth, pc) = (self.frames.len(), self.pc);
+        let result = self.run_call(func, args.len() as u16, depth, pc);
+        // A call which fails is abandoned, so that the interpreter can be called again.
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.ram.set(SP, sp);
+            self.pc = pc;
+        }
+        result
+    }
+
+    fn run_call(&mut self, func: &str, args: u16, depth: usize, pc: usize) -> Result<u16> {
+        // Returning from the outermost call leaves the program counter where it was.
+        self.call_function(func, args, pc)
+            .map_err(|err| self.trace(err))?;
+
+        let mut steps = 0;
+        while self.frames.len() > depth {
+            if steps == self.step_limit {
+                return Err(self.trace(anyhow!(
+                    "gave up after running {} commands",
+                    self.step_limit
+                )));
+            }
+
+            if !self.step().map_err(|err| self.trace(err))? {
+                return Err(anyhow!("program ended before `{}` returned", func));
+            }
+            steps += 1;
+        }
+
+        self.pop()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.started = true;
+        self.ram.set(SP, self.layout.stack_base);
+
+        match self.init.clone() {
+            Some(init) => self
+                .call_function(&init, 0, self.program.len())
+                .map_err(|err| self.trace(err)),
+            None => Ok(()),
+        }
+    }
+
+    // Runs the next command, returning false if the program has finished.
+    fn step(&mut self) -> Result<bool> {
+        let instr = match self.program.get(self.pc) {
+            Some(instr) => instr,
+            None => return Ok(false),
+        };
+
+        let (module, function, cmd) = (instr.module, instr.function, instr.cmd);
+        self.pc += 1;
+
+        match cmd {
+            Command::Add => self.binary_op(|x, y| x.wrapping_add(y))?,
+            Command::Sub => self.binary_op(|x, y| x.wrapping_sub(y))?,
+            Command::Neg => self.unary_op(|x| x.wrapping_neg())?,
+            Command::And => self.binary_op(|x, y| x & y)?,
+            Command::Or => self.binary_op(|x, y| x | y)?,
+            Command::Not => self.unary_op(|x| !x)?,
+            Command::Eq => self.binary_op(|x, y| bool_value(x == y))?,
+            Command::Gt => self.binary_op(|x, y| bool_value(x as i16 > y as i16))?,
+            Command::Lt => self.binary_op(|x, y| bool_value((x as i16) < y as i16))?,
+            Command::Push(Segment::Constant, n) => self.push(*n)?,
+            Command::Push(segment, idx) => {
+                let addr = self.segment_addr(module, *segment, *idx)?;
+                let value = self.read(addr)?;
+                self.push(value)?;
+            }
+            Command::Pop(Segment::Constant, _) => {
+                return Err(anyhow!("`{}` is not allowed", cmd));
+            }
+            Command::Pop(segment, idx) => {
+                let addr = self.segment_addr(module, *segment, *idx)?;
+                let value = self.pop()?;
+                self.write(addr, value)?;
+            }
+            Command::Label(_) => {}
+            Command::Goto(label) => self.pc = self.label(function, label)?,
+            Command::IfGoto(label) => {
+                if self.pop()? != 0 {
+                    self.pc = self.label(function, label)?;
+                }
+            }
+            Command::Function(_, locals) => {
+                for _ in 0..*locals {
+                    self.push(0)?;
+                }
+            }
+            Command::Call(func, args) => self.call_function(func, *args, self.pc)?,
+            Command::Return => return self.return_function(),
+        }
+
+        Ok(true)
+    }
+
+    fn call_function(&mut self, func: &str, args: u16, return_pc: usize) -> Result<()> {
+        let sp = self.ram.get(SP);
+        let arg = sp
+            .checked_sub(args)
+            .ok_or_else(|| anyhow!("not enough arguments on the stack to call `{}`", func))?;
+
+        if let Some((&name, _)) = self.natives.get_key_value(func) {
+            let values = (arg..sp).map(|addr| self.ram.get(addr)).collect::<Vec<_>>();
+
+            // Natives appear in the call stack while they run, so that any error they return
+            // shows where they were called from.
+            self.frames.push((name, return_pc));
+            let native = self.natives.get_mut(name).unwrap();
+            let result = native(&mut self.ram, &values)?;
+            self.frames.pop();
+
+            self.ram.set(SP, arg);
+            return self.push(result);
+        }
+
+        let (&name, &pc) = self
+            .functions
+            .get_key_value(func)
+            .ok_or_else(|| anyhow!("call to undefined function `{}`", func))?;
+
+        self.push(return_pc as u16)?;
+        for register in &[LCL, ARG, THIS, THAT] {
+            self.push(self.ram.get(*register))?;
+        }
+
+        self.ram.set(ARG, arg);
+        self.ram.set(LCL, self.ram.get(SP));
+        self.frames.push((name, return_pc));
+        self.pc = pc;
+
+        Ok(())
+    }
+
+    fn return_function(&mut self) -> Result<bool> {
+        let (_, return_pc) = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return Ok(false),
+        };
+
+        let frame = self.ram.get(LCL);
+        let value = self.pop()?;
+        let arg = self.ram.get(ARG);
+
+        self.write(arg, value)?;
+        self.ram.set(SP, arg + 1);
+
+        for (offset, register) in [THAT, THIS, ARG, LCL].iter().enumerate() {
+            let saved = self.read(frame.wrapping_sub(offset as u16 + 1))?;
+            self.ram.set(*register, saved);
+        }
+
+        self.pc = return_pc;
+        Ok(true)
+    }
+
+    fn segment_addr(&self, module: usize, segment: Segment, idx: u16) -> Result<u16> {
+        let base = match segment {
+            Segment::Argument => self.ram.get(ARG),
+            Segment::Local => self.ram.get(LCL),
+            Segment::This => self.ram.get(THIS),
+            Segment::That => self.ram.get(THAT),
+            Segment::Pointer if idx <= 1 => return Ok(THIS + idx),
+            Segment::Temp if idx <= 7 => return Ok(TEMP + idx),
+            Segment::Static => {
+                let statics = &self.layout.statics;
+                let offset = self.statics[&(module, idx)];
+
+                return if (offset as usize) < statics.len() {
+                    Ok(statics.start + offset)
+                } else {
+                    Err(anyhow!(
+                        "no room for `static {}`, as only {} static variables fit in RAM[{}..{}]",
+                        idx,
+                        statics.len(),
+                        statics.start,
+                        statics.end
+                    ))
+                };
+            }
+            _ => return Err(anyhow!("invalid segment index `{} {}`", segment, idx)),
+        };
+
+        Ok(base.wrapping_add(idx))
+    }
+
+    fn label(&self, function: Option<&'a str>, label: &str) -> Result<usize> {
+        self.labels
+            .get(&(function, label))
+            .copied()
+            .ok_or_else(|| anyhow!("jump to undefined label `{}`", label))
+    }
+
+    fn unary_op<F>(&mut self, op: F) -> Result<()>
+    where
+        F: Fn(u16) -> u16,
+    {
+        let x = self.pop()?;
+        self.push(op(x))
+    }
+
+    fn binary_op<F>(&mut self, op: F) -> Result<()>
+    where
+        F: Fn(u16, u16) -> u16,
+    {
+        let y = self.pop()?;
+        let x = self.pop()?;
+        self.push(op(x, y))
+    }
+
+    fn push(&mut self, value: u16) -> Result<()> {
+        let sp = self.ram.get(SP);
+        self.write(sp, value)?;
+        self.ram.set(SP, sp + 1);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<u16> {
+        let sp = self.ram.get(SP);
+        if sp <= self.layout.stack_base {
+            return Err(anyhow!("stack underflow"));
+        }
+
+        self.ram.set(SP, sp - 1);
+        self.read(sp - 1)
+    }
+
+    fn read(&self, addr: u16) -> Result<u16> {
+        if (addr as usize) < RAM_SIZE {
+            Ok(self.ram.get(addr))
+        } else {
+            Err(anyhow!("read from RAM[{}], which is out of range", addr))
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u16) -> Result<()> {
+        if (addr as usize) < RAM_SIZE {
+            self.ram.set(addr, value);
+            Ok(())
+        } else {
+            Err(anyhow!("write to RAM[{}], which is out of range", addr))
+        }
+    }
+
+    // Prefixes an error with the functions being called when it happened.
+    // Adds the call stack to an error. A native which fails is still on the call stack so that
+    // it shows up here, after which its frame is popped, as it's no longer running.
+    fn trace(&mut self, err: Error) -> Error {
+        let err = if self.frames.is_empty() {
+            err
+        } else {
+            anyhow!("in {}: {}", self.call_stack().join(" > "), err)
+        };
+
+        if let Some((func, _)) = self.frames.last() {
+            if self.natives.contains_key(func) {
+                self.frames.pop();
+            }
+        }
+
+        err
+    }
+}
+
+fn bool_value(value: bool) -> u16 {
+    if value {
+        0xffff
+    } else {
+        0
+    }
+}
@@ -1,19 +1,141 @@
+mod callgraph;
 mod command;
+mod interpreter;
+mod optimizer;
 mod parser;
 mod translator;
+mod validator;
 
+pub use callgraph::*;
 pub use command::*;
+pub use interpreter::*;
+pub use optimizer::*;
 pub use parser::*;
 pub use translator::*;
+pub use validator::*;
+
+use anyhow::{anyhow, Error};
+use std::fmt;
+
+// The function called to start a program, unless told otherwise.
+pub const DEFAULT_INIT: &str = "Sys.init";
+
+// The Jack compiler collects static variable initialisers into a function with this name. It's
+// a valid VM function name, so the output runs in the course's own tools, but as `static` is a
+// keyword and `.` can't appear in a Jack identifier, it can never clash with a subroutine.
+pub const STATIC_INIT: &str = "static.init";
+
+// The function which calls every static initialiser before doing anything else. By then,
+// `Sys.init` has initialised the OS, so the initialisers are free to allocate memory.
+pub const STATIC_INIT_CALLER: &str = "Main.main";
+
+pub fn static_init_name(class: &str) -> String {
+    format!("{}.{}", class, STATIC_INIT)
+}
+
+// Inserts a call to each module's static initialiser at the start of `Main.main`, in alphabetical
+// order of class name. Initialisers which `Main.main` already calls, such as in a `.vm` file
+// written out after linking, aren't called again.
+pub fn link_static_inits(modules: &mut [Module]) -> Result<(), Error> {
+    let mut inits = modules
+        .iter()
+        .filter_map(Module::static_init)
+        .collect::<Vec<_>>();
+    inits.sort();
+
+    if inits.is_empty() {
+        return Ok(());
+    }
+
+    let (module, start) = modules
+        .iter_mut()
+        .find_map(|module| {
+            let start = module.cmds.iter().position(
+                |cmd| matches!(cmd, Command::Function(func, _) if func == STATIC_INIT_CALLER),
+            )?;
+            Some((module, start + 1))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "static initialisers are called from `{}`, which isn't defined",
+                STATIC_INIT_CALLER
+            )
+        })?;
+
+    let end = module.cmds[start..]
+        .iter()
+        .position(|cmd| matches!(cmd, Command::Function(_, _)))
+        .map_or(module.cmds.len(), |len| start + len);
+    let called = module.cmds[start..end]
+        .iter()
+        .filter_map(|cmd| match cmd {
+            Command::Call(func, _) => Some(func.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let calls = inits
+        .into_iter()
+        .filter(|init| !called.contains(init))
+        .flat_map(|init| vec![Command::Call(init, 0), Command::Pop(Segment::Temp, 0)])
+        .collect::<Vec<_>>();
+
+    // Comments stay with the commands they were written before.
+    for (idx, _) in &mut module.comments {
+        if *idx >= start {
+            *idx += calls.len();
+        }
+    }
+    module.cmds.splice(start..start, calls);
+
+    Ok(())
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Module {
     pub name: String,
     pub cmds: Vec<Command>,
+    // Comments to be written out before the command at the given index, in order of index.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub comments: Vec<(usize, String)>,
 }
 
 impl Module {
     pub fn new(name: String, cmds: Vec<Command>) -> Self {
-        Module { name, cmds }
+        Module {
+            name,
+            cmds,
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn static_init(&self) -> Option<String> {
+        let name = static_init_name(&self.name);
+
+        self.cmds
+            .iter()
+            .any(|cmd| matches!(cmd, Command::Function(func, _) if *func == name))
+            .then_some(name)
+    }
+}
+
+// Writes out the module in the textual format of a `.vm` file.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut comments = self.comments.iter().peekable();
+
+        for (i, cmd) in self.cmds.iter().enumerate() {
+            while let Some((_, comment)) = comments.next_if(|(idx, _)| *idx <= i) {
+                writeln!(f, "// {}", comment)?;
+            }
+            writeln!(f, "{}", cmd)?;
+        }
+
+        for (_, comment) in comments {
+            writeln!(f, "// {}", comment)?;
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,273 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+// Passes give up after this many rounds even if they're still finding things to improve, which
+// can only happen if two passes keep undoing each other's work.
+const MAX_ROUNDS: usize = 16;
+
+// A peephole optimisation over the commands in a function. Each pass can be enabled on its own,
+// and is also enabled by any optimisation level at or above `Pass::level`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Pass {
+    // `push X; pop X` has no effect.
+    PushPop,
+    // `if-goto` with a constant condition is either always or never taken, as in
+    // `push constant 0; not; if-goto L` which becomes `goto L`.
+    ConstantCondition,
+    // `lt; not; if-goto A; goto B; label A` becomes `lt; if-goto B; label A`. As `not` is
+    // bitwise, this only holds when the condition is a boolean, so the pass only applies straight
+    // after a comparison.
+    InvertedJump,
+    // `goto L` which lands on the very next command can be removed.
+    JumpToNext,
+    // A jump to a label which is immediately followed by `goto L` can jump straight to `L`.
+    ThreadJumps,
+    // Labels which aren't the target of any jump can be removed.
+    PruneLabels,
+}
+
+impl Pass {
+    pub const ALL: &'static [Pass] = &[
+        Pass::PushPop,
+        Pass::ConstantCondition,
+        Pass::InvertedJump,
+        Pass::JumpToNext,
+        Pass::ThreadJumps,
+        Pass::PruneLabels,
+    ];
+
+    pub fn level(self) -> u8 {
+        match self {
+            Pass::PushPop | Pass::ConstantCondition | Pass::JumpToNext => 1,
+            Pass::InvertedJump | Pass::ThreadJumps | Pass::PruneLabels => 2,
+        }
+    }
+
+    // Tries to rewrite the commands at the start of `cmds`, returning the number of commands to
+    // be replaced along with their replacement.
+    fn apply(self, scope: &Scope, cmds: &[Command]) -> Option<(usize, Vec<Command>)> {
+        match self {
+            Pass::PushPop => match cmds {
+                [Command::Push(seg, idx), Command::Pop(pop_seg, pop_idx), ..]
+                    if seg == pop_seg && idx == pop_idx && *seg != Segment::Constant =>
+                {
+                    Some((2, vec![]))
+                }
+                _ => None,
+            },
+            Pass::ConstantCondition => {
+                let mut value = match cmds.first()? {
+                    Command::Push(Segment::Constant, n) => *n,
+                    _ => return None,
+                };
+
+                let mut len = 1;
+                loop {
+                    match cmds.get(len)? {
+                        Command::Neg => value = value.wrapping_neg(),
+                        Command::Not => value = !value,
+                        Command::IfGoto(label) if value != 0 => {
+                            return Some((len + 1, vec![Command::Goto(label.clone())]));
+                        }
+                        Command::IfGoto(_) => return Some((len + 1, vec![])),
+                        _ => return None,
+                    }
+                    len += 1;
+                }
+            }
+            Pass::InvertedJump => match cmds {
+                [cmp @ (Command::Eq | Command::Lt | Command::Gt), Command::Not, Command::IfGoto(skip), Command::Goto(target), Command::Label(label), ..]
+                    if skip == label =>
+                {
+                    Some((
+                        5,
+                        vec![
+                            cmp.clone(),
+                            Command::IfGoto(target.clone()),
+                            Command::Label(label.clone()),
+                        ],
+                    ))
+                }
+                _ => None,
+            },
+            Pass::JumpToNext => match cmds.split_first()? {
+                (Command::Goto(target), rest) => rest
+                    .iter()
+                    .map_while(|cmd| match cmd {
+                        Command::Label(label) => Some(label),
+                        _ => None,
+                    })
+                    .any(|label| label == target)
+                    .then(|| (1, vec![])),
+                _ => None,
+            },
+            Pass::ThreadJumps => {
+                let (target, cmd): (_, fn(String) -> Command) = match cmds.first()? {
+                    Command::Goto(target) => (target, Command::Goto),
+                    Command::IfGoto(target) => (target, Command::IfGoto),
+                    _ => return None,
+                };
+
+                let threaded = scope.thread(target);
+                (threaded != target).then(|| (1, vec![cmd(threaded.to_owned())]))
+            }
+            Pass::PruneLabels => match cmds.first()? {
+                Command::Label(label) if !scope.targets.contains(label.as_str()) => {
+                    Some((1, vec![]))
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+pub struct Optimizer {
+    passes: Vec<Pass>,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Optimizer { passes: Vec::new() }
+    }
+
+    // Enables every pass up to the given optimisation level, where level 0 disables them all.
+    pub fn level(&mut self, level: u8) {
+        self.passes = Pass::ALL
+            .iter()
+            .copied()
+            .filter(|pass| pass.level() <= level)
+            .collect();
+    }
+
+    pub fn passes(&mut self, passes: &[Pass]) {
+        self.passes = passes.to_vec();
+    }
+
+    pub fn optimize(&self, module: &mut Module) {
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+
+            for pass in &self.passes {
+                changed |= run_pass(*pass, module);
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer::new()
+    }
+}
+
+// Applies a pass throughout the module, returning whether anything changed. Any comments attached
+// to commands which get rewritten are moved to the start of their replacement.
+fn run_pass(pass: Pass, module: &mut Module) -> bool {
+    let cmds = &module.cmds;
+    let mut out = Vec::with_capacity(cmds.len());
+    let mut comments = module.comments.drain(..).peekable();
+    let mut moved = Vec::new();
+    let mut changed = false;
+
+    for (start, end) in function_ranges(cmds) {
+        let scope = Scope::new(&cmds[start..end]);
+        let mut i = start;
+
+        while i < end {
+            let (len, replacement) = match pass.apply(&scope, &cmds[i..end]) {
+                Some(rewrite) => {
+                    changed = true;
+                    rewrite
+                }
+                None => (1, vec![cmds[i].clone()]),
+            };
+
+            while let Some((_, comment)) = comments.next_if(|(idx, _)| *idx < i + len) {
+                moved.push((out.len(), comment));
+            }
+
+            out.extend(replacement);
+            i += len;
+        }
+    }
+
+    moved.extend(comments.map(|(_, comment)| (out.len(), comment)));
+
+    module.cmds = out;
+    module.comments = moved;
+    changed
+}
+
+// Splits a module into the ranges of commands making up each function, along with any commands
+// before the first function.
+fn function_ranges(cmds: &[Command]) -> Vec<(usize, usize)> {
+    let mut starts = cmds
+        .iter()
+        .enumerate()
+        .filter(|(_, cmd)| matches!(cmd, Command::Function(..)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    let mut ends = starts[1..].to_vec();
+    ends.push(cmds.len());
+
+    starts.into_iter().zip(ends).collect()
+}
+
+// The labels used within a single function.
+struct Scope<'a> {
+    targets: HashSet<&'a str>,
+    // Labels which are immediately followed by a `goto`, mapped onto that goto's target.
+    forwards: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(cmds: &'a [Command]) -> Self {
+        let mut targets = HashSet::new();
+        let mut forwards = HashMap::new();
+
+        for (i, cmd) in cmds.iter().enumerate() {
+            match cmd {
+                Command::Goto(label) | Command::IfGoto(label) => {
+                    targets.insert(label.as_str());
+                }
+                Command::Label(label) => {
+                    let next = cmds[i + 1..]
+                        .iter()
+                        .find(|cmd| !matches!(cmd, Command::Label(_)));
+
+                    if let Some(Command::Goto(target)) = next {
+                        forwards.insert(label.as_str(), target.as_str());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Scope { targets, forwards }
+    }
+
+    // Follows a chain of gotos from the given label to its final destination, stopping if the
+    // chain turns out to be an infinite loop.
+    fn thread<'b>(&'b self, label: &'b str) -> &'b str {
+        let mut seen = HashSet::new();
+        let mut label = label;
+
+        while let Some(next) = self.forwards.get(label) {
+            if !seen.insert(label) {
+                break;
+            }
+            label = next;
+        }
+
+        label
+    }
+}
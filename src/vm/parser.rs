@@ -2,96 +2,122 @@ use super::*;
 use anyhow::{anyhow, Error};
 use std::str::FromStr;
 
+// Parses the text of a `.vm` file. Each line holds at most one command, which may be surrounded
+// by any amount of whitespace and followed by a `//` comment.
 pub fn parse(s: &str) -> Result<Vec<Command>, Error> {
-    s.lines().map(|line| line.parse()).collect()
+    let mut cmds = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let cmd = line
+            .parse()
+            .map_err(|err| anyhow!("line {}: {}", i + 1, err))?;
+        cmds.push(cmd);
+    }
+
+    Ok(cmds)
 }
 
 impl FromStr for Command {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "add" => Ok(Command::Add),
-            "sub" => Ok(Command::Sub),
-            "neg" => Ok(Command::Neg),
-            "eq" => Ok(Command::Eq),
-            "gt" => Ok(Command::Gt),
-            "lt" => Ok(Command::Lt),
-            "and" => Ok(Command::And),
-            "or" => Ok(Command::Or),
-            "not" => Ok(Command::Not),
-            "return" => Ok(Command::Return),
-            _ if s.starts_with("pop") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [segment, index] => {
-                        let segment = segment.parse()?;
-                        let index = index.parse()?;
-                        Ok(Command::Pop(segment, index))
-                    }
-                    got => Err(anyhow!(
-                        "expected a segment followed by a number, found `{}`",
-                        got.join(" ")
-                    )),
-                }
-            }
-            _ if s.starts_with("push") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [segment, index] => {
-                        let segment = segment.parse()?;
-                        let index = index.parse()?;
-                        Ok(Command::Push(segment, index))
-                    }
-                    got => Err(anyhow!(
-                        "expected a segment followed by a number, found `{}`",
-                        got.join(" ")
-                    )),
-                }
-            }
-            _ if s.starts_with("label") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [label] => Ok(Command::Label(label.to_string())),
-                    got => Err(anyhow!("expected a label, found `{}`", got.join(" "))),
-                }
-            }
-            _ if s.starts_with("goto") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [label] => Ok(Command::Goto(label.to_string())),
-                    got => Err(anyhow!("expected a label, found `{}`", got.join(" "))),
-                }
+        let words = s.split_whitespace().collect::<Vec<_>>();
+
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Err(anyhow!("expected a VM command")),
+        };
+
+        match (name, args) {
+            ("add", []) => Ok(Command::Add),
+            ("sub", []) => Ok(Command::Sub),
+            ("neg", []) => Ok(Command::Neg),
+            ("eq", []) => Ok(Command::Eq),
+            ("gt", []) => Ok(Command::Gt),
+            ("lt", []) => Ok(Command::Lt),
+            ("and", []) => Ok(Command::And),
+            ("or", []) => Ok(Command::Or),
+            ("not", []) => Ok(Command::Not),
+            ("return", []) => Ok(Command::Return),
+            ("pop", [segment, index]) => {
+                let (segment, index) = parse_segment_index(segment, index)?;
+                Ok(Command::Pop(segment, index))
             }
-            _ if s.starts_with("if-goto") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [label] => Ok(Command::IfGoto(label.to_string())),
-                    got => Err(anyhow!("expected a label, found `{}`", got.join(" "))),
-                }
+            ("push", [segment, index]) => {
+                let (segment, index) = parse_segment_index(segment, index)?;
+                Ok(Command::Push(segment, index))
             }
-            _ if s.starts_with("function") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [function, locals] => {
-                        let locals = locals.parse()?;
-                        Ok(Command::Function(function.to_string(), locals))
-                    }
-                    got => Err(anyhow!(
-                        "expected a function name followed by a number, found `{}`",
-                        got.join(" ")
-                    )),
-                }
+            ("label", [label]) => Ok(Command::Label(label.to_string())),
+            ("goto", [label]) => Ok(Command::Goto(label.to_string())),
+            ("if-goto", [label]) => Ok(Command::IfGoto(label.to_string())),
+            ("function", [function, locals]) => Ok(Command::Function(
+                function.to_string(),
+                parse_number(locals)?,
+            )),
+            ("call", [function, args]) => {
+                Ok(Command::Call(function.to_string(), parse_number(args)?))
             }
-            _ if s.starts_with("call") => {
-                match s.split_whitespace().skip(1).collect::<Vec<_>>().as_slice() {
-                    [function, args] => {
-                        let args = args.parse()?;
-                        Ok(Command::Call(function.to_string(), args))
-                    }
-                    got => Err(anyhow!(
-                        "expected a function name followed by a number, found `{}`",
-                        got.join(" ")
-                    )),
-                }
+            ("add", _)
+            | ("sub", _)
+            | ("neg", _)
+            | ("eq", _)
+            | ("gt", _)
+            | ("lt", _)
+            | ("and", _)
+            | ("or", _)
+            | ("not", _)
+            | ("return", _) => Err(anyhow!(
+                "unexpected arguments after `{}`, found `{}`",
+                name,
+                args.join(" ")
+            )),
+            ("pop", _) | ("push", _) => Err(anyhow!(
+                "expected a segment followed by a number, found `{}`",
+                args.join(" ")
+            )),
+            ("label", _) | ("goto", _) | ("if-goto", _) => {
+                Err(anyhow!("expected a label, found `{}`", args.join(" ")))
             }
-            _ => Err(anyhow!("`{}` is not a valid VM command", s)),
+            ("function", _) | ("call", _) => Err(anyhow!(
+                "expected a function name followed by a number, found `{}`",
+                args.join(" ")
+            )),
+            _ => Err(anyhow!("`{}` is not a valid VM command", s.trim())),
+        }
+    }
+}
+
+fn parse_segment_index(segment: &str, index: &str) -> Result<(Segment, u16), Error> {
+    let segment = segment.parse::<Segment>()?;
+    let index = parse_number(index)?;
+
+    if let Some(max) = segment.max_index() {
+        if index > max {
+            return Err(anyhow!(
+                "`{} {}` is out of range, as the {} segment only has {} entries",
+                segment,
+                index,
+                segment,
+                max + 1
+            ));
         }
     }
+
+    Ok((segment, index))
+}
+
+fn parse_number(s: &str) -> Result<u16, Error> {
+    s.parse()
+        .map_err(|_| anyhow!("`{}` is not a valid number", s))
 }
 
 impl FromStr for Segment {
@@ -1,16 +1,52 @@
 use super::*;
 use crate::asm;
-use crate::asm::Instruction;
+use crate::asm::{Instruction, Jump, MemoryLayout, SCRATCH_REGISTERS};
 use crate::labels::Labeller;
 use anyhow::{anyhow, Error};
+use std::collections::{HashMap, HashSet};
 
-const DEFAULT_INIT: &str = "Sys.init";
+// The words of stack set aside for recursion below the static frames, unless told otherwise.
+pub const DEFAULT_STACK_MARGIN: usize = 256;
 
 type Result<T> = std::result::Result<T, Error>;
 
+// How the generated code represents the VM stack.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Strategy {
+    // Every value on the stack lives in RAM.
+    Memory,
+    // The value on top of the stack is kept in D for as long as possible, and only written to
+    // RAM when the next command needs the whole stack there.
+    CachedTop,
+}
+
+// Whether calls and returns should keep the program small or make it fast.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OptimizeFor {
+    // Every call and return jumps to a routine shared by the whole program.
+    Size,
+    // Each call and return has its own copy of the routine, specialised where possible.
+    Speed,
+}
+
 pub struct Translator<'a> {
     modules: &'a [Module],
     init: Option<String>,
+    layout: MemoryLayout,
+    strategy: Strategy,
+    // Whether the value on top of the stack is currently held in D rather than in RAM.
+    cached: bool,
+    tail_calls: bool,
+    static_frames: bool,
+    stack_margin: usize,
+    // The static frame of each function which has one, and the address of the first slot.
+    frames: HashMap<&'a str, StaticFrame>,
+    frames_base: u16,
+    optimize_for: OptimizeFor,
+    function_goals: HashMap<String, OptimizeFor>,
+    remove_unreachable: bool,
+    roots: Vec<String>,
+    removed: Vec<String>,
     labeller: Labeller,
     function: Option<&'a str>,
 }
@@ -20,6 +56,19 @@ impl<'a> Translator<'a> {
         Translator {
             modules,
             init: Some(DEFAULT_INIT.to_owned()),
+            layout: MemoryLayout::new(),
+            strategy: Strategy::Memory,
+            cached: false,
+            tail_calls: false,
+            static_frames: false,
+            stack_margin: DEFAULT_STACK_MARGIN,
+            frames: HashMap::new(),
+            frames_base: 0,
+            optimize_for: OptimizeFor::Size,
+            function_goals: HashMap::new(),
+            remove_unreachable: false,
+            roots: Vec::new(),
+            removed: Vec::new(),
             labeller: Labeller::new(),
             function: None,
         }
@@ -29,37 +78,220 @@ impl<'a> Translator<'a> {
         self.init = init;
     }
 
+    pub fn layout(&mut self, layout: MemoryLayout) -> Result<()> {
+        layout.validate()?;
+        self.layout = layout;
+        Ok(())
+    }
+
+    pub fn strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+    }
+
+    // Translates a call which is immediately followed by a return into a jump which reuses the
+    // current function's frame, so that tail-recursive functions run in constant stack space.
+    pub fn tail_calls(&mut self, enabled: bool) {
+        self.tail_calls = enabled;
+    }
+
+    // Keeps the arguments and locals of each function which can't recurse at fixed addresses at
+    // the top of the stack area, so that they can be accessed without going through ARG or LCL.
+    pub fn static_frames(&mut self, enabled: bool) {
+        self.static_frames = enabled;
+    }
+
+    // The words of stack set aside below the static frames for recursive calls, and for calls
+    // to functions defined outside the VM code, whose stack usage can't be worked out.
+    pub fn stack_margin(&mut self, words: usize) {
+        self.stack_margin = words;
+    }
+
+    pub fn optimize_for(&mut self, goal: OptimizeFor) {
+        self.optimize_for = goal;
+    }
+
+    // Overrides the goal for calls to the given function, and for its returns.
+    pub fn optimize_function_for(&mut self, func: String, goal: OptimizeFor) {
+        self.function_goals.insert(func, goal);
+    }
+
+    // Leaves out any function which can't be reached from the init function, or from commands
+    // outside of any function.
+    pub fn remove_unreachable(&mut self, enabled: bool) {
+        self.remove_unreachable = enabled;
+    }
+
+    // Keeps the given function, along with everything it calls, when leaving out unreachable
+    // functions. This is for functions which are called from outside the VM code.
+    pub fn root(&mut self, func: String) {
+        self.roots.push(func);
+    }
+
+    // The functions left out of the last translation, in the order in which they're defined.
+    pub fn removed(&self) -> &[String] {
+        &self.removed
+    }
+
     pub fn translate(&mut self) -> Result<Vec<Instruction>> {
+        let scratch = self.scratch_registers();
+        self.cached = false;
+        self.allocate_frames()?;
+
         let mut prog = vec![
-            asm!(@256),
+            asm!(@self.layout.stack_base),
             asm!(D = A),
             asm!(@"SP"),
             asm!(M = D),
-            asm!(@"START"),
+            asm!(@internal("START")),
             asm!(0;JMP),
         ];
 
-        prog.extend(eq());
-        prog.extend(lt());
-        prog.extend(gt());
-        prog.extend(ret());
-        prog.extend(call());
-        prog.push(asm!(("START")));
+        prog.extend(eq(&scratch));
+        prog.extend(lt(&scratch));
+        prog.extend(gt(&scratch));
+        // The shared call and return routines are only needed if anything is optimised for size.
+        let mut goals = self.function_goals.values().chain(Some(&self.optimize_for));
+        if goals.any(|goal| *goal == OptimizeFor::Size) {
+            prog.extend(ret(&scratch));
+            prog.extend(call(&scratch));
+        }
+        if self.tail_calls {
+            prog.extend(tail_call(&scratch));
+        }
+        prog.push(asm!((internal("START"))));
 
         if let Some(func) = &self.init {
             let func = func.clone();
             prog.extend(self.translate_call(func, 0));
         }
 
+        let reachable = self.reachable();
+        self.removed.clear();
+
         for module in self.modules {
-            for cmd in &module.cmds {
-                prog.extend(self.translate_cmd(module, cmd)?);
+            let mut skip = false;
+            let mut cmds = module.cmds.iter().peekable();
+
+            while let Some(cmd) = cmds.next() {
+                if let (Command::Function(func, _), Some(reachable)) = (cmd, &reachable) {
+                    skip = !reachable.contains(func.as_str());
+                    if skip {
+                        self.removed.push(func.clone());
+                    }
+                }
+
+                if skip {
+                    continue;
+                }
+
+                if let Command::Call(func, args) = cmd {
+                    let returns = cmds.peek() == Some(&&Command::Return);
+                    if self.tail_calls && returns && self.function.is_some() {
+                        cmds.next();
+                        prog.extend(self.spill());
+                        prog.extend(self.translate_tail_call(func, *args));
+                        continue;
+                    }
+                }
+
+                prog.extend(match self.strategy {
+                    Strategy::Memory => self.translate_cmd(module, cmd)?,
+                    Strategy::CachedTop => self.translate_cached_cmd(module, cmd)?,
+                });
             }
         }
 
         Ok(prog)
     }
 
+    fn scratch_registers(&self) -> Vec<Instruction> {
+        (0..SCRATCH_REGISTERS)
+            .map(|n| self.scratch_register(n))
+            .collect()
+    }
+
+    // Refers to the scratch registers by their `Rn` names where possible, for readability.
+    fn scratch_register(&self, n: u16) -> Instruction {
+        match self.layout.scratch + n {
+            addr if addr < 16 => asm!(@format!("R{}", addr)),
+            addr => asm!(@addr),
+        }
+    }
+
+    fn allocate_frames(&mut self) -> Result<()> {
+        let mut graph = CallGraph::new(self.modules);
+        self.frames = if self.static_frames {
+            graph.static_frames()
+        } else {
+            HashMap::new()
+        };
+
+        let size = self
+            .frames
+            .values()
+            .map(|frame| frame.base + frame.size())
+            .max()
+            .unwrap_or(0);
+        // The frames have to leave room below them for the deepest the stack can get.
+        graph.margin(self.stack_margin);
+        let depth = match &self.init {
+            _ if size == 0 => StackDepth::Bounded(0),
+            Some(init) => graph.stack_depth(init),
+            None => graph.top_level_stack_depth(),
+        };
+        let stack = match depth {
+            StackDepth::Bounded(words) => words,
+            // The margin already bounds every depth, so this is only for completeness.
+            _ => self.stack_margin,
+        };
+
+        let room = (self.layout.heap_base - self.layout.stack_base) as usize;
+        if size + stack > room {
+            return Err(anyhow!(
+                "no room for the static frames, as they need {} words and the stack can need \
+                 {} more, but only {} fit in RAM[{}..{}]",
+                size,
+                stack,
+                room,
+                self.layout.stack_base,
+                self.layout.heap_base
+            ));
+        }
+
+        self.frames_base = self.layout.heap_base - size as u16;
+        Ok(())
+    }
+
+    // The fixed address of an argument or local, if the current function has a static frame.
+    fn frame_slot(&self, segment: Segment, idx: u16) -> Option<Instruction> {
+        let frame = self.frames.get(self.function?)?;
+        let slot = match segment {
+            Segment::Argument => frame.base + idx as usize,
+            Segment::Local => frame.base + frame.args + idx as usize,
+            _ => return None,
+        };
+
+        Some(asm!(@self.frames_base + slot as u16))
+    }
+
+    fn reachable(&self) -> Option<HashSet<&'a str>> {
+        if !self.remove_unreachable {
+            return None;
+        }
+
+        let graph = CallGraph::new(self.modules);
+        let defined = |name: &str| graph.functions().iter().copied().find(|func| *func == name);
+        let roots = graph
+            .top_level()
+            .iter()
+            .copied()
+            .chain(self.init.as_deref().and_then(defined))
+            .chain(self.roots.iter().filter_map(|root| defined(root)))
+            .collect::<Vec<_>>();
+
+        Some(graph.reachable(roots))
+    }
+
     fn translate_cmd(&mut self, module: &Module, cmd: &'a Command) -> Result<Vec<Instruction>> {
         let instr = match cmd {
             Command::Add => self.translate_binary_op(asm!(M = D + M)),
@@ -69,79 +301,194 @@ impl<'a> Translator<'a> {
             Command::Or => self.translate_binary_op(asm!(M = D | M)),
             Command::Not => self.translate_unary_op(asm!(M = !M)),
             Command::Eq | Command::Gt | Command::Lt => self.translate_comparison(cmd)?,
-            Command::Pop(Segment::Pointer, 0) => self.translate_pop(&[asm!(@"THIS")]),
-            Command::Pop(Segment::Pointer, 1) => self.translate_pop(&[asm!(@"THAT")]),
-            Command::Pop(Segment::Temp, idx) => self.translate_pop(&[temp_register(*idx)?]),
-            Command::Pop(Segment::Static, idx) => self.translate_pop(&[static_addr(module, *idx)]),
-            Command::Pop(segment, idx) => {
-                let register = segment_register(*segment)?;
+            Command::Pop(segment, idx) => match self.address(module, *segment, *idx)? {
+                Some(addr) => self.translate_pop(&addr),
+                None => {
+                    let mut instr = vec![
+                        segment_register(*segment)?,
+                        asm!(D = M),
+                        asm!(@*idx),
+                        asm!(D = D + A),
+                        self.scratch_register(0),
+                        asm!(M = D),
+                    ];
+                    instr.extend(self.translate_pop(&[self.scratch_register(0), asm!(A = M)]));
+                    instr
+                }
+            },
+            Command::Push(Segment::Constant, n) => self.translate_push_const(*n),
+            Command::Push(segment, idx) => self.translate_push(&self.load(module, *segment, *idx)?),
+            Command::Label(label) => self.translate_label(label),
+            Command::Goto(label) => self.translate_goto(label),
+            Command::IfGoto(label) => self.translate_if_goto(label),
+            Command::Function(func, locals) => self.translate_function(func, *locals),
+            Command::Call(func, args) => self.translate_call(func.to_owned(), *args),
+            Command::Return => self.translate_return(),
+        };
 
-                match idx {
-                    0 => self.translate_pop(&[register, asm!(A = M)]),
-                    1..=6 => {
-                        let mut write = vec![register, asm!(A = M + 1)];
-                        for _ in 1..*idx {
-                            write.push(asm!(A = A + 1));
-                        }
-                        self.translate_pop(&write)
-                    }
-                    _ => {
-                        let mut instr = vec![
-                            register,
+        Ok(instr)
+    }
+
+    // Translates a command using the `CachedTop` strategy. Every label and function is reached
+    // with the whole stack in RAM, so the cached value is spilled before them, and before any
+    // command which jumps elsewhere or relies on the shared routines.
+    fn translate_cached_cmd(
+        &mut self,
+        module: &Module,
+        cmd: &'a Command,
+    ) -> Result<Vec<Instruction>> {
+        let mut instr = Vec::new();
+
+        match cmd {
+            Command::Add => instr.extend(self.translate_cached_binary_op(asm!(D = D + M))),
+            Command::Sub => instr.extend(self.translate_cached_binary_op(asm!(D = M - D))),
+            Command::And => instr.extend(self.translate_cached_binary_op(asm!(D = D & M))),
+            Command::Or => instr.extend(self.translate_cached_binary_op(asm!(D = D | M))),
+            Command::Neg if self.cached => instr.push(asm!(D = -D)),
+            Command::Not if self.cached => instr.push(asm!(D = !D)),
+            Command::Push(segment, idx) => {
+                instr.extend(self.spill());
+                instr.extend(self.load(module, *segment, *idx)?);
+                self.cached = true;
+            }
+            Command::Pop(segment, idx) if self.cached => {
+                match self.address(module, *segment, *idx)? {
+                    Some(addr) => instr.extend(addr),
+                    None => {
+                        instr.extend(vec![
+                            self.scratch_register(0),
+                            asm!(M = D),
+                            segment_register(*segment)?,
                             asm!(D = M),
                             asm!(@*idx),
                             asm!(D = D + A),
-                            asm!(@"R13"),
+                            self.scratch_register(1),
                             asm!(M = D),
-                        ];
-                        instr.extend(self.translate_pop(&[asm!(@"R13"), asm!(A = M)]));
-                        instr
+                            self.scratch_register(0),
+                            asm!(D = M),
+                            self.scratch_register(1),
+                            asm!(A = M),
+                        ]);
                     }
                 }
+                instr.push(asm!(M = D));
+                self.cached = false;
             }
-            Command::Push(Segment::Constant, n) => self.translate_push_const(*n),
-            Command::Push(Segment::Pointer, 0) => {
-                self.translate_push(&[asm!(@"THIS"), asm!(D = M)])
+            Command::IfGoto(label) => {
+                instr.extend(self.fill());
+                instr.extend(vec![
+                    asm!(@func_label_name(self.function, label)),
+                    asm!(D;JNE),
+                ]);
+                self.cached = false;
             }
-            Command::Push(Segment::Pointer, 1) => {
-                self.translate_push(&[asm!(@"THAT"), asm!(D = M)])
-            }
-            Command::Push(Segment::Temp, idx) => {
-                self.translate_push(&[temp_register(*idx)?, asm!(D = M)])
-            }
-            Command::Push(Segment::Static, idx) => {
-                self.translate_push(&[static_addr(module, *idx), asm!(D = M)])
+            _ => {
+                instr.extend(self.spill());
+                instr.extend(self.translate_cmd(module, cmd)?);
             }
-            Command::Push(segment, idx) => {
-                let register = segment_register(*segment)?;
+        }
+
+        Ok(instr)
+    }
+
+    fn translate_cached_binary_op(&mut self, op: Instruction) -> Vec<Instruction> {
+        let mut instr = self.fill();
+        instr.extend(vec![asm!(@"SP"), asm!(AM = M - 1), op]);
+        instr
+    }
+
+    // Writes the cached value back onto the stack in RAM.
+    fn spill(&mut self) -> Vec<Instruction> {
+        if !self.cached {
+            return vec![];
+        }
+
+        self.cached = false;
+        vec![asm!(@"SP"), asm!(AM = M + 1), asm!(A = A - 1), asm!(M = D)]
+    }
+
+    // Moves the value on top of the stack from RAM into D.
+    fn fill(&mut self) -> Vec<Instruction> {
+        if self.cached {
+            return vec![];
+        }
+
+        self.cached = true;
+        vec![asm!(@"SP"), asm!(AM = M - 1), asm!(D = M)]
+    }
+
+    // Loads the value of a segment entry into D.
+    fn load(&self, module: &Module, segment: Segment, idx: u16) -> Result<Vec<Instruction>> {
+        if let Some(slot) = self.frame_slot(segment, idx) {
+            return Ok(vec![slot, asm!(D = M)]);
+        }
+
+        let instr = match (segment, idx) {
+            (Segment::Constant, 0) => vec![asm!(D = 0)],
+            (Segment::Constant, 1) => vec![asm!(D = 1)],
+            (Segment::Constant, 0xffff) => vec![asm!(D = -1)],
+            (Segment::Constant, n) if n >> 15 == 0 => vec![asm!(@n), asm!(D = A)],
+            (Segment::Constant, n) => vec![asm!(@!n), asm!(D = !A)],
+            (Segment::Local, 3..=u16::MAX)
+            | (Segment::Argument, 3..=u16::MAX)
+            | (Segment::This, 3..=u16::MAX)
+            | (Segment::That, 3..=u16::MAX) => vec![
+                segment_register(segment)?,
+                asm!(D = M),
+                asm!(@idx),
+                asm!(A = D + A),
+                asm!(D = M),
+            ],
+            _ => match self.address(module, segment, idx)? {
+                Some(mut instr) => {
+                    instr.push(asm!(D = M));
+                    instr
+                }
+                None => return Err(anyhow!("invalid segment index `{} {}`", segment, idx)),
+            },
+        };
 
+        Ok(instr)
+    }
+
+    // Points A at a segment entry without changing D, or returns `None` if that would take
+    // too many instructions.
+    fn address(
+        &self,
+        module: &Module,
+        segment: Segment,
+        idx: u16,
+    ) -> Result<Option<Vec<Instruction>>> {
+        if let Some(slot) = self.frame_slot(segment, idx) {
+            return Ok(Some(vec![slot]));
+        }
+
+        let instr = match (segment, idx) {
+            (Segment::Pointer, 0) => vec![asm!(@"THIS")],
+            (Segment::Pointer, 1) => vec![asm!(@"THAT")],
+            (Segment::Temp, idx) => vec![temp_register(idx)?],
+            (Segment::Static, idx) => vec![static_addr(module, idx)],
+            (Segment::Local, _)
+            | (Segment::Argument, _)
+            | (Segment::This, _)
+            | (Segment::That, _) => {
+                let mut instr = vec![segment_register(segment)?];
                 match idx {
-                    0 => self.translate_push(&[register, asm!(A = M), asm!(D = M)]),
-                    1 => self.translate_push(&[register, asm!(A = M + 1), asm!(D = M)]),
-                    2 => self.translate_push(&[
-                        register,
-                        asm!(A = M + 1),
-                        asm!(A = A + 1),
-                        asm!(D = M),
-                    ]),
-                    _ => self.translate_push(&[
-                        register,
-                        asm!(D = M),
-                        asm!(@*idx),
-                        asm!(A = D + A),
-                        asm!(D = M),
-                    ]),
+                    0 => instr.push(asm!(A = M)),
+                    1..=6 => {
+                        instr.push(asm!(A = M + 1));
+                        for _ in 1..idx {
+                            instr.push(asm!(A = A + 1));
+                        }
+                    }
+                    _ => return Ok(None),
                 }
+                instr
             }
-            Command::Label(label) => self.translate_label(label),
-            Command::Goto(label) => self.translate_goto(label),
-            Command::IfGoto(label) => self.translate_if_goto(label),
-            Command::Function(func, locals) => self.translate_function(func, *locals),
-            Command::Call(func, args) => self.translate_call(func.to_owned(), *args),
-            Command::Return => vec![asm!(@"RETURN"), asm!(0;JMP)],
+            _ => return Err(anyhow!("invalid segment index `{} {}`", segment, idx)),
         };
 
-        Ok(instr)
+        Ok(Some(instr))
     }
 
     fn translate_unary_op(&self, op: Instruction) -> Vec<Instruction> {
@@ -160,9 +507,18 @@ impl<'a> Translator<'a> {
 
     fn translate_comparison(&mut self, cmd: &Command) -> Result<Vec<Instruction>> {
         let (label, op) = match cmd {
-            Command::Eq => Ok((self.labeller.generate("RET_ADDRESS_EQ"), asm!(@"EQ"))),
-            Command::Gt => Ok((self.labeller.generate("RET_ADDRESS_GT"), asm!(@"GT"))),
-            Command::Lt => Ok((self.labeller.generate("RET_ADDRESS_LT"), asm!(@"LT"))),
+            Command::Eq => Ok((
+                self.labeller.generate("$RET_ADDRESS_EQ"),
+                asm!(@internal("EQ")),
+            )),
+            Command::Gt => Ok((
+                self.labeller.generate("$RET_ADDRESS_GT"),
+                asm!(@internal("GT")),
+            )),
+            Command::Lt => Ok((
+                self.labeller.generate("$RET_ADDRESS_LT"),
+                asm!(@internal("LT")),
+            )),
             _ => Err(anyhow!("unexpected comparison command `{}`", cmd)),
         }?;
 
@@ -232,19 +588,20 @@ impl<'a> Translator<'a> {
     fn translate_function(&mut self, func: &'a str, locals: u16) -> Vec<Instruction> {
         self.function = Some(func);
 
+        if let Some(frame) = self.frames.get(func) {
+            return self.translate_static_function(func, frame);
+        }
+
         let init = match locals {
             0 => vec![],
-            1 | 2 => std::iter::repeat(vec![
-                asm!(@"SP"),
-                asm!(AM = M + 1),
-                asm!(A = A - 1),
-                asm!(M = 0),
-            ])
-            .take(locals as usize)
+            1 | 2 => std::iter::repeat_n(
+                vec![asm!(@"SP"), asm!(AM = M + 1), asm!(A = A - 1), asm!(M = 0)],
+                locals as usize,
+            )
             .flatten()
             .collect(),
             _ => {
-                let label = format!("LOOP_{}", func);
+                let label = internal(&format!("LOOP_{}", func));
                 vec![
                     asm!(@locals),
                     asm!(D = A),
@@ -265,36 +622,102 @@ impl<'a> Translator<'a> {
         instr
     }
 
+    // Copies the arguments from the stack into the function's static frame, and clears its
+    // locals. Nothing is pushed, so LCL and SP are left pointing at the same place.
+    fn translate_static_function(&self, func: &str, frame: &StaticFrame) -> Vec<Instruction> {
+        let mut instr = vec![asm!((func))];
+
+        for idx in 0..frame.args as u16 {
+            instr.extend(match idx {
+                0 => vec![asm!(@"ARG"), asm!(A = M)],
+                1 => vec![asm!(@"ARG"), asm!(A = M + 1)],
+                _ => vec![asm!(@"ARG"), asm!(D = M), asm!(@idx), asm!(A = D + A)],
+            });
+            instr.extend(vec![
+                asm!(D = M),
+                self.frame_slot(Segment::Argument, idx).unwrap(),
+                asm!(M = D),
+            ]);
+        }
+
+        for idx in 0..frame.locals as u16 {
+            instr.extend(vec![
+                self.frame_slot(Segment::Local, idx).unwrap(),
+                asm!(M = 0),
+            ]);
+        }
+
+        instr
+    }
+
+    fn goal(&self, func: Option<&str>) -> OptimizeFor {
+        func.and_then(|func| self.function_goals.get(func))
+            .copied()
+            .unwrap_or(self.optimize_for)
+    }
+
+    fn translate_return(&self) -> Vec<Instruction> {
+        match self.goal(self.function) {
+            OptimizeFor::Size => vec![asm!(@internal("RETURN")), asm!(0;JMP)],
+            OptimizeFor::Speed => return_frame(&self.scratch_registers()),
+        }
+    }
+
     fn translate_call(&mut self, func: String, args: u16) -> Vec<Instruction> {
-        let label = self.labeller.generate("RET_ADDRESS_CALL");
+        let label = self.labeller.generate("$RET_ADDRESS_CALL");
+
+        if self.goal(Some(&func)) == OptimizeFor::Speed {
+            return inline_call(&func, args, label);
+        }
 
+        let mut instr = self.call_target(&func, args);
+        instr.extend_from_slice(&[
+            asm!(@label.clone()),
+            asm!(D = A),
+            asm!(@internal("CALL")),
+            asm!(0;JMP),
+            asm!((label)),
+        ]);
+
+        instr
+    }
+
+    fn translate_tail_call(&self, func: &str, args: u16) -> Vec<Instruction> {
+        let mut instr = self.call_target(func, args);
+        instr.extend_from_slice(&[asm!(@internal("TAIL_CALL")), asm!(0;JMP)]);
+        instr
+    }
+
+    // Stores the number of arguments and the function's address in the scratch registers, for
+    // the shared call routines to pick up.
+    fn call_target(&self, func: &str, args: u16) -> Vec<Instruction> {
         let mut instr = match args {
-            0 => vec![asm!(@"R13"), asm!(M = 0)],
-            1 => vec![asm!(@"R13"), asm!(M = 1)],
-            2 => vec![asm!(@"R13"), asm!(D = 1), asm!(M = D + 1)],
-            _ => vec![asm!(@args), asm!(D = A), asm!(@"R13"), asm!(M = D)],
+            0 => vec![self.scratch_register(0), asm!(M = 0)],
+            1 => vec![self.scratch_register(0), asm!(M = 1)],
+            2 => vec![self.scratch_register(0), asm!(D = 1), asm!(M = D + 1)],
+            _ => vec![
+                asm!(@args),
+                asm!(D = A),
+                self.scratch_register(0),
+                asm!(M = D),
+            ],
         };
 
         instr.extend_from_slice(&[
             asm!(@func),
             asm!(D = A),
-            asm!(@"R14"),
+            self.scratch_register(1),
             asm!(M = D),
-            asm!(@label.clone()),
-            asm!(D = A),
-            asm!(@"CALL"),
-            asm!(0;JMP),
-            asm!((label)),
         ]);
 
         instr
     }
 }
 
-fn eq() -> Vec<Instruction> {
+fn eq(scratch: &[Instruction]) -> Vec<Instruction> {
     vec![
-        asm!(("EQ")),
-        asm!(@"R14"),
+        asm!((internal("EQ"))),
+        scratch[1].clone(),
         asm!(M = D),
         asm!(@"SP"),
         asm!(AM = M - 1),
@@ -302,73 +725,112 @@ fn eq() -> Vec<Instruction> {
         asm!(A = A - 1),
         asm!(D = M - D),
         asm!(M = 0),
-        asm!(@"END_EQ"),
+        asm!(@internal("END_EQ")),
         asm!(D;JNE),
         asm!(@"SP"),
         asm!(A = M - 1),
         asm!(M = -1),
-        asm!(("END_EQ")),
-        asm!(@"R14"),
+        asm!((internal("END_EQ"))),
+        scratch[1].clone(),
         asm!(A = M),
         asm!(0;JMP),
     ]
 }
 
-fn lt() -> Vec<Instruction> {
+fn lt(scratch: &[Instruction]) -> Vec<Instruction> {
+    comparison(scratch, "LT", Jump::JLT, Jump::JLT)
+}
+
+fn gt(scratch: &[Instruction]) -> Vec<Instruction> {
+    comparison(scratch, "GT", Jump::JGT, Jump::JGE)
+}
+
+// Compares the top two values on the stack, x and y, with the return address in D. Subtracting
+// y from x overflows if their signs differ, so in that case the result only depends on the sign
+// of x: the result is true if x - y would have jumped with `same_sign`, or if x itself jumps
+// with `different_sign`.
+fn comparison(
+    scratch: &[Instruction],
+    name: &str,
+    same_sign: Jump,
+    different_sign: Jump,
+) -> Vec<Instruction> {
+    let label = |suffix: &str| internal(&format!("{}_{}", name, suffix));
+    let jump = |jump: Jump| Instruction::C(None, "D".parse().unwrap(), Some(jump));
+
     vec![
-        asm!(("LT")),
-        asm!(@"R15"),
+        asm!((internal(name))),
+        scratch[2].clone(),
         asm!(M = D),
         asm!(@"SP"),
         asm!(AM = M - 1),
         asm!(D = M),
-        asm!(A = A - 1),
-        asm!(D = M - D),
-        asm!(M = 0),
-        asm!(@"END_LT"),
+        scratch[0].clone(),
+        asm!(M = D),
+        asm!(@"SP"),
+        asm!(A = M - 1),
+        asm!(D = M),
+        asm!(@label("X_NEG")),
+        asm!(D;JLT),
+        scratch[0].clone(),
+        asm!(D = M),
+        asm!(@label("DIFFERENT")),
+        asm!(D;JLT),
+        asm!(@label("SAME")),
+        asm!(0;JMP),
+        asm!((label("X_NEG"))),
+        scratch[0].clone(),
+        asm!(D = M),
+        asm!(@label("DIFFERENT")),
         asm!(D;JGE),
+        asm!((label("SAME"))),
+        scratch[0].clone(),
+        asm!(D = M),
         asm!(@"SP"),
         asm!(A = M - 1),
-        asm!(M = -1),
-        asm!(("END_LT")),
-        asm!(@"R15"),
-        asm!(A = M),
+        asm!(D = M - D),
+        asm!(@label("TRUE")),
+        jump(same_sign),
+        asm!(@label("FALSE")),
         asm!(0;JMP),
-    ]
-}
-
-fn gt() -> Vec<Instruction> {
-    vec![
-        asm!(("GT")),
-        asm!(@"R15"),
-        asm!(M = D),
+        asm!((label("DIFFERENT"))),
         asm!(@"SP"),
-        asm!(AM = M - 1),
+        asm!(A = M - 1),
         asm!(D = M),
-        asm!(A = A - 1),
-        asm!(D = M - D),
+        asm!(@label("TRUE")),
+        jump(different_sign),
+        asm!((label("FALSE"))),
+        asm!(@"SP"),
+        asm!(A = M - 1),
         asm!(M = 0),
-        asm!(@"END_GT"),
-        asm!(D;JLE),
+        scratch[2].clone(),
+        asm!(A = M),
+        asm!(0;JMP),
+        asm!((label("TRUE"))),
         asm!(@"SP"),
         asm!(A = M - 1),
         asm!(M = -1),
-        asm!(("END_GT")),
-        asm!(@"R15"),
+        scratch[2].clone(),
         asm!(A = M),
         asm!(0;JMP),
     ]
 }
 
-fn ret() -> Vec<Instruction> {
+fn ret(scratch: &[Instruction]) -> Vec<Instruction> {
+    let mut instr = vec![asm!((internal("RETURN")))];
+    instr.extend(return_frame(scratch));
+    instr
+}
+
+// Returns from the current function, restoring the caller's frame.
+fn return_frame(scratch: &[Instruction]) -> Vec<Instruction> {
     vec![
-        asm!(("RETURN")),
         asm!(@5),
         asm!(D = A),
         asm!(@"LCL"),
         asm!(A = M - D),
         asm!(D = M),
-        asm!(@"R13"),
+        scratch[0].clone(),
         asm!(M = D),
         asm!(@"SP"),
         asm!(AM = M - 1),
@@ -381,35 +843,35 @@ fn ret() -> Vec<Instruction> {
         asm!(M = D + 1),
         asm!(@"LCL"),
         asm!(D = M),
-        asm!(@"R14"),
+        scratch[1].clone(),
         asm!(AM = D - 1),
         asm!(D = M),
         asm!(@"THAT"),
         asm!(M = D),
-        asm!(@"R14"),
+        scratch[1].clone(),
         asm!(AM = M - 1),
         asm!(D = M),
         asm!(@"THIS"),
         asm!(M = D),
-        asm!(@"R14"),
+        scratch[1].clone(),
         asm!(AM = M - 1),
         asm!(D = M),
         asm!(@"ARG"),
         asm!(M = D),
-        asm!(@"R14"),
+        scratch[1].clone(),
         asm!(AM = M - 1),
         asm!(D = M),
         asm!(@"LCL"),
         asm!(M = D),
-        asm!(@"R13"),
+        scratch[0].clone(),
         asm!(A = M),
         asm!(0;JMP),
     ]
 }
 
-fn call() -> Vec<Instruction> {
+fn call(scratch: &[Instruction]) -> Vec<Instruction> {
     vec![
-        asm!(("CALL")),
+        asm!((internal("CALL"))),
         asm!(@"SP"),
         asm!(A = M),
         asm!(M = D),
@@ -435,7 +897,7 @@ fn call() -> Vec<Instruction> {
         asm!(M = D),
         asm!(@4),
         asm!(D = A),
-        asm!(@"R13"),
+        scratch[0].clone(),
         asm!(D = D + M),
         asm!(@"SP"),
         asm!(D = M - D),
@@ -445,7 +907,111 @@ fn call() -> Vec<Instruction> {
         asm!(MD = M + 1),
         asm!(@"LCL"),
         asm!(M = D),
-        asm!(@"R14"),
+        scratch[1].clone(),
+        asm!(A = M),
+        asm!(0;JMP),
+    ]
+}
+
+// Calls a function in the same way as the shared `CALL` routine, but with the number of
+// arguments known in advance.
+fn inline_call(func: &str, args: u16, label: String) -> Vec<Instruction> {
+    let mut instr = vec![
+        asm!(@label.clone()),
+        asm!(D = A),
+        asm!(@"SP"),
+        asm!(A = M),
+        asm!(M = D),
+    ];
+
+    for register in &["LCL", "ARG", "THIS", "THAT"] {
+        instr.extend(vec![
+            asm!(@*register),
+            asm!(D = M),
+            asm!(@"SP"),
+            asm!(AM = M + 1),
+            asm!(M = D),
+        ]);
+    }
+
+    instr.extend(vec![
+        asm!(@"SP"),
+        asm!(MD = M + 1),
+        asm!(@"LCL"),
+        asm!(M = D),
+        asm!(@args + 5),
+        asm!(D = D - A),
+        asm!(@"ARG"),
+        asm!(M = D),
+        asm!(@func),
+        asm!(0;JMP),
+        asm!((label)),
+    ]);
+
+    instr
+}
+
+// Jumps to a function like `call`, but reuses the current function's frame. The current
+// function's saved frame is copied above the new arguments, and then the arguments and the frame
+// are moved down together to ARG, where the current function's own arguments began. As the
+// destination is always below the source, copying upwards from the bottom is safe.
+fn tail_call(scratch: &[Instruction]) -> Vec<Instruction> {
+    let frame = internal("TAIL_CALL_FRAME");
+    let move_frame = internal("TAIL_CALL_MOVE");
+
+    vec![
+        asm!((internal("TAIL_CALL"))),
+        asm!(@5),
+        asm!(D = A),
+        asm!(@"LCL"),
+        asm!(D = M - D),
+        scratch[2].clone(),
+        asm!(M = D),
+        asm!((frame.clone())),
+        scratch[2].clone(),
+        asm!(A = M),
+        asm!(D = M),
+        asm!(@"SP"),
+        asm!(AM = M + 1),
+        asm!(A = A - 1),
+        asm!(M = D),
+        scratch[2].clone(),
+        asm!(MD = M + 1),
+        asm!(@"LCL"),
+        asm!(D = M - D),
+        asm!(@frame),
+        asm!(D;JGT),
+        asm!(@5),
+        asm!(D = A),
+        scratch[0].clone(),
+        asm!(MD = D + M),
+        asm!(@"SP"),
+        asm!(D = M - D),
+        scratch[2].clone(),
+        asm!(M = D),
+        asm!(@"ARG"),
+        asm!(D = M),
+        asm!(@"SP"),
+        asm!(M = D),
+        asm!((move_frame.clone())),
+        scratch[2].clone(),
+        asm!(A = M),
+        asm!(D = M),
+        asm!(@"SP"),
+        asm!(AM = M + 1),
+        asm!(A = A - 1),
+        asm!(M = D),
+        scratch[2].clone(),
+        asm!(M = M + 1),
+        scratch[0].clone(),
+        asm!(MD = M - 1),
+        asm!(@move_frame),
+        asm!(D;JGT),
+        asm!(@"SP"),
+        asm!(D = M),
+        asm!(@"LCL"),
+        asm!(M = D),
+        scratch[1].clone(),
         asm!(A = M),
         asm!(0;JMP),
     ]
@@ -480,6 +1046,12 @@ fn static_addr(module: &Module, idx: u16) -> Instruction {
     asm!(@symbol)
 }
 
+// Labels used only by the generated code start with `$`, which can't begin a Jack or VM
+// identifier, so they never clash with the program's own functions and labels.
+fn internal(name: &str) -> String {
+    format!("${}", name)
+}
+
 fn func_label_name(function: Option<&str>, label: &str) -> String {
     function.map_or_else(|| label.to_owned(), |func| format!("{}${}", func, label))
 }
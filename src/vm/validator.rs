@@ -0,0 +1,330 @@
+use super::*;
+use crate::asm::MemoryLayout;
+use anyhow::{anyhow, Error};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// The classes of the standard OS, which the course's own tools provide when a program leaves
+// out their `.vm` files.
+pub const OS_CLASSES: &[&str] = &[
+    "Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String", "Sys",
+];
+
+// Checks a whole VM program for mistakes which would otherwise only surface as confusing
+// assembler errors, or as crashes at runtime.
+pub struct Validator<'a> {
+    modules: &'a [Module],
+    init: Option<String>,
+    layout: MemoryLayout,
+    externals: HashSet<String>,
+    os_external: bool,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(modules: &'a [Module]) -> Self {
+        Validator {
+            modules,
+            init: Some(DEFAULT_INIT.to_owned()),
+            layout: MemoryLayout::new(),
+            externals: HashSet::new(),
+            os_external: false,
+        }
+    }
+
+    pub fn init(&mut self, init: Option<String>) {
+        self.init = init;
+    }
+
+    pub fn layout(&mut self, layout: MemoryLayout) {
+        self.layout = layout;
+    }
+
+    // Allows the given function to be called without being defined by any module, for example
+    // when the OS is provided separately. A name of the form `Class.*` allows every function in
+    // the class.
+    pub fn external(&mut self, name: String) {
+        self.externals.insert(name);
+    }
+
+    // Allows calls to any function in the standard OS classes without it being defined, as if
+    // each class had been given to `external`.
+    pub fn os_external(&mut self, enabled: bool) {
+        self.os_external = enabled;
+    }
+
+    // The OS functions which the program calls without defining them, including the init
+    // function if it's one of them.
+    pub fn undefined_os_functions(&self) -> Vec<&str> {
+        let graph = CallGraph::new(self.modules);
+        let mut undefined = Vec::new();
+
+        let calls = graph
+            .top_level()
+            .into_iter()
+            .chain(self.init.as_deref())
+            .chain(
+                graph
+                    .functions()
+                    .iter()
+                    .flat_map(|func| graph.callees(func)),
+            );
+        for func in calls {
+            if is_os_function(func)
+                && !graph.functions().contains(&func)
+                && !self.is_listed_external(func)
+                && !undefined.contains(&func)
+            {
+                undefined.push(func);
+            }
+        }
+
+        undefined
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        let mut functions = HashMap::new();
+        let mut statics = 0;
+        let static_area = self.layout.statics.len();
+
+        for module in self.modules {
+            for cmd in &module.cmds {
+                if let Command::Function(name, _) = cmd {
+                    if !is_function_name(name) {
+                        errors.push(format!("`{}` is not a valid function name", name));
+                    } else if let Some(kind) = self.symbol_clash(name) {
+                        errors.push(format!("function `{}` clashes with {}", name, kind));
+                    }
+
+                    if let Some(other) = functions.insert(name.as_str(), &module.name) {
+                        errors.push(format!(
+                            "function `{}` in `{}` is already defined in `{}`",
+                            name, module.name, other
+                        ));
+                    }
+                }
+            }
+
+            let module_statics = module
+                .cmds
+                .iter()
+                .filter_map(|cmd| match cmd {
+                    Command::Push(Segment::Static, idx) | Command::Pop(Segment::Static, idx) => {
+                        Some(*idx)
+                    }
+                    _ => None,
+                })
+                .collect::<HashSet<_>>()
+                .len();
+
+            statics += module_statics;
+            if statics > static_area {
+                errors.push(format!(
+                    "module `{}` brings the total number of statics to {}, but only {} fit in the \
+                     static area",
+                    module.name, statics, static_area
+                ));
+            }
+        }
+
+        // Labels outside of any function are global in the generated code, so they mustn't clash
+        // with anything else in the program.
+        let mut top_level_labels = HashMap::new();
+
+        for module in self.modules {
+            for scope in function_scopes(module) {
+                errors.extend(self.validate_scope(&scope, &functions));
+
+                if scope.function.is_some() {
+                    continue;
+                }
+
+                for cmd in scope.cmds {
+                    let label = match cmd {
+                        Command::Label(label) if is_identifier(label) => label.as_str(),
+                        _ => continue,
+                    };
+
+                    if let Some(kind) = self.symbol_clash(label) {
+                        errors.push(format!(
+                            "{}: label `{}` clashes with {}",
+                            scope, label, kind
+                        ));
+                    } else if functions.contains_key(label) {
+                        errors.push(format!(
+                            "{}: label `{}` clashes with a function of the same name",
+                            scope, label
+                        ));
+                    } else if let Some(other) = top_level_labels.insert(label, &module.name) {
+                        if *other != module.name {
+                            errors.push(format!(
+                                "{}: label `{}` is also defined in module `{}`",
+                                scope, label, other
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(init) = &self.init {
+            if !functions.contains_key(init.as_str()) && !self.is_external(init) {
+                errors.push(format!("init function `{}` is not defined", init));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("{}", errors.join("\n")))
+        }
+    }
+
+    fn validate_scope(&self, scope: &Scope, functions: &HashMap<&str, &String>) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut labels = HashSet::new();
+
+        for cmd in scope.cmds {
+            if let Command::Label(label) = cmd {
+                if !is_identifier(label) {
+                    errors.push(format!("{}: `{}` is not a valid label name", scope, label));
+                } else if !labels.insert(label.as_str()) {
+                    errors.push(format!("{}: label `{}` is defined twice", scope, label));
+                }
+            }
+        }
+
+        for cmd in scope.cmds {
+            match cmd {
+                Command::Goto(label) | Command::IfGoto(label)
+                    if !labels.contains(label.as_str()) =>
+                {
+                    errors.push(format!(
+                        "{}: `{}` targets undefined label `{}`",
+                        scope, cmd, label
+                    ))
+                }
+                Command::Call(func, _)
+                    if !functions.contains_key(func.as_str()) && !self.is_external(func) =>
+                {
+                    errors.push(format!("{}: call to undefined function `{}`", scope, func))
+                }
+                Command::Pop(Segment::Constant, _) => {
+                    errors.push(format!("{}: `{}` is not allowed", scope, cmd))
+                }
+                _ => {}
+            }
+        }
+
+        errors
+    }
+
+    // Names which the assembler already gives a meaning to, either as one of the predefined
+    // symbols or as a static variable.
+    fn symbol_clash(&self, name: &str) -> Option<&'static str> {
+        if self
+            .layout
+            .symbols()
+            .iter()
+            .any(|(symbol, _)| symbol == name)
+        {
+            return Some("a predefined symbol");
+        }
+
+        match name.rsplit_once('.') {
+            Some((_, idx)) if !idx.is_empty() && idx.bytes().all(|b| b.is_ascii_digit()) => {
+                Some("the symbol for a static variable")
+            }
+            _ => None,
+        }
+    }
+
+    fn is_external(&self, func: &str) -> bool {
+        (self.os_external && is_os_function(func)) || self.is_listed_external(func)
+    }
+
+    // Whether the function was allowed by a call to `external`.
+    fn is_listed_external(&self, func: &str) -> bool {
+        if self.externals.contains(func) {
+            return true;
+        }
+
+        match func.split_once('.') {
+            Some((class, _)) => self.externals.contains(&format!("{}.*", class)),
+            None => false,
+        }
+    }
+}
+
+fn is_os_function(func: &str) -> bool {
+    matches!(func.split_once('.'), Some((class, _)) if OS_CLASSES.contains(&class))
+}
+
+// Identifiers in VM code are made up of letters, digits, `_`, `.` and `:`, and have to begin
+// with a letter or `_`. The translator relies on this to keep its own labels apart from the
+// program's.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == ':')
+}
+
+// Function names are split by `.` into the class and subroutine names, neither of which can be
+// empty. Otherwise, a label `init` in function `Ball.` would be translated as `Ball.$init`, with
+// a `$` straight after the class name like the translator's own names.
+fn is_function_name(name: &str) -> bool {
+    is_identifier(name) && name.split('.').all(|part| !part.is_empty())
+}
+
+// A run of commands in which labels are visible: either a single function, or any commands
+// at the start of a module which precede its first function.
+struct Scope<'a> {
+    module: &'a str,
+    function: Option<&'a str>,
+    cmds: &'a [Command],
+}
+
+impl fmt::Display for Scope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.function {
+            Some(func) => write!(f, "in function `{}`", func),
+            None => write!(f, "in module `{}`", self.module),
+        }
+    }
+}
+
+fn function_scopes(module: &Module) -> Vec<Scope<'_>> {
+    let mut scopes = Vec::new();
+    let mut start = 0;
+    let mut function = None;
+
+    for (i, cmd) in module.cmds.iter().enumerate() {
+        if let Command::Function(name, _) = cmd {
+            if i > start || function.is_some() {
+                scopes.push(Scope {
+                    module: &module.name,
+                    function,
+                    cmds: &module.cmds[start..i],
+                });
+            }
+
+            start = i;
+            function = Some(name.as_str());
+        }
+    }
+
+    if start < module.cmds.len() {
+        scopes.push(Scope {
+            module: &module.name,
+            function,
+            cmds: &module.cmds[start..],
+        });
+    }
+
+    scopes
+}
@@ -0,0 +1,113 @@
+use jackc::asm;
+use jackc::asm::Instruction;
+
+fn optimize(prog: Vec<Instruction>) -> Vec<String> {
+    asm::optimize(prog)
+        .iter()
+        .map(|instr| instr.to_string())
+        .collect()
+}
+
+fn push() -> Vec<Instruction> {
+    vec![asm!(@"SP"), asm!(AM = M + 1), asm!(A = A - 1), asm!(M = D)]
+}
+
+fn pop() -> Vec<Instruction> {
+    vec![asm!(@"SP"), asm!(AM = M - 1), asm!(D = M)]
+}
+
+#[test]
+fn push_pop_test() {
+    let prog = [push(), pop(), vec![asm!(@"THIS"), asm!(M = D)]].concat();
+    assert_eq!(optimize(prog), &["@THIS", "M=D"]);
+
+    let prog = [pop(), push(), vec![asm!(@"THIS"), asm!(M = D)]].concat();
+    assert_eq!(optimize(prog), &["@SP", "A=M-1", "D=M", "@THIS", "M=D"]);
+
+    let prog = vec![
+        asm!(@"SP"),
+        asm!(AM = M + 1),
+        asm!(A = A - 1),
+        asm!(M = -1),
+        asm!(@"SP"),
+        asm!(AM = M - 1),
+        asm!(D = M),
+        asm!(M = D),
+    ];
+    assert_eq!(optimize(prog), &["@SP", "A=M", "D=-1", "M=D"]);
+
+    // The pop can be reached from elsewhere, so has to stay.
+    let prog = [push(), vec![asm!(("LOOP"))], pop()].concat();
+    assert_eq!(optimize(prog.clone()).len(), prog.len());
+}
+
+#[test]
+fn redundant_load_test() {
+    let prog = vec![
+        asm!(@"SP"),
+        asm!(M = M - 1),
+        asm!(@"SP"),
+        asm!(A = M),
+        asm!(M = 0),
+        asm!(@"SP"),
+        asm!(M = M + 1),
+        asm!(("LOOP")),
+        asm!(@"SP"),
+        asm!(M = M + 1),
+    ];
+
+    assert_eq!(
+        optimize(prog),
+        &["@SP", "M=M-1", "A=M", "M=0", "@SP", "M=M+1", "(LOOP)", "@SP", "M=M+1"]
+    );
+}
+
+#[test]
+fn jumps_test() {
+    let prog = vec![
+        asm!(@"END"),
+        asm!(0;JMP),
+        asm!(@"R13"),
+        asm!(M = D),
+        asm!(("END")),
+        asm!(@"R14"),
+        asm!(M = D),
+        asm!(@"NEXT"),
+        asm!(D;JNE),
+        asm!(("NEXT")),
+        asm!(@"R15"),
+        asm!(M = D),
+    ];
+
+    assert_eq!(
+        optimize(prog),
+        &["(END)", "@R14", "M=D", "(NEXT)", "@R15", "M=D"]
+    );
+}
+
+#[test]
+fn dead_store_test() {
+    let prog = vec![
+        asm!(@"R13"),
+        asm!(D = A),
+        asm!(@"R14"),
+        asm!(D = M),
+        asm!(@"R15"),
+        asm!(M = D),
+    ];
+    assert_eq!(optimize(prog), &["@R14", "D=M", "@R15", "M=D"]);
+
+    // The jump's target might use D, and the last value of A is left for whatever comes after.
+    let prog = vec![
+        asm!(@"RETURN"),
+        asm!(D = A),
+        asm!(@"CALL"),
+        asm!(0;JMP),
+        asm!(("RETURN")),
+        asm!(@"R13"),
+    ];
+    assert_eq!(
+        optimize(prog),
+        &["@RETURN", "D=A", "@CALL", "0;JMP", "(RETURN)", "@R13"]
+    );
+}
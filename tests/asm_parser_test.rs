@@ -0,0 +1,214 @@
+use jackc::asm::{self, Comp, Dest, Instruction, Jump, Load};
+use jackc::hack::Emulator;
+use jackc::vm::{self, Module, Translator};
+
+#[test]
+fn parse_test() {
+    let source = "// Adds 2 and 3, storing the result in RAM[0].\r\n\
+                  \r\n\
+                  @2\r\n\
+                  \tD = A   \r\n\
+                  @3 // the second operand\r\n\
+                  \x20\x20D=D+A\r\n\
+                  @R0\r\n\
+                  M=D";
+
+    assert_eq!(
+        asm::parse(source).unwrap(),
+        vec![
+            Instruction::A(Load::Constant(2)),
+            Instruction::C(Some(Dest::D), Comp::A, None),
+            Instruction::A(Load::Constant(3)),
+            Instruction::C(Some(Dest::D), Comp::DPlusA, None),
+            Instruction::A(Load::Symbol("R0".into())),
+            Instruction::C(Some(Dest::M), Comp::D, None),
+        ]
+    );
+}
+
+#[test]
+fn parse_all_forms_test() {
+    let source = "
+        (Main.main$LOOP)
+        @Main.main$LOOP
+        @$RET_ADDRESS_CALL0
+        @sys:halt_1
+        @32767
+        AMD=M+1;JGT
+        DM=A+D
+        0;JMP
+        D;JLE
+        M=-1;JEQ
+        D|M
+        AM = M - 1 ; JNE  ";
+
+    assert_eq!(
+        asm::parse(source).unwrap(),
+        vec![
+            Instruction::Label("Main.main$LOOP".into()),
+            Instruction::A(Load::Symbol("Main.main$LOOP".into())),
+            Instruction::A(Load::Symbol("$RET_ADDRESS_CALL0".into())),
+            Instruction::A(Load::Symbol("sys:halt_1".into())),
+            Instruction::A(Load::Constant(32767)),
+            Instruction::C(Some(Dest::AMD), Comp::MPlusOne, Some(Jump::JGT)),
+            Instruction::C(Some(Dest::MD), Comp::DPlusA, None),
+            Instruction::C(None, Comp::Zero, Some(Jump::JMP)),
+            Instruction::C(None, Comp::D, Some(Jump::JLE)),
+            Instruction::C(Some(Dest::M), Comp::NegOne, Some(Jump::JEQ)),
+            Instruction::C(None, Comp::DOrM, None),
+            Instruction::C(Some(Dest::AM), Comp::MMinusOne, Some(Jump::JNE)),
+        ]
+    );
+}
+
+#[test]
+fn parse_error_test() {
+    let cases = &[
+        (
+            "@0\n@32768",
+            "line 2: `@32768` is out of range, as an A-instruction can only load up to 32767",
+        ),
+        ("@-1", "line 1: `-1` is not a valid symbol"),
+        ("@1abc", "line 1: `1abc` is not a valid symbol"),
+        ("(LOOP", "line 1: expected `)` at the end of label `(LOOP`"),
+        ("(a-b)", "line 1: `a-b` is not a valid symbol"),
+        ("@FOO BAR", "line 1: `FOO BAR` is not a valid symbol"),
+        ("@1 2", "line 1: `1 2` is not a valid symbol"),
+        ("(MY LOOP)", "line 1: `MY LOOP` is not a valid symbol"),
+        ("A D=M", "line 1: `A D` is not a valid destination"),
+        ("0;J MP", "line 1: `J MP` is not a valid jump"),
+        ("MM=D", "line 1: `MM` is not a valid destination"),
+        ("=D", "line 1: expected a destination before `=`"),
+        ("D=D*A", "line 1: `D*A` is not a valid computation"),
+        ("0;JMP\n\n0;JUMP", "line 3: `JUMP` is not a valid jump"),
+    ];
+
+    for (source, expected) in cases {
+        let err = asm::parse(source).unwrap_err().to_string();
+        assert_eq!(&err, expected, "`{}`", source);
+    }
+}
+
+// Every instruction generated by the translator can be written out and read back in.
+#[test]
+fn display_round_trip_test() {
+    let modules = [Module::new(
+        "Main".into(),
+        vm::parse(
+            "function Main.main 2
+             push constant 40000
+             push argument 7
+             lt
+             if-goto END
+             push local 1
+             call Main.main 1
+             label END
+             return",
+        )
+        .unwrap(),
+    )];
+
+    let prog = Translator::new(&modules).translate().unwrap();
+    let source = prog
+        .iter()
+        .map(|instr| instr.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert_eq!(asm::parse(&source).unwrap(), prog);
+}
+
+// Multiplies RAM[0] by RAM[1] into RAM[2], as in project 4 of the course.
+#[test]
+fn standalone_program_test() {
+    let source = "
+        @R2
+        M=0
+        (LOOP)
+        @R1
+        D=M
+        @END
+        D;JLE   // stop once RAM[1] has counted down to zero
+        @R0
+        D=M
+        @R2
+        M=D+M
+        @R1
+        M=M-1
+        @LOOP
+        0;JMP
+        (END)
+        @END
+        0;JMP";
+
+    let rom = asm::assemble(&asm::parse(source).unwrap()).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    emulator.ram.init(&[(0, 123), (1, 45)]);
+    emulator.run(1000);
+
+    assert_eq!(emulator.ram.get(2), 5535);
+}
+
+// The course's own Add.asm from project 6, along with the expected Add.hack.
+#[test]
+fn assemble_test() {
+    let source = "// Computes R0 = 2 + 3  (R0 refers to RAM[0])
+        @2
+        D=A
+        @3
+        D=D+A
+        @0
+        M=D";
+
+    assert_eq!(
+        asm::assemble(&asm::parse(source).unwrap()).unwrap(),
+        &[
+            0b0000000000000010,
+            0b1110110000010000,
+            0b0000000000000011,
+            0b1110000010010000,
+            0b0000000000000000,
+            0b1110001100001000,
+        ]
+    );
+}
+
+// Hand-written routines can be linked after the translated VM code and called from it, as long
+// as they follow the VM's calling convention.
+#[test]
+fn link_test() {
+    let modules = [Module::new(
+        "Sys".into(),
+        vm::parse(
+            "function Sys.init 0
+             push constant 21
+             call Asm.double 1
+             pop static 0
+             label HALT
+             goto HALT",
+        )
+        .unwrap(),
+    )];
+
+    let routine = "
+        (Asm.double)
+        @ARG
+        A=M
+        D=M
+        D=D+M
+        @SP
+        AM=M+1
+        A=A-1
+        M=D
+        @$RETURN
+        0;JMP";
+
+    let mut prog = Translator::new(&modules).translate().unwrap();
+    prog.extend(asm::parse(routine).unwrap());
+
+    let rom = asm::assemble(&prog).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(1000);
+
+    assert_eq!(emulator.ram.get(16), 42);
+}
@@ -0,0 +1,294 @@
+use jackc::asm;
+use jackc::hack::Emulator;
+use jackc::vm::{self, CallGraph, Module, StackDepth, StaticFrame, Translator};
+
+fn module(name: &str, source: &str) -> Module {
+    Module::new(name.to_owned(), vm::parse(source).unwrap())
+}
+
+fn recursive_program() -> Vec<Module> {
+    vec![
+        module(
+            "Main",
+            "function Main.main 0
+             call Main.even 0
+             call Math.abs 1
+             return
+             function Main.even 0
+             call Main.odd 0
+             return
+             function Main.odd 0
+             call Main.even 0
+             return
+             function Main.loop 0
+             call Main.loop 0
+             return
+             function Main.unused 0
+             return",
+        ),
+        module(
+            "Sys",
+            "function Sys.init 0
+             call Main.main 0
+             return",
+        ),
+    ]
+}
+
+#[test]
+fn callees_test() {
+    let modules = recursive_program();
+    let graph = CallGraph::new(&modules);
+
+    assert_eq!(
+        graph.functions(),
+        &[
+            "Main.main",
+            "Main.even",
+            "Main.odd",
+            "Main.loop",
+            "Main.unused",
+            "Sys.init"
+        ]
+    );
+    assert_eq!(graph.callees("Main.main"), &["Main.even", "Math.abs"]);
+    assert!(graph.callees("Main.unused").is_empty());
+
+    let mut reachable = graph
+        .reachable(vec!["Sys.init"])
+        .into_iter()
+        .collect::<Vec<_>>();
+    reachable.sort_unstable();
+    assert_eq!(
+        reachable,
+        &["Main.even", "Main.main", "Main.odd", "Math.abs", "Sys.init"]
+    );
+}
+
+#[test]
+fn cycles_test() {
+    let modules = recursive_program();
+    let graph = CallGraph::new(&modules);
+
+    assert_eq!(
+        graph.cycles(),
+        vec![vec!["Main.even", "Main.odd"], vec!["Main.loop"]]
+    );
+    assert_eq!(
+        graph.stack_depth("Sys.init"),
+        StackDepth::Recursive("Main.even".into())
+    );
+    assert_eq!(graph.stack_depth("Main.unused"), StackDepth::Bounded(5));
+}
+
+#[test]
+fn hot_functions_test() {
+    let modules = [module(
+        "Main",
+        "function Main.main 0
+         call Main.setup 0
+         label LOOP
+         call Main.step 0
+         push constant 1
+         if-goto LOOP
+         call Main.teardown 0
+         label SKIP
+         return
+         function Main.setup 0
+         goto SKIP
+         call Main.unused 0
+         label SKIP
+         return
+         function Main.step 0
+         call Math.abs 1
+         return
+         function Main.teardown 0
+         call Main.even 0
+         return",
+    )];
+
+    let mut hot = CallGraph::new(&modules)
+        .hot_functions()
+        .into_iter()
+        .collect::<Vec<_>>();
+    hot.sort_unstable();
+    assert_eq!(hot, &["Main.step", "Math.abs"]);
+
+    let modules = recursive_program();
+    let mut hot = CallGraph::new(&modules)
+        .hot_functions()
+        .into_iter()
+        .collect::<Vec<_>>();
+    hot.sort_unstable();
+    assert_eq!(hot, &["Main.even", "Main.loop", "Main.odd"]);
+}
+
+#[test]
+fn static_frames_test() {
+    let modules = [module(
+        "Main",
+        "function Main.main 2
+         push argument 0
+         call Main.first 1
+         call Main.second 0
+         call Main.even 0
+         return
+         function Main.first 0
+         push argument 1
+         call Main.leaf 0
+         return
+         function Main.second 1
+         push local 3
+         call Main.leaf 0
+         return
+         function Main.leaf 1
+         return
+         function Main.even 0
+         call Main.odd 0
+         return
+         function Main.odd 0
+         call Main.even 0
+         call Main.leaf 0
+         return",
+    )];
+
+    let frames = CallGraph::new(&modules).static_frames();
+    let frame = |func| frames.get(func).copied();
+
+    let frame_at = |base, args, locals| Some(StaticFrame { base, args, locals });
+    assert_eq!(frame("Main.main"), frame_at(0, 1, 2));
+    // Main.first and Main.second are never running at the same time, so share slots.
+    assert_eq!(frame("Main.first"), frame_at(3, 2, 0));
+    assert_eq!(frame("Main.second"), frame_at(3, 0, 4));
+    // Main.leaf can be called through either of them, or while the recursive functions run.
+    assert_eq!(frame("Main.leaf"), frame_at(7, 0, 1));
+    assert_eq!(frame("Main.even"), None);
+    assert_eq!(frame("Main.odd"), None);
+}
+
+#[test]
+fn undefined_test() {
+    let modules = [module(
+        "Main",
+        "function Main.main 0
+         push constant 1
+         call Math.abs 1
+         return",
+    )];
+    let graph = CallGraph::new(&modules);
+
+    assert_eq!(
+        graph.stack_depth("Main.main"),
+        StackDepth::Undefined("Math.abs".into())
+    );
+    assert_eq!(
+        graph.stack_depth("Sys.init"),
+        StackDepth::Undefined("Sys.init".into())
+    );
+}
+
+#[test]
+fn dot_test() {
+    let modules = recursive_program();
+    let graph = CallGraph::new(&modules);
+
+    assert_eq!(
+        graph.to_dot(),
+        "digraph callgraph {
+    node [shape=box];
+    \"Main.main\";
+    \"Main.even\" [color=red];
+    \"Main.odd\" [color=red];
+    \"Main.loop\" [color=red];
+    \"Main.unused\";
+    \"Sys.init\";
+    \"Math.abs\" [style=dashed];
+    \"Main.main\" -> \"Main.even\";
+    \"Main.main\" -> \"Math.abs\";
+    \"Main.even\" -> \"Main.odd\" [color=red];
+    \"Main.odd\" -> \"Main.even\" [color=red];
+    \"Main.loop\" -> \"Main.loop\" [color=red];
+    \"Sys.init\" -> \"Main.main\";
+}
+"
+    );
+}
+
+#[test]
+fn stack_depth_test() {
+    let modules = [
+        module(
+            "Main",
+            "function Main.main 2
+             push constant 1
+             push constant 2
+             push constant 3
+             call Main.sum 3
+             pop local 0
+             push constant 10
+             push local 0
+             push constant 1
+             push constant 2
+             call Main.sum 3
+             add
+             return
+             function Main.sum 1
+             push argument 0
+             push argument 1
+             add
+             push argument 2
+             add
+             pop local 0
+             push local 0
+             if-goto POSITIVE
+             push constant 0
+             return
+             label POSITIVE
+             push local 0
+             return",
+        ),
+        module(
+            "Sys",
+            "function Sys.init 0
+             call Main.main 0
+             pop temp 0
+             label HALT
+             goto HALT",
+        ),
+    ];
+
+    let graph = CallGraph::new(&modules);
+
+    // Main.sum uses its call frame, one local and two operands.
+    assert_eq!(
+        graph.stack_depth("Main.sum"),
+        StackDepth::Bounded(5 + 1 + 2)
+    );
+    // Main.main reaches its deepest point calling Main.sum with four operands on the stack.
+    assert_eq!(
+        graph.stack_depth("Main.main"),
+        StackDepth::Bounded(5 + 2 + 4 + 8)
+    );
+
+    let bound = match graph.stack_depth("Sys.init") {
+        StackDepth::Bounded(words) => words,
+        depth => panic!("unexpected stack depth: {}", depth),
+    };
+
+    let mut translator = Translator::new(&modules);
+    translator.init(Some("Sys.init".into()));
+    let rom = asm::assemble(&translator.translate().unwrap()).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    let mut max_sp = 0;
+    for _ in 0..2000 {
+        emulator.step();
+        max_sp = max_sp.max(emulator.ram.get(0));
+    }
+
+    assert_eq!(emulator.ram.get(5), 19);
+    assert_eq!(
+        (max_sp - asm::MemoryLayout::new().stack_base) as usize,
+        bound
+    );
+}
@@ -0,0 +1,154 @@
+use jackc::vm::{self, Interpreter, Module};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::{env, fs, process};
+
+// Creates an empty directory for a test's input and output files.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("jackc-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn testdata(filename: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("testdata")
+        .join(filename)
+}
+
+fn jackc(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jackc"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn read_vm(path: &Path) -> Module {
+    let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+    let source = fs::read_to_string(path).unwrap();
+    Module::new(name, vm::parse(&source).unwrap())
+}
+
+#[test]
+fn vm_static_init_test() {
+    let dir = temp_dir("vm_static_init");
+    fs::write(
+        dir.join("Main.jack"),
+        "class Main {
+             function void main() {
+                 do Output.printInt(Ball.speed());
+                 return;
+             }
+         }",
+    )
+    .unwrap();
+
+    let ball = testdata("StaticInit.jack");
+    let output = jackc(
+        &dir,
+        &["--vm", "-o", "out", "Main.jack", ball.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    // The `.vm` files must run on their own, without being linked again.
+    let modules = [
+        read_vm(&dir.join("out").join("Ball.vm")),
+        read_vm(&dir.join("out").join("Main.vm")),
+        Module::new(
+            "Sys".into(),
+            vm::parse(
+                "function Sys.init 0
+                 call Main.main 0
+                 pop temp 0
+                 label HALT
+                 goto HALT",
+            )
+            .unwrap(),
+        ),
+    ];
+
+    let printed = RefCell::new(Vec::new());
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Array.new", |_, _| Ok(2048));
+    interpreter.native("Math.multiply", |_, args| Ok(args[0].wrapping_mul(args[1])));
+    interpreter.native("Output.printInt", |_, args| {
+        printed.borrow_mut().push(args[0]);
+        Ok(0)
+    });
+
+    assert!(interpreter.run(1000).unwrap());
+    drop(interpreter);
+    assert_eq!(printed.into_inner(), &[3]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn without_os_test() {
+    let dir = temp_dir("without_os");
+    fs::write(
+        dir.join("Main.jack"),
+        "class Main {
+             function void main() {
+                 do Output.printInt(6 * 7);
+                 return;
+             }
+         }",
+    )
+    .unwrap();
+
+    // The OS `.vm` files can be left out, as with the course's own tools.
+    let output = jackc(&dir, &["Main.jack"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("Main.hack").exists());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Warning: the program calls OS functions which aren't defined, so the OS `.vm` files \
+         need to be included for it to run: Sys.init, Math.multiply, Output.printInt\n"
+    );
+
+    let output = jackc(&dir, &["--strict", "-o", "Strict.hack", "Main.jack"]);
+    assert!(!output.status.success());
+    assert!(!dir.join("Strict.hack").exists());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: in function `Main.main`: call to undefined function `Math.multiply`\n\
+         in function `Main.main`: call to undefined function `Output.printInt`\n\
+         init function `Sys.init` is not defined\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn verbose_test() {
+    let dir = temp_dir("verbose");
+    fs::write(
+        dir.join("Sys.vm"),
+        "function Sys.init 0
+         label HALT
+         goto HALT
+         function Sys.unused 0
+         push constant 0
+         return",
+    )
+    .unwrap();
+
+    let output = jackc(&dir, &["--remove-unreachable", "Sys.vm"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty());
+
+    // The functions which were left out are only listed when asked for.
+    let output = jackc(&dir, &["--remove-unreachable", "--verbose", "Sys.vm"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Removed 1 unreachable function: Sys.unused\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
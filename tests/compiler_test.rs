@@ -1,5 +1,7 @@
-use jackc::jack::{Compiler, Parser, Tokenizer};
-use jackc::vm::{Command, Module, Segment};
+use jackc::jack::{
+    Compiler, Parser, Tokenizer, ERR_INDEX_OUT_OF_BOUNDS, ERR_NULL_ARRAY, ERR_NULL_RECEIVER,
+};
+use jackc::vm::{self, Command, Module, Segment};
 
 mod helpers;
 
@@ -1163,3 +1165,219 @@ fn bat_jack_test() {
         )
     );
 }
+
+#[test]
+fn static_init_jack_test() {
+    let source = helpers::read_test_file("StaticInit.jack");
+    let tokens = Tokenizer::new(&source).tokenize().expect("tokenizer error");
+    let class = Parser::new(tokens).parse().expect("parsing error");
+    let module = Compiler::new(&class).compile().unwrap();
+
+    assert_eq!(
+        module,
+        Module::new(
+            "Ball".into(),
+            vec![
+                Command::Function("Ball.speed".into(), 0),
+                Command::Push(Segment::Static, 0),
+                Command::Return,
+                Command::Function("Ball.static.init".into(), 0),
+                Command::Push(Segment::Constant, 3),
+                Command::Pop(Segment::Static, 0),
+                Command::Push(Segment::Static, 0),
+                Command::Push(Segment::Constant, 2),
+                Command::Call("Math.multiply".into(), 2),
+                Command::Call("Array.new".into(), 1),
+                Command::Pop(Segment::Static, 2),
+                Command::Push(Segment::Constant, 0),
+                Command::Return,
+            ]
+        )
+    );
+
+    assert_eq!(module.static_init(), Some("Ball.static.init".into()));
+}
+
+#[test]
+fn static_init_errors_test() {
+    let compile = |init: &str| {
+        let source = format!(
+            "class Ball {{
+                 field int x;
+                 static int speed = {};
+
+                 method int size() {{
+                     return 1;
+                 }}
+             }}",
+            init
+        );
+        let tokens = Tokenizer::new(&source).tokenize().unwrap();
+        let class = Parser::new(tokens).parse().unwrap();
+        Compiler::new(&class)
+            .compile()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+
+    // Static initialisers run outside of any object, so can't refer to one.
+    assert_eq!(
+        compile("x + 1"),
+        Err("can't use field `x` in a static initialiser".into())
+    );
+    assert_eq!(
+        compile("this"),
+        Err("can't use `this` in a static initialiser".into())
+    );
+    assert_eq!(
+        compile("size()"),
+        Err("can't call method `size` in a static initialiser".into())
+    );
+    assert_eq!(compile("Ball.size() + speed"), Ok(()));
+}
+
+#[test]
+fn debug_checks_jack_test() {
+    let source = helpers::read_test_file("DebugChecks.jack");
+    let tokens = Tokenizer::new(&source).tokenize().expect("tokenizer error");
+    let class = Parser::new(tokens).parse().expect("parsing error");
+
+    let mut compiler = Compiler::new(&class);
+    compiler.debug_checks(true);
+    let module = compiler.compile().unwrap();
+
+    assert_eq!(
+        module,
+        Module::new(
+            "Main".into(),
+            vec![
+                Command::Function("Main.main".into(), 2),
+                Command::Push(Segment::Constant, 3),
+                Command::Call("Array.new".into(), 1),
+                Command::Pop(Segment::Local, 0),
+                // a[2]
+                Command::Push(Segment::Local, 0),
+                Command::IfGoto("CHECK_OK0".into()),
+                Command::Push(Segment::Constant, ERR_NULL_ARRAY),
+                Command::Call("Sys.error".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Label("CHECK_OK0".into()),
+                Command::Push(Segment::Constant, 2),
+                Command::Pop(Segment::Temp, 1),
+                Command::Push(Segment::Temp, 1),
+                Command::Push(Segment::Constant, 0),
+                Command::Lt,
+                Command::IfGoto("CHECK_ERROR0".into()),
+                Command::Push(Segment::Temp, 1),
+                Command::Push(Segment::Local, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Pop(Segment::Pointer, 1),
+                Command::Push(Segment::That, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Lt,
+                Command::IfGoto("CHECK_OK1".into()),
+                Command::Label("CHECK_ERROR0".into()),
+                Command::Push(Segment::Constant, ERR_INDEX_OUT_OF_BOUNDS),
+                Command::Call("Sys.error".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Label("CHECK_OK1".into()),
+                Command::Push(Segment::Local, 0),
+                Command::Push(Segment::Temp, 1),
+                Command::Add,
+                Command::Pop(Segment::Pointer, 1),
+                Command::Push(Segment::That, 0),
+                // let a[1] = ...
+                Command::Push(Segment::Local, 0),
+                Command::IfGoto("CHECK_OK2".into()),
+                Command::Push(Segment::Constant, ERR_NULL_ARRAY),
+                Command::Call("Sys.error".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Label("CHECK_OK2".into()),
+                Command::Push(Segment::Constant, 1),
+                Command::Pop(Segment::Temp, 1),
+                Command::Push(Segment::Temp, 1),
+                Command::Push(Segment::Constant, 0),
+                Command::Lt,
+                Command::IfGoto("CHECK_ERROR1".into()),
+                Command::Push(Segment::Temp, 1),
+                Command::Push(Segment::Local, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Pop(Segment::Pointer, 1),
+                Command::Push(Segment::That, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Lt,
+                Command::IfGoto("CHECK_OK3".into()),
+                Command::Label("CHECK_ERROR1".into()),
+                Command::Push(Segment::Constant, ERR_INDEX_OUT_OF_BOUNDS),
+                Command::Call("Sys.error".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Label("CHECK_OK3".into()),
+                Command::Push(Segment::Local, 0),
+                Command::Push(Segment::Temp, 1),
+                Command::Add,
+                Command::Pop(Segment::Pointer, 1),
+                Command::Pop(Segment::That, 0),
+                // do s.draw()
+                Command::Push(Segment::Local, 1),
+                Command::IfGoto("CHECK_OK4".into()),
+                Command::Push(Segment::Constant, ERR_NULL_RECEIVER),
+                Command::Call("Sys.error".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Label("CHECK_OK4".into()),
+                Command::Push(Segment::Local, 1),
+                Command::Call("Square.draw".into(), 1),
+                Command::Pop(Segment::Temp, 0),
+                Command::Push(Segment::Constant, 0),
+                Command::Return,
+            ]
+        )
+    );
+}
+
+#[test]
+fn annotate_test() {
+    let source = "
+        class Main {
+            function int abs(int x) {
+                if (x < 0) {
+                    let x = -x;
+                }
+                return x;
+            }
+        }
+    ";
+    let tokens = Tokenizer::new(source).tokenize().expect("tokenizer error");
+    let class = Parser::new(tokens).parse().expect("parsing error");
+
+    let mut compiler = Compiler::new(&class);
+    compiler.annotate(true);
+    let module = compiler.compile().unwrap();
+
+    assert_eq!(
+        module.to_string(),
+        "function Main.abs 0
+// if (x < 0) {
+push argument 0
+push constant 0
+lt
+if-goto IF_TRUE0
+goto IF_FALSE0
+label IF_TRUE0
+// let x = -x;
+push argument 0
+neg
+pop argument 0
+label IF_FALSE0
+// return x;
+push argument 0
+return
+"
+    );
+
+    // The annotated output can be read back in by the VM parser.
+    assert_eq!(vm::parse(&module.to_string()).unwrap(), module.cmds);
+}
@@ -0,0 +1,775 @@
+// Checks the translator against the VM interpreter by generating random programs, running them
+// through both, and comparing the contents of memory afterwards. Any program which behaves
+// differently is shrunk to a smaller one which still does, to make the bug easier to find.
+
+use jackc::asm;
+use jackc::hack::Emulator;
+use jackc::vm::{
+    Command, Interpreter, Module, OptimizeFor, Optimizer, Segment, Strategy, Translator,
+};
+
+const CASES: u64 = 200;
+
+// The generated code keeps the stack well away from the region pointed to by THIS and THAT.
+const POINTER_BASE: u16 = 3000;
+const POINTER_SIZE: u16 = 20;
+
+// temp 6 is set once the program has finished, and temp 7 is used as a loop counter, so
+// neither is used by generated statements.
+const DONE: u16 = 11;
+const WRITABLE_TEMPS: u16 = 6;
+const STATICS: u16 = 10;
+// Recursive calls are only made while the static after the writable ones is above zero, and
+// each one counts it down, so that every program still terminates.
+const FUEL: u16 = STATICS;
+
+const MAX_TICKS: usize = 5_000_000;
+const MAX_STEPS: usize = 500_000;
+
+// Each program is translated with every one of these sets of options.
+type Configure = fn(&mut Translator);
+
+const CONFIGS: &[(&str, Configure)] = &[
+    ("the default settings", |_| {}),
+    ("the CachedTop strategy", |t| {
+        t.strategy(Strategy::CachedTop)
+    }),
+    ("tail calls", |t| t.tail_calls(true)),
+    ("calls optimised for speed", |t| {
+        t.optimize_for(OptimizeFor::Speed)
+    }),
+    ("static frames", |t| t.static_frames(true)),
+    // Each pair of options which change the code for calls and returns.
+    ("CachedTop and tail calls", |t| {
+        t.strategy(Strategy::CachedTop);
+        t.tail_calls(true);
+    }),
+    ("CachedTop and calls optimised for speed", |t| {
+        t.strategy(Strategy::CachedTop);
+        t.optimize_for(OptimizeFor::Speed);
+    }),
+    ("CachedTop and static frames", |t| {
+        t.strategy(Strategy::CachedTop);
+        t.static_frames(true);
+    }),
+    ("tail calls optimised for speed", |t| {
+        t.tail_calls(true);
+        t.optimize_for(OptimizeFor::Speed);
+    }),
+    ("tail calls and static frames", |t| {
+        t.tail_calls(true);
+        t.static_frames(true);
+    }),
+    ("static frames and calls optimised for speed", |t| {
+        t.static_frames(true);
+        t.optimize_for(OptimizeFor::Speed);
+    }),
+    ("all options", |t| {
+        t.strategy(Strategy::CachedTop);
+        t.tail_calls(true);
+        t.optimize_for(OptimizeFor::Speed);
+        t.static_frames(true);
+    }),
+];
+
+const BINARY_OPS: &[Command] = &[
+    Command::Add,
+    Command::Sub,
+    Command::And,
+    Command::Or,
+    Command::Eq,
+];
+
+const COMPARISONS: &[Command] = &[Command::Lt, Command::Gt];
+
+#[test]
+fn differential_test() {
+    check_random_programs(BINARY_OPS);
+}
+
+#[test]
+fn comparison_differential_test() {
+    check_random_programs(&[BINARY_OPS, COMPARISONS].concat());
+}
+
+fn check_random_programs(ops: &[Command]) {
+    for case in 0..CASES {
+        let seed = 0x5eed_0000 + case;
+        let program = Program::generate(&mut Rng(seed), ops);
+
+        if let Some(mismatch) = program.check() {
+            let (program, mismatch) = program.shrink(mismatch);
+            panic!(
+                "seed {:#x}: {}\n\nshrunk program:\n{}",
+                seed,
+                mismatch,
+                program.module()
+            );
+        }
+    }
+}
+
+// A xorshift generator, so that failures can be reproduced from their seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Const(u16),
+    Read(Segment, u16),
+    Unary(Command, Box<Expr>),
+    Binary(Command, Box<Expr>, Box<Expr>),
+    Call(usize, Vec<Expr>),
+}
+
+#[derive(Clone, Debug)]
+enum Stmt {
+    Write(Expr, Segment, u16),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Loop(u16, Vec<Stmt>),
+    // Calls the current function or an earlier one, if there's any fuel left, and writes the
+    // result to the given location.
+    Recurse(usize, Vec<Expr>, Segment, u16),
+}
+
+#[derive(Clone, Debug)]
+struct Function {
+    locals: u16,
+    body: Vec<Stmt>,
+    result: Expr,
+}
+
+// A program made up of functions which call the functions after them freely, but the current
+// function or those before it only as many times in total as the fuel allows, so that it's
+// guaranteed to terminate. The first function is called by top-level code with constant
+// arguments, and its result is stored in temp 0.
+#[derive(Clone, Debug)]
+struct Program {
+    functions: Vec<Function>,
+    args: Vec<u16>,
+    fuel: u16,
+}
+
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    ops: &'a [Command],
+    // The number of arguments taken by each function.
+    arities: Vec<u16>,
+    function: usize,
+    locals: u16,
+}
+
+impl Program {
+    fn generate(rng: &mut Rng, ops: &[Command]) -> Self {
+        let count = 1 + rng.below(4);
+        let arities = (0..count).map(|_| rng.below(4) as u16).collect::<Vec<_>>();
+        let args = (0..arities[0]).map(|_| constant(rng)).collect();
+        let fuel = rng.below(8) as u16;
+
+        let mut generator = Generator {
+            rng,
+            ops,
+            arities: arities.clone(),
+            function: 0,
+            locals: 0,
+        };
+
+        let functions = (0..count)
+            .map(|function| {
+                generator.function = function;
+                generator.locals = generator.rng.below(10) as u16;
+
+                let body = generator.stmts(3);
+                let result = generator.expr(3);
+
+                Function {
+                    locals: generator.locals,
+                    body,
+                    result,
+                }
+            })
+            .collect();
+
+        Program {
+            functions,
+            args,
+            fuel,
+        }
+    }
+
+    fn module(&self) -> Module {
+        let mut cmds = Vec::new();
+
+        // Statics are allocated in the order they're first used, so every one is read up front
+        // to keep them in the same place when the VM optimiser removes some of their uses.
+        for idx in 0..=FUEL {
+            cmds.push(Command::Push(Segment::Static, idx));
+            cmds.push(Command::Pop(Segment::Temp, 0));
+        }
+
+        cmds.push(Command::Push(Segment::Constant, self.fuel));
+        cmds.push(Command::Pop(Segment::Static, FUEL));
+
+        for arg in &self.args {
+            cmds.push(Command::Push(Segment::Constant, *arg));
+        }
+        cmds.extend(vec![
+            Command::Call("Main.f0".into(), self.args.len() as u16),
+            Command::Pop(Segment::Temp, 0),
+            Command::Push(Segment::Constant, 1),
+            Command::Pop(Segment::Temp, DONE - 5),
+            Command::Label("HALT".into()),
+            Command::Goto("HALT".into()),
+        ]);
+
+        for (i, func) in self.functions.iter().enumerate() {
+            let mut labels = 0;
+            let this = POINTER_BASE + 2 * POINTER_SIZE * i as u16;
+
+            cmds.extend(vec![
+                Command::Function(format!("Main.f{}", i), func.locals),
+                Command::Push(Segment::Constant, this),
+                Command::Pop(Segment::Pointer, 0),
+                Command::Push(Segment::Constant, this + POINTER_SIZE),
+                Command::Pop(Segment::Pointer, 1),
+            ]);
+            emit_stmts(&func.body, &mut labels, &mut cmds);
+            emit_expr(&func.result, &mut cmds);
+            cmds.push(Command::Return);
+        }
+
+        Module::new("Main".into(), cmds)
+    }
+
+    // Runs the program through both the interpreter and the emulator, describing the first
+    // difference found between them.
+    fn check(&self) -> Option<String> {
+        let modules = [self.module()];
+
+        let mut interpreter = Interpreter::new(&modules);
+        interpreter.init(None);
+        let mut steps = 0;
+        while interpreter.ram.get(DONE) == 0 {
+            if steps >= MAX_STEPS {
+                return Some("interpreter didn't finish".into());
+            }
+            if let Err(err) = interpreter.run(1000) {
+                return Some(format!("interpreter failed: {}", err));
+            }
+            steps += 1000;
+        }
+
+        for (name, configure) in CONFIGS {
+            for optimize in [false, true] {
+                if let Some(mismatch) = self.compare(&modules, &interpreter, *configure, optimize) {
+                    let optimized = if optimize {
+                        " and the asm optimiser"
+                    } else {
+                        ""
+                    };
+                    return Some(format!("with {}{}, {}", name, optimized, mismatch));
+                }
+            }
+        }
+
+        // The VM optimiser is checked on its own, with the default settings.
+        let mut optimized = modules.clone();
+        let mut optimizer = Optimizer::new();
+        optimizer.level(2);
+        for module in &mut optimized {
+            optimizer.optimize(module);
+        }
+        if let Some(mismatch) = self.compare(&optimized, &interpreter, CONFIGS[0].1, false) {
+            return Some(format!("with -O2, {}", mismatch));
+        }
+
+        None
+    }
+
+    fn compare(
+        &self,
+        modules: &[Module],
+        interpreter: &Interpreter,
+        configure: Configure,
+        optimize: bool,
+    ) -> Option<String> {
+        let mut translator = Translator::new(modules);
+        translator.init(None);
+        configure(&mut translator);
+        let insts = translator.translate().map(|insts| match optimize {
+            true => asm::optimize(insts),
+            false => insts,
+        });
+        let rom = match insts.map(|insts| asm::assemble(&insts)) {
+            Ok(Ok(rom)) => rom,
+            Ok(Err(err)) | Err(err) => return Some(format!("translation failed: {}", err)),
+        };
+
+        let mut emulator = Emulator::new(&rom);
+        let mut ticks = 0;
+        while emulator.ram.get(DONE) == 0 {
+            if ticks >= MAX_TICKS {
+                return Some("emulator didn't finish".into());
+            }
+            emulator.run(10_000);
+            ticks += 10_000;
+        }
+
+        let sp = interpreter.ram.get(0);
+        let pointers_end = POINTER_BASE + 2 * POINTER_SIZE * self.functions.len() as u16;
+        // R13 to R15 are scratch registers for the translator, so aren't compared.
+        let addrs = (0..13)
+            .chain(16..=16 + FUEL)
+            .chain(256..sp.max(emulator.ram.get(0)))
+            .chain(POINTER_BASE..pointers_end);
+
+        for addr in addrs {
+            let (expected, actual) = (interpreter.ram.get(addr), emulator.ram.get(addr));
+            if expected != actual {
+                return Some(format!(
+                    "RAM[{}] is {} in the interpreter but {} in the emulator",
+                    addr, expected as i16, actual as i16
+                ));
+            }
+        }
+
+        None
+    }
+
+    // Repeatedly makes the program smaller for as long as it still fails.
+    fn shrink(mut self, mut mismatch: String) -> (Self, String) {
+        'shrink: loop {
+            for candidate in self.candidates() {
+                if let Some(candidate_mismatch) = candidate.check() {
+                    self = candidate;
+                    mismatch = candidate_mismatch;
+                    continue 'shrink;
+                }
+            }
+
+            return (self, mismatch);
+        }
+    }
+
+    fn candidates(&self) -> Vec<Program> {
+        let mut candidates = Vec::new();
+
+        // The last function can only be called by earlier ones, other than by itself, so it
+        // can be removed as long as none of them call it.
+        let last = self.functions.len() - 1;
+        if last > 0 && !self.functions[..last].iter().any(|func| func.calls(last)) {
+            let mut program = self.clone();
+            program.functions.pop();
+            candidates.push(program);
+        }
+
+        if self.fuel > 0 {
+            let mut program = self.clone();
+            program.fuel = 0;
+            candidates.push(program);
+        }
+
+        for (i, arg) in self.args.iter().enumerate() {
+            if *arg != 0 {
+                let mut program = self.clone();
+                program.args[i] = 0;
+                candidates.push(program);
+            }
+        }
+
+        for (i, func) in self.functions.iter().enumerate() {
+            if func.locals > 0 && !func.uses_local(func.locals - 1) {
+                let mut program = self.clone();
+                program.functions[i].locals -= 1;
+                candidates.push(program);
+            }
+
+            for body in shrink_stmts(&func.body) {
+                let mut program = self.clone();
+                program.functions[i].body = body;
+                candidates.push(program);
+            }
+
+            for result in shrink_expr(&func.result) {
+                let mut program = self.clone();
+                program.functions[i].result = result;
+                candidates.push(program);
+            }
+        }
+
+        candidates
+    }
+}
+
+impl Function {
+    fn calls(&self, function: usize) -> bool {
+        self.body.iter().any(|stmt| stmt.calls(function)) || self.result.calls(function)
+    }
+
+    fn uses_local(&self, idx: u16) -> bool {
+        let reads = |expr: &Expr| expr.reads(Segment::Local, idx);
+        self.body
+            .iter()
+            .any(|stmt| stmt.writes_local(idx) || stmt.any_expr(&reads))
+            || reads(&self.result)
+    }
+}
+
+impl Stmt {
+    fn any_expr(&self, pred: &dyn Fn(&Expr) -> bool) -> bool {
+        match self {
+            Stmt::Write(expr, _, _) => pred(expr),
+            Stmt::If(cond, then, otherwise) => {
+                pred(cond)
+                    || then.iter().any(|stmt| stmt.any_expr(pred))
+                    || otherwise.iter().any(|stmt| stmt.any_expr(pred))
+            }
+            Stmt::Loop(_, body) => body.iter().any(|stmt| stmt.any_expr(pred)),
+            Stmt::Recurse(_, args, _, _) => args.iter().any(pred),
+        }
+    }
+
+    fn calls(&self, function: usize) -> bool {
+        match self {
+            Stmt::Recurse(callee, _, _, _) if *callee == function => true,
+            Stmt::If(_, then, otherwise) => then
+                .iter()
+                .chain(otherwise)
+                .any(|stmt| stmt.calls(function)),
+            Stmt::Loop(_, body) => body.iter().any(|stmt| stmt.calls(function)),
+            _ => self.any_expr(&|expr| expr.calls(function)),
+        }
+    }
+
+    fn writes_local(&self, idx: u16) -> bool {
+        match self {
+            Stmt::Write(_, segment, i) | Stmt::Recurse(_, _, segment, i) => {
+                *segment == Segment::Local && *i == idx
+            }
+            Stmt::If(_, then, otherwise) => then
+                .iter()
+                .chain(otherwise)
+                .any(|stmt| stmt.writes_local(idx)),
+            Stmt::Loop(_, body) => body.iter().any(|stmt| stmt.writes_local(idx)),
+        }
+    }
+}
+
+impl Expr {
+    fn calls(&self, function: usize) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Read(..) => false,
+            Expr::Unary(_, expr) => expr.calls(function),
+            Expr::Binary(_, lhs, rhs) => lhs.calls(function) || rhs.calls(function),
+            Expr::Call(callee, args) => {
+                *callee == function || args.iter().any(|arg| arg.calls(function))
+            }
+        }
+    }
+
+    fn reads(&self, segment: Segment, idx: u16) -> bool {
+        match self {
+            Expr::Const(_) => false,
+            Expr::Read(seg, i) => *seg == segment && *i == idx,
+            Expr::Unary(_, expr) => expr.reads(segment, idx),
+            Expr::Binary(_, lhs, rhs) => lhs.reads(segment, idx) || rhs.reads(segment, idx),
+            Expr::Call(_, args) => args.iter().any(|arg| arg.reads(segment, idx)),
+        }
+    }
+}
+
+impl Generator<'_> {
+    fn stmts(&mut self, depth: usize) -> Vec<Stmt> {
+        (0..self.rng.below(5)).map(|_| self.stmt(depth)).collect()
+    }
+
+    fn stmt(&mut self, depth: usize) -> Stmt {
+        match self.rng.below(10) {
+            0 | 1 if depth > 0 => {
+                Stmt::If(self.expr(2), self.stmts(depth - 1), self.stmts(depth - 1))
+            }
+            2 if depth > 0 => Stmt::Loop(1 + self.rng.below(3) as u16, self.stmts(depth - 1)),
+            3 => {
+                let callee = self.rng.below(self.function + 1);
+                let args = (0..self.arities[callee]).map(|_| self.expr(2)).collect();
+                let (segment, idx) = self.location(true);
+                Stmt::Recurse(callee, args, segment, idx)
+            }
+            _ => {
+                let (segment, idx) = self.location(true);
+                Stmt::Write(self.expr(3), segment, idx)
+            }
+        }
+    }
+
+    fn expr(&mut self, depth: usize) -> Expr {
+        let callees = self.function + 1..self.arities.len();
+
+        match self.rng.below(10) {
+            0 | 1 if depth > 0 => Expr::Unary(
+                self.rng.pick(&[Command::Neg, Command::Not]).clone(),
+                Box::new(self.expr(depth - 1)),
+            ),
+            2..=4 if depth > 0 => Expr::Binary(
+                self.rng.pick(self.ops).clone(),
+                Box::new(self.expr(depth - 1)),
+                Box::new(self.expr(depth - 1)),
+            ),
+            5 if depth > 0 && !callees.is_empty() => {
+                let callee = callees.start + self.rng.below(callees.len());
+                let args = (0..self.arities[callee])
+                    .map(|_| self.expr(depth - 1))
+                    .collect();
+                Expr::Call(callee, args)
+            }
+            6 | 7 => {
+                let (segment, idx) = self.location(false);
+                Expr::Read(segment, idx)
+            }
+            _ => Expr::Const(constant(self.rng)),
+        }
+    }
+
+    // Picks a memory location to read from or write to, covering each of the translator's
+    // special cases for small segment indices.
+    fn location(&mut self, write: bool) -> (Segment, u16) {
+        let args = self.arities[self.function];
+
+        loop {
+            let location = match self.rng.below(if write { 6 } else { 7 }) {
+                0 if self.locals > 0 => (Segment::Local, self.rng.below(self.locals as usize)),
+                1 if args > 0 => (Segment::Argument, self.rng.below(args as usize)),
+                2 => (Segment::This, self.rng.below(POINTER_SIZE as usize)),
+                3 => (Segment::That, self.rng.below(POINTER_SIZE as usize)),
+                4 => (Segment::Temp, self.rng.below(WRITABLE_TEMPS as usize)),
+                5 => (Segment::Static, self.rng.below(STATICS as usize)),
+                6 => (Segment::Pointer, self.rng.below(2)),
+                _ => continue,
+            };
+
+            return (location.0, location.1 as u16);
+        }
+    }
+}
+
+// Picks a constant, favouring those which the translator treats specially.
+fn constant(rng: &mut Rng) -> u16 {
+    match rng.below(4) {
+        0 => *rng.pick(&[0, 1, 2, 3, 0x7fff, 0x8000, 0x8001, 0xffff]),
+        1 => rng.below(16) as u16,
+        _ => rng.next() as u16,
+    }
+}
+
+fn emit_stmts(stmts: &[Stmt], labels: &mut usize, cmds: &mut Vec<Command>) {
+    for stmt in stmts {
+        let mut label = |name: &str| {
+            *labels += 1;
+            format!("{}{}", name, labels)
+        };
+
+        match stmt {
+            Stmt::Write(expr, segment, idx) => {
+                emit_expr(expr, cmds);
+                cmds.push(Command::Pop(*segment, *idx));
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let (then_label, end_label) = (label("THEN"), label("END"));
+
+                emit_expr(cond, cmds);
+                cmds.push(Command::IfGoto(then_label.clone()));
+                emit_stmts(otherwise, labels, cmds);
+                cmds.push(Command::Goto(end_label.clone()));
+                cmds.push(Command::Label(then_label));
+                emit_stmts(then, labels, cmds);
+                cmds.push(Command::Label(end_label));
+            }
+            Stmt::Loop(count, body) => {
+                let (start_label, end_label) = (label("LOOP"), label("DONE"));
+
+                // Loops count down in temp 7, which any nested loop leaves at zero, so nesting
+                // can only ever make the outer loop finish sooner.
+                cmds.extend(vec![
+                    Command::Push(Segment::Constant, *count),
+                    Command::Pop(Segment::Temp, 7),
+                    Command::Label(start_label.clone()),
+                    Command::Push(Segment::Temp, 7),
+                    Command::Push(Segment::Constant, 0),
+                    Command::Gt,
+                    Command::Not,
+                    Command::IfGoto(end_label.clone()),
+                ]);
+                emit_stmts(body, labels, cmds);
+                cmds.extend(vec![
+                    Command::Push(Segment::Temp, 7),
+                    Command::Push(Segment::Constant, 1),
+                    Command::Sub,
+                    Command::Pop(Segment::Temp, 7),
+                    Command::Goto(start_label),
+                    Command::Label(end_label),
+                ]);
+            }
+            Stmt::Recurse(callee, args, segment, idx) => {
+                let skip_label = label("SKIP");
+
+                cmds.extend(vec![
+                    Command::Push(Segment::Static, FUEL),
+                    Command::Push(Segment::Constant, 0),
+                    Command::Gt,
+                    Command::Not,
+                    Command::IfGoto(skip_label.clone()),
+                    Command::Push(Segment::Static, FUEL),
+                    Command::Push(Segment::Constant, 1),
+                    Command::Sub,
+                    Command::Pop(Segment::Static, FUEL),
+                ]);
+                emit_expr(&Expr::Call(*callee, args.clone()), cmds);
+                cmds.push(Command::Pop(*segment, *idx));
+                cmds.push(Command::Label(skip_label));
+            }
+        }
+    }
+}
+
+fn emit_expr(expr: &Expr, cmds: &mut Vec<Command>) {
+    match expr {
+        Expr::Const(n) => cmds.push(Command::Push(Segment::Constant, *n)),
+        Expr::Read(segment, idx) => cmds.push(Command::Push(*segment, *idx)),
+        Expr::Unary(op, expr) => {
+            emit_expr(expr, cmds);
+            cmds.push(op.clone());
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            emit_expr(lhs, cmds);
+            emit_expr(rhs, cmds);
+            cmds.push(op.clone());
+        }
+        Expr::Call(callee, args) => {
+            for arg in args {
+                emit_expr(arg, cmds);
+            }
+            cmds.push(Command::Call(
+                format!("Main.f{}", callee),
+                args.len() as u16,
+            ));
+        }
+    }
+}
+
+fn shrink_stmts(stmts: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut candidates = Vec::new();
+
+    for (i, stmt) in stmts.iter().enumerate() {
+        let splice = |replacement: &[Stmt]| {
+            let mut stmts = stmts.to_vec();
+            stmts.splice(i..=i, replacement.iter().cloned());
+            stmts
+        };
+
+        candidates.push(splice(&[]));
+
+        match stmt {
+            Stmt::Write(expr, segment, idx) => {
+                for expr in shrink_expr(expr) {
+                    candidates.push(splice(&[Stmt::Write(expr, *segment, *idx)]));
+                }
+            }
+            Stmt::If(cond, then, otherwise) => {
+                candidates.push(splice(then));
+                candidates.push(splice(otherwise));
+                for cond in shrink_expr(cond) {
+                    candidates.push(splice(&[Stmt::If(cond, then.clone(), otherwise.clone())]));
+                }
+                for then in shrink_stmts(then) {
+                    candidates.push(splice(&[Stmt::If(cond.clone(), then, otherwise.clone())]));
+                }
+                for otherwise in shrink_stmts(otherwise) {
+                    candidates.push(splice(&[Stmt::If(cond.clone(), then.clone(), otherwise)]));
+                }
+            }
+            Stmt::Recurse(callee, args, segment, idx) => {
+                for (i, arg) in args.iter().enumerate() {
+                    for arg in shrink_expr(arg) {
+                        let mut args = args.clone();
+                        args[i] = arg;
+                        candidates.push(splice(&[Stmt::Recurse(*callee, args, *segment, *idx)]));
+                    }
+                }
+            }
+            Stmt::Loop(count, body) => {
+                candidates.push(splice(body));
+                if *count > 1 {
+                    candidates.push(splice(&[Stmt::Loop(1, body.clone())]));
+                }
+                for body in shrink_stmts(body) {
+                    candidates.push(splice(&[Stmt::Loop(*count, body)]));
+                }
+            }
+        }
+    }
+
+    candidates
+}
+
+fn shrink_expr(expr: &Expr) -> Vec<Expr> {
+    let mut candidates = Vec::new();
+
+    match expr {
+        Expr::Const(0) => {}
+        Expr::Const(n) => {
+            candidates.push(Expr::Const(0));
+            if *n > 1 {
+                candidates.push(Expr::Const(n / 2));
+            }
+        }
+        Expr::Read(..) => candidates.push(Expr::Const(0)),
+        Expr::Unary(op, inner) => {
+            candidates.push(Expr::Const(0));
+            candidates.push((**inner).clone());
+            for inner in shrink_expr(inner) {
+                candidates.push(Expr::Unary(op.clone(), Box::new(inner)));
+            }
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            candidates.push(Expr::Const(0));
+            candidates.push((**lhs).clone());
+            candidates.push((**rhs).clone());
+            for lhs in shrink_expr(lhs) {
+                candidates.push(Expr::Binary(op.clone(), Box::new(lhs), rhs.clone()));
+            }
+            for rhs in shrink_expr(rhs) {
+                candidates.push(Expr::Binary(op.clone(), lhs.clone(), Box::new(rhs)));
+            }
+        }
+        Expr::Call(callee, args) => {
+            candidates.push(Expr::Const(0));
+            for (i, arg) in args.iter().enumerate() {
+                for arg in shrink_expr(arg) {
+                    let mut args = args.clone();
+                    args[i] = arg;
+                    candidates.push(Expr::Call(*callee, args));
+                }
+            }
+        }
+    }
+
+    candidates
+}
@@ -0,0 +1,148 @@
+use jackc::jack::*;
+
+mod helpers;
+
+const SOURCE: &str = "
+// Not a doc comment.
+/**
+ * A counter.
+ *
+ * Counts things.
+ */
+class Counter {
+    /** The current count. */
+    field int count;
+
+    /* Not a doc comment either. */
+    static int total;
+
+    /** Creates a counter starting from `start`. */
+    constructor Counter new(int start) {
+        let count = start;
+        return this;
+    }
+
+    /** Adds `n` to the count. */
+    /* This comment separates the doc comment from the method. */
+    method void add(int n) {
+        let count = count + n;
+        return;
+    }
+
+    /** Returns the larger of `a` and `b`. */
+    function int max(int a, Array b) {
+        return a;
+    }
+}
+";
+
+#[test]
+fn class_doc_test() {
+    let doc = ClassDoc::parse(SOURCE).unwrap();
+
+    let docs = |members: &[MemberDoc]| {
+        members
+            .iter()
+            .map(|member| member.doc.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(doc.name, "Counter");
+    assert_eq!(doc.doc.as_deref(), Some("A counter.\n\nCounts things."));
+    assert_eq!(
+        docs(&doc.vars),
+        vec![Some("The current count.".to_owned()), None]
+    );
+    assert_eq!(
+        docs(&doc.subs),
+        vec![
+            Some("Creates a counter starting from `start`.".to_owned()),
+            None,
+            Some("Returns the larger of `a` and `b`.".to_owned()),
+        ]
+    );
+    assert_eq!(doc.subs[1].kind, Keyword::Method);
+    assert_eq!(doc.subs[1].signature, "method void add(int n)");
+}
+
+#[test]
+fn square_doc_test() {
+    let source = helpers::read_test_file("Square.jack");
+    let doc = ClassDoc::parse(&source).unwrap();
+
+    assert_eq!(doc.doc.as_deref(), Some("Implements a graphical square."));
+    assert_eq!(doc.vars.len(), 2);
+    assert!(doc.vars.iter().all(|var| var.doc.is_none()));
+    assert_eq!(doc.subs.len(), 10);
+    assert!(doc.subs.iter().all(|sub| sub.doc.is_some()));
+}
+
+#[test]
+fn static_init_doc_test() {
+    let source = helpers::read_test_file("StaticInit.jack");
+    let doc = ClassDoc::parse(&source).unwrap();
+
+    // Initialisers aren't part of the signature.
+    let signatures = doc
+        .vars
+        .iter()
+        .map(|var| var.signature.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        signatures,
+        &["static int speed, size", "static Array trail", "field int x"]
+    );
+}
+
+#[test]
+fn markdown_test() {
+    let doc = ClassDoc::parse(SOURCE).unwrap();
+
+    assert_eq!(
+        docs_to_markdown(&[doc]),
+        "# Counter
+
+A counter.
+
+Counts things.
+
+## Fields
+
+### `field int count`
+
+The current count.
+
+### `static int total`
+
+## Constructors
+
+### `constructor Counter new(int start)`
+
+Creates a counter starting from `start`.
+
+## Methods
+
+### `method void add(int n)`
+
+## Functions
+
+### `function int max(int a, Array b)`
+
+Returns the larger of `a` and `b`.
+"
+    );
+}
+
+#[test]
+fn html_test() {
+    let source = "/** Compares `a < b` & more. */ class Cmp { function void f() { return; } }";
+    let doc = ClassDoc::parse(source).unwrap();
+    let html = docs_to_html(&[doc]);
+
+    assert!(html.contains("<li><a href=\"#Cmp\">Cmp</a></li>"));
+    assert!(html
+        .contains("<section id=\"Cmp\">\n<h1>Cmp</h1>\n<p>Compares `a &lt; b` &amp; more.</p>\n"));
+    assert!(
+        html.contains("<h2>Functions</h2>\n<dl>\n<dt><code>function void f()</code></dt>\n</dl>\n")
+    );
+}
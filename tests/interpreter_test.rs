@@ -0,0 +1,273 @@
+use anyhow::anyhow;
+use jackc::asm::MemoryLayout;
+use jackc::jack::{Compiler, Parser, Tokenizer};
+use jackc::vm::{self, Interpreter, Module};
+use std::cell::RefCell;
+
+fn compile(source: &str) -> Module {
+    let tokens = Tokenizer::new(source).tokenize().unwrap();
+    let class = Parser::new(tokens).parse().unwrap();
+    Compiler::new(&class).compile().unwrap()
+}
+
+fn module(name: &str, source: &str) -> Module {
+    Module::new(name.to_owned(), vm::parse(source).unwrap())
+}
+
+#[test]
+fn call_test() {
+    let modules = [compile(
+        "class Main {
+             function int fib(int n) {
+                 if (n < 2) {
+                     return n;
+                 }
+                 return Main.fib(n - 1) + Main.fib(n - 2);
+             }
+
+             function int square(int n) {
+                 return n * n;
+             }
+         }",
+    )];
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Math.multiply", |_, args| {
+        Ok((args[0] as i16).wrapping_mul(args[1] as i16) as u16)
+    });
+
+    assert_eq!(interpreter.call("Main.fib", &[10]).unwrap(), 55);
+    assert_eq!(interpreter.call("Main.fib", &[1]).unwrap(), 1);
+    assert_eq!(
+        interpreter.call("Main.square", &[-12i16 as u16]).unwrap(),
+        144
+    );
+    assert!(interpreter.call_stack().is_empty());
+}
+
+#[test]
+fn run_test() {
+    let mut modules = [
+        compile(
+            "class Main {
+                 static int total;
+                 static Array squares = Array.new(3);
+
+                 function void main() {
+                     var int i;
+                     while (i < 3) {
+                         let squares[i] = i * i;
+                         let total = total + squares[i];
+                         let i = i + 1;
+                     }
+                     do Output.printInt(total);
+                     do Output.printInt(-1 < 1);
+                     do Output.printInt(32767 > -32767);
+                     return;
+                 }
+             }",
+        ),
+        module(
+            "Sys",
+            "function Sys.init 0
+             call Main.main 0
+             pop temp 0
+             label HALT
+             goto HALT",
+        ),
+    ];
+    vm::link_static_inits(&mut modules).unwrap();
+
+    let output = RefCell::new(Vec::new());
+    let heap = RefCell::new(MemoryLayout::new().heap_base);
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Array.new", |_, args| {
+        let mut heap = heap.borrow_mut();
+        let addr = *heap;
+        *heap += args[0];
+        Ok(addr)
+    });
+    interpreter.native("Math.multiply", |_, args| Ok(args[0].wrapping_mul(args[1])));
+    interpreter.native("Output.printInt", |_, args| {
+        output.borrow_mut().push(args[0] as i16);
+        Ok(0)
+    });
+
+    assert!(interpreter.run(1000).unwrap());
+    assert_eq!(interpreter.call_stack(), &["Sys.init"]);
+    drop(interpreter);
+
+    assert_eq!(output.into_inner(), &[5, -1, -1]);
+}
+
+#[test]
+fn init_return_test() {
+    let modules = [module(
+        "Sys",
+        "function Sys.init 0
+         push constant 7
+         pop temp 0
+         push constant 0
+         return",
+    )];
+
+    let mut interpreter = Interpreter::new(&modules);
+    assert!(!interpreter.run(100).unwrap());
+    assert_eq!(interpreter.ram.get(5), 7);
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.init(None);
+    assert!(!interpreter.run(100).unwrap());
+    assert_eq!(interpreter.ram.get(5), 7);
+}
+
+#[test]
+fn stack_trace_test() {
+    let modules = [
+        compile(
+            "class Main {
+                 function void main() {
+                     do Foo.bar(3);
+                     return;
+                 }
+             }",
+        ),
+        compile(
+            "class Foo {
+                 function void bar(int code) {
+                     do Sys.error(code);
+                     return;
+                 }
+
+                 function void baz() {
+                     do Foo.missing();
+                     return;
+                 }
+             }",
+        ),
+    ];
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Sys.error", |_, args| {
+        Err(anyhow!("error code {}", args[0]))
+    });
+
+    assert_eq!(
+        interpreter.call("Main.main", &[]).unwrap_err().to_string(),
+        "in Main.main > Foo.bar > Sys.error: error code 3"
+    );
+    assert!(interpreter.call_stack().is_empty());
+
+    assert_eq!(
+        interpreter.call("Foo.baz", &[]).unwrap_err().to_string(),
+        "in Foo.baz: call to undefined function `Foo.missing`"
+    );
+}
+
+// Arrays are allocated the way the book's `Memory.alloc` does it, with the size of the block,
+// including the extra word, stored just before the array.
+#[test]
+fn bounds_check_test() {
+    let tokens = Tokenizer::new(
+        "class Main {
+             function int get(int i) {
+                 var Array a;
+                 let a = Array.new(3);
+                 return a[i];
+             }
+         }",
+    )
+    .tokenize()
+    .unwrap();
+    let class = Parser::new(tokens).parse().unwrap();
+    let mut compiler = Compiler::new(&class);
+    compiler.debug_checks(true);
+    let modules = [compiler.compile().unwrap()];
+
+    let heap = RefCell::new(MemoryLayout::new().heap_base);
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Array.new", |ram, args| {
+        let mut heap = heap.borrow_mut();
+        ram.set(*heap, args[0] + 1);
+        let addr = *heap + 1;
+        *heap += args[0] + 1;
+        Ok(addr)
+    });
+    interpreter.native("Sys.error", |_, args| {
+        Err(anyhow!("error code {}", args[0]))
+    });
+
+    // Each failed call is abandoned, leaving the interpreter ready for the next one.
+    assert_eq!(interpreter.call("Main.get", &[2]).unwrap(), 0);
+    for i in [3, -1i16 as u16, 32767] {
+        assert_eq!(
+            interpreter.call("Main.get", &[i]).unwrap_err().to_string(),
+            "in Main.get > Sys.error: error code 102"
+        );
+        assert!(interpreter.call_stack().is_empty());
+    }
+    assert_eq!(interpreter.call("Main.get", &[1]).unwrap(), 0);
+}
+
+#[test]
+fn native_error_test() {
+    let modules = [module(
+        "Sys",
+        "function Sys.init 0
+         push constant 7
+         call Sys.error 1
+         pop temp 0
+         label HALT
+         goto HALT",
+    )];
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.native("Sys.error", |_, args| {
+        Err(anyhow!("error code {}", args[0]))
+    });
+
+    assert_eq!(
+        interpreter.run(100).unwrap_err().to_string(),
+        "in Sys.init > Sys.error: error code 7"
+    );
+    // The native isn't running any more, so only its caller is left on the call stack.
+    assert_eq!(interpreter.call_stack(), &["Sys.init"]);
+}
+
+#[test]
+fn step_limit_test() {
+    let modules = [module(
+        "Main",
+        "function Main.loop 0
+         label LOOP
+         goto LOOP",
+    )];
+
+    let mut interpreter = Interpreter::new(&modules);
+    interpreter.step_limit(50);
+
+    assert_eq!(
+        interpreter.call("Main.loop", &[]).unwrap_err().to_string(),
+        "in Main.loop: gave up after running 50 commands"
+    );
+}
+
+#[test]
+fn memory_error_test() {
+    let modules = [module(
+        "Main",
+        "function Main.main 0
+         push constant 16384
+         pop pointer 1
+         push that 0
+         return",
+    )];
+
+    let mut interpreter = Interpreter::new(&modules);
+
+    assert_eq!(
+        interpreter.call("Main.main", &[]).unwrap_err().to_string(),
+        "in Main.main: read from RAM[16384], which is out of range"
+    );
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "serde")]
+
+use jackc::asm::{Dest, Instruction, Load};
+use jackc::jack::*;
+use jackc::vm;
+use serde_json::json;
+
+#[test]
+fn tokens_json_test() {
+    let tokens = Tokenizer::new("let x = \"hi\";").tokenize().unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&tokens).unwrap(),
+        json!([
+            { "Keyword": "Let" },
+            { "Identifier": "x" },
+            { "Symbol": "=" },
+            { "StrConst": "hi" },
+            { "Symbol": ";" },
+        ])
+    );
+}
+
+#[test]
+fn class_json_test() {
+    let source = "class Main { static int x; function void main() { return; } }";
+    let tokens = Tokenizer::new(source).tokenize().unwrap();
+    let class = Parser::new(tokens).parse().unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&class).unwrap(),
+        json!({
+            "name": "Main",
+            "vars": [{
+                "kind": "Static",
+                "typ": "Int",
+                "names": ["x"],
+                "inits": [null],
+            }],
+            "subs": [{
+                "kind": "Function",
+                "typ": "Void",
+                "name": "main",
+                "params": [],
+                "body": {
+                    "vars": [],
+                    "statements": [{ "Return": null }],
+                },
+            }],
+        })
+    );
+}
+
+#[test]
+fn module_json_test() {
+    let module = vm::Module::new(
+        "Main".into(),
+        vec![
+            vm::Command::Function("Main.main".into(), 0),
+            vm::Command::Push(vm::Segment::Constant, 7),
+            vm::Command::Return,
+        ],
+    );
+
+    assert_eq!(
+        serde_json::to_value(&module).unwrap(),
+        json!({
+            "name": "Main",
+            "cmds": [
+                { "Function": ["Main.main", 0] },
+                { "Push": ["Constant", 7] },
+                "Return",
+            ],
+        })
+    );
+}
+
+#[test]
+fn instruction_json_test() {
+    let insts = vec![
+        Instruction::A(Load::Symbol("SP".into())),
+        Instruction::C(Some(Dest::D), "M".parse().unwrap(), None),
+        Instruction::Label("LOOP".into()),
+    ];
+
+    assert_eq!(
+        serde_json::to_value(&insts).unwrap(),
+        json!([
+            { "A": { "Symbol": "SP" } },
+            { "C": ["D", "M", null] },
+            { "Label": "LOOP" },
+        ])
+    );
+}
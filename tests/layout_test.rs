@@ -0,0 +1,88 @@
+use jackc::asm::{self, Instruction, MemoryLayout};
+
+#[test]
+fn parse_test() {
+    assert_eq!("".parse::<MemoryLayout>().unwrap(), MemoryLayout::new());
+
+    let layout = "stack=0x400, heap=4096,statics=16..1024,scratch=13"
+        .parse::<MemoryLayout>()
+        .unwrap();
+    assert_eq!(layout.stack_base, 1024);
+    assert_eq!(layout.heap_base, 4096);
+    assert_eq!(layout.statics, 16..1024);
+    assert_eq!(layout.scratch_registers(), 13..16);
+    assert_eq!(layout.screen, 0x4000);
+
+    let error = |s: &str| s.parse::<MemoryLayout>().unwrap_err().to_string();
+    assert_eq!(error("stack"), "expected `key=value`, found `stack`");
+    assert_eq!(error("stack=-1"), "`-1` is not a valid address");
+    assert_eq!(error("statics=16"), "expected `start..end`, found `16`");
+    assert_eq!(error("code=0"), "unknown memory layout setting `code`");
+}
+
+#[test]
+fn validate_test() {
+    let error = |s: &str| s.parse::<MemoryLayout>().unwrap_err().to_string();
+
+    assert_eq!(
+        error("stack=2048"),
+        "the stack base (2048) must be below the heap base (2048)"
+    );
+    assert_eq!(
+        error("heap=0x5000,screen=0x5000"),
+        "the heap base (20480), screen (20480) and keyboard (24576) must be in ascending order"
+    );
+    assert_eq!(
+        error("scratch=10"),
+        "the registers at RAM[0..13] overlap the scratch registers at RAM[10..13]"
+    );
+    assert_eq!(
+        error("statics=16..300"),
+        "the static area at RAM[16..300] overlap the stack at RAM[256..2048]"
+    );
+    assert_eq!(
+        error("scratch=300,statics=16..256"),
+        "the scratch registers at RAM[300..303] overlap the stack at RAM[256..2048]"
+    );
+}
+
+#[test]
+fn assemble_test() {
+    let layout = "heap=3000,statics=20..22,screen=0x5000"
+        .parse::<MemoryLayout>()
+        .unwrap();
+    let prog = vec![
+        Instruction::A("HEAP_BASE".into()),
+        Instruction::A("SCREEN".into()),
+        Instruction::A("Main.0".into()),
+        Instruction::A("Main.1".into()),
+        Instruction::A("Main.0".into()),
+    ];
+
+    assert_eq!(
+        asm::assemble_with_layout(&prog, &layout).unwrap(),
+        &[3000, 0x5000, 20, 21, 20]
+    );
+    assert_eq!(asm::assemble(&prog).unwrap(), &[2048, 0x4000, 16, 17, 16]);
+
+    let mut prog = prog;
+    prog.push(Instruction::A("Main.2".into()));
+    assert_eq!(
+        asm::assemble_with_layout(&prog, &layout)
+            .unwrap_err()
+            .to_string(),
+        "no room for static variable `Main.2`, as only 2 fit in RAM[20..22]"
+    );
+
+    // Layouts built without parsing are checked too.
+    let layout = MemoryLayout {
+        stack_base: 3000,
+        ..layout
+    };
+    assert_eq!(
+        asm::assemble_with_layout(&prog, &layout)
+            .unwrap_err()
+            .to_string(),
+        "the stack base (3000) must be below the heap base (3000)"
+    );
+}
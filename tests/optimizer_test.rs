@@ -0,0 +1,361 @@
+use jackc::asm;
+use jackc::hack::Emulator;
+use jackc::vm::{self, Module, Optimizer, Pass, Translator};
+
+fn module(source: &str) -> Module {
+    Module::new("Main".to_owned(), vm::parse(source).unwrap())
+}
+
+fn optimize(passes: &[Pass], source: &str) -> String {
+    let mut module = module(source);
+    let mut optimizer = Optimizer::new();
+    optimizer.passes(passes);
+    optimizer.optimize(&mut module);
+    module.to_string()
+}
+
+fn lines(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| format!("{}\n", line.trim()))
+        .collect()
+}
+
+#[test]
+fn push_pop_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::PushPop],
+            "push local 0
+             pop local 0
+             push local 0
+             pop local 1
+             push constant 3
+             pop temp 0",
+        ),
+        lines(
+            "push local 0
+             pop local 1
+             push constant 3
+             pop temp 0"
+        )
+    );
+}
+
+#[test]
+fn constant_condition_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::ConstantCondition],
+            "label A
+             push constant 0
+             not
+             if-goto A
+             push constant 0
+             if-goto A
+             push constant 1
+             neg
+             if-goto A
+             push local 0
+             not
+             if-goto A",
+        ),
+        lines(
+            "label A
+             goto A
+             goto A
+             push local 0
+             not
+             if-goto A"
+        )
+    );
+}
+
+#[test]
+fn inverted_jump_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::InvertedJump],
+            "push local 0
+             push local 1
+             lt
+             not
+             if-goto THEN
+             goto ELSE
+             label THEN
+             push constant 1
+             label ELSE",
+        ),
+        lines(
+            "push local 0
+             push local 1
+             lt
+             if-goto ELSE
+             label THEN
+             push constant 1
+             label ELSE"
+        )
+    );
+
+    // `not` is bitwise, so the condition could be non-zero both before and after it.
+    let source = "push local 0
+                  not
+                  if-goto THEN
+                  goto ELSE
+                  label THEN
+                  push constant 1
+                  label ELSE";
+    assert_eq!(optimize(&[Pass::InvertedJump], source), lines(source));
+}
+
+#[test]
+fn inverted_jump_non_boolean_test() {
+    // As !5 is -6, which is also true, THEN is reached.
+    let source = "function Sys.init 0
+                  push constant 5
+                  pop local 0
+                  push local 0
+                  not
+                  if-goto THEN
+                  goto ELSE
+                  label THEN
+                  push constant 1
+                  pop temp 0
+                  label ELSE
+                  label HALT
+                  goto HALT";
+
+    let mut module = module(source);
+    let mut optimizer = Optimizer::new();
+    optimizer.level(2);
+    optimizer.optimize(&mut module);
+
+    let modules = [module];
+    let rom = asm::assemble(&Translator::new(&modules).translate().unwrap()).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(1000);
+
+    assert_eq!(emulator.ram.get(5), 1);
+}
+
+#[test]
+fn jump_to_next_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::JumpToNext],
+            "goto A
+             label B
+             label A
+             goto B
+             push constant 0
+             label B",
+        ),
+        lines(
+            "label B
+             label A
+             goto B
+             push constant 0
+             label B"
+        )
+    );
+}
+
+#[test]
+fn thread_jumps_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::ThreadJumps],
+            "if-goto A
+             goto B
+             label A
+             goto B
+             label B
+             label C
+             goto END
+             label END
+             push constant 0
+             label LOOP
+             goto LOOP",
+        ),
+        lines(
+            "if-goto END
+             goto END
+             label A
+             goto END
+             label B
+             label C
+             goto END
+             label END
+             push constant 0
+             label LOOP
+             goto LOOP"
+        )
+    );
+}
+
+#[test]
+fn prune_labels_test() {
+    assert_eq!(
+        optimize(
+            &[Pass::PruneLabels],
+            "function Main.a 0
+             label A
+             label B
+             goto A
+             function Main.b 0
+             label A
+             return",
+        ),
+        lines(
+            "function Main.a 0
+             label A
+             goto A
+             function Main.b 0
+             return"
+        )
+    );
+}
+
+#[test]
+fn level_test() {
+    let source = "function Main.main 0
+                  push local 0
+                  push constant 0
+                  eq
+                  not
+                  if-goto THEN
+                  goto ELSE
+                  label THEN
+                  push local 1
+                  pop local 1
+                  goto ELSE
+                  label ELSE
+                  push constant 0
+                  return";
+
+    let at_level = |level| {
+        let mut module = module(source);
+        let mut optimizer = Optimizer::new();
+        optimizer.level(level);
+        optimizer.optimize(&mut module);
+        module.to_string()
+    };
+
+    assert_eq!(at_level(0), lines(source));
+    assert_eq!(
+        at_level(1),
+        lines(
+            "function Main.main 0
+             push local 0
+             push constant 0
+             eq
+             not
+             if-goto THEN
+             label THEN
+             label ELSE
+             push constant 0
+             return"
+        )
+    );
+    assert_eq!(
+        at_level(2),
+        lines(
+            "function Main.main 0
+             push local 0
+             push constant 0
+             eq
+             if-goto ELSE
+             label ELSE
+             push constant 0
+             return"
+        )
+    );
+}
+
+#[test]
+fn comments_test() {
+    let mut module = module(
+        "push local 0
+         pop local 0
+         push constant 1
+         goto A
+         label A",
+    );
+    module.comments = vec![(0, "let x = x;".into()), (3, "goto".into())];
+
+    let mut optimizer = Optimizer::new();
+    optimizer.level(2);
+    optimizer.optimize(&mut module);
+
+    assert_eq!(
+        module.to_string(),
+        "// let x = x;\npush constant 1\n// goto\n"
+    );
+}
+
+#[test]
+fn equivalence_test() {
+    // Sums the odd numbers from 1 to 10 into temp 0.
+    let source = "function Sys.init 2
+                  push constant 10
+                  pop local 0
+                  label LOOP
+                  push local 0
+                  push constant 0
+                  eq
+                  not
+                  not
+                  if-goto END
+                  push local 0
+                  push constant 1
+                  and
+                  push constant 0
+                  eq
+                  not
+                  if-goto ADD
+                  goto NEXT
+                  label ADD
+                  push local 1
+                  push local 0
+                  add
+                  pop local 1
+                  goto NEXT
+                  label NEXT
+                  push local 0
+                  push constant 1
+                  sub
+                  pop local 0
+                  goto CONTINUE
+                  label CONTINUE
+                  push constant 0
+                  not
+                  if-goto LOOP
+                  label END
+                  push local 1
+                  pop temp 0
+                  label HALT
+                  goto HALT";
+
+    let run = |level| {
+        let mut module = module(source);
+        let mut optimizer = Optimizer::new();
+        optimizer.level(level);
+        optimizer.optimize(&mut module);
+
+        let modules = [module];
+        let mut translator = Translator::new(&modules);
+        translator.init(Some("Sys.init".into()));
+        let rom = asm::assemble(&translator.translate().unwrap()).unwrap();
+
+        let mut emulator = Emulator::new(&rom);
+        emulator.run(5000);
+        (emulator.ram.get(5), rom.len())
+    };
+
+    let (unoptimized, unoptimized_len) = run(0);
+    let (optimized, optimized_len) = run(2);
+
+    assert_eq!(unoptimized, 25);
+    assert_eq!(optimized, 25);
+    assert!(optimized_len < unoptimized_len);
+}
@@ -167,6 +167,7 @@ fn square_main_jack_test() {
                 kind: ClassVarKind::Static,
                 typ: VarType::Boolean,
                 names: vec!["test".into()],
+                inits: vec![None],
             }],
             subs: vec![
                 Subroutine {
@@ -310,11 +311,13 @@ fn square_jack_test() {
                     kind: ClassVarKind::Field,
                     typ: VarType::Int,
                     names: vec!["x".into(), "y".into()],
+                    inits: vec![None, None],
                 },
                 ClassVars {
                     kind: ClassVarKind::Field,
                     typ: VarType::Int,
                     names: vec!["size".into()],
+                    inits: vec![None],
                 }
             ],
             subs: vec![
@@ -934,11 +937,13 @@ fn square_game_jack_test() {
                     kind: ClassVarKind::Field,
                     typ: VarType::ClassName("Square".into()),
                     names: vec!["square".into()],
+                    inits: vec![None],
                 },
                 ClassVars {
                     kind: ClassVarKind::Field,
                     typ: VarType::Int,
                     names: vec!["direction".into()],
+                    inits: vec![None],
                 }
             ],
             subs: vec![
@@ -1247,3 +1252,127 @@ fn square_game_jack_test() {
         }
     );
 }
+
+#[test]
+fn static_init_jack_test() {
+    let source = helpers::read_test_file("StaticInit.jack");
+    let tokens = Tokenizer::new(&source).tokenize().expect("tokenizer error");
+    let mut parser = Parser::new(tokens);
+
+    assert_eq!(
+        parser.parse().unwrap(),
+        Class {
+            name: "Ball".into(),
+            vars: vec![
+                ClassVars {
+                    kind: ClassVarKind::Static,
+                    typ: VarType::Int,
+                    names: vec!["speed".into(), "size".into()],
+                    inits: vec![Some(Expr::Term(Term::IntConst(3))), None],
+                },
+                ClassVars {
+                    kind: ClassVarKind::Static,
+                    typ: VarType::ClassName("Array".into()),
+                    names: vec!["trail".into()],
+                    inits: vec![Some(Expr::Term(Term::SubroutineCall(SubroutineCall {
+                        receiver: Some("Array".into()),
+                        subroutine: "new".into(),
+                        args: vec![Expr::Binary(
+                            BinaryOp::Multiply,
+                            Term::Var("speed".into()),
+                            Box::new(Expr::Term(Term::IntConst(2))),
+                        )],
+                    })))],
+                },
+                ClassVars {
+                    kind: ClassVarKind::Field,
+                    typ: VarType::Int,
+                    names: vec!["x".into()],
+                    inits: vec![None],
+                },
+            ],
+            subs: vec![Subroutine {
+                kind: SubroutineKind::Function,
+                typ: SubroutineType::NonVoid(VarType::Int),
+                name: "speed".into(),
+                params: vec![],
+                body: SubroutineBody {
+                    vars: vec![],
+                    statements: vec![Statement::Return(Some(Expr::Term(Term::Var(
+                        "speed".into()
+                    ))))],
+                },
+            }],
+        }
+    );
+}
+
+#[test]
+fn field_init_error_test() {
+    let source = "class Main { field int x = 1; }";
+    let tokens = Tokenizer::new(source).tokenize().expect("tokenizer error");
+
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn display_round_trip_test() {
+    let files = &[
+        "ArrayTest.jack",
+        "Average.jack",
+        "Bat.jack",
+        "ConvertToBin.jack",
+        "DebugChecks.jack",
+        "Seven.jack",
+        "Square.jack",
+        "SquareGame.jack",
+        "SquareMain.jack",
+        "StaticInit.jack",
+        "Unformatted.jack",
+    ];
+
+    for filename in files {
+        let source = helpers::read_test_file(filename);
+        let tokens = Tokenizer::new(&source).tokenize().expect("tokenizer error");
+        let class = Parser::new(tokens).parse().expect("parsing error");
+
+        let printed = class.to_string();
+        let tokens = Tokenizer::new(&printed)
+            .tokenize()
+            .expect("tokenizer error");
+        let reparsed = Parser::new(tokens).parse().expect("parsing error");
+
+        assert_eq!(reparsed, class, "{} did not round-trip", filename);
+    }
+}
+
+#[test]
+fn display_test() {
+    let source = "class Main { static int a = 1, b; field Array c;
+        method void f(int x, char y) { var int i; let c[x] = -(i + ~y) * 2;
+        if (x) { while (true) { do g(); } } else { return; } return null; } }";
+    let tokens = Tokenizer::new(source).tokenize().expect("tokenizer error");
+    let class = Parser::new(tokens).parse().expect("parsing error");
+
+    assert_eq!(
+        class.to_string(),
+        "class Main {
+    static int a = 1, b;
+    field Array c;
+
+    method void f(int x, char y) {
+        var int i;
+        let c[x] = -(i + ~y) * 2;
+        if (x) {
+            while (true) {
+                do g();
+            }
+        } else {
+            return;
+        }
+        return null;
+    }
+}
+"
+    );
+}
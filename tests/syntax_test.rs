@@ -0,0 +1,90 @@
+use jackc::jack::*;
+
+mod helpers;
+
+const TEST_FILES: &[&str] = &[
+    "ArrayTest.jack",
+    "Average.jack",
+    "Bat.jack",
+    "ConvertToBin.jack",
+    "Seven.jack",
+    "Square.jack",
+    "SquareGame.jack",
+    "SquareMain.jack",
+    "StaticInit.jack",
+    "Unformatted.jack",
+];
+
+fn parse_tree(source: &str) -> SyntaxTree {
+    let (tokens, trailing) = Tokenizer::new(source)
+        .tokenize_with_trivia()
+        .expect("tokenizer error");
+
+    SyntaxParser::new(tokens, trailing)
+        .parse()
+        .expect("parsing error")
+}
+
+#[test]
+fn lossless_round_trip_test() {
+    for filename in TEST_FILES {
+        let source = helpers::read_test_file(filename);
+        let tree = parse_tree(&source);
+
+        assert_eq!(tree.to_string(), source, "{} did not round-trip", filename);
+    }
+}
+
+#[test]
+fn syntax_tree_test() {
+    let tree = parse_tree("class Main { // comment\n  function void main() { return -x; } }\n");
+
+    assert_eq!(tree.root.kind, SyntaxKind::Class);
+    assert_eq!(tree.trailing, vec![Trivia::Whitespace("\n".into())]);
+
+    let texts: Vec<_> = tree.root.tokens().iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        vec![
+            "class", "Main", "{", "function", "void", "main", "(", ")", "{", "return", "-", "x",
+            ";", "}", "}"
+        ]
+    );
+
+    let sub = match &tree.root.children[3] {
+        SyntaxElement::Node(node) => node,
+        other => panic!("expected a subroutine, found {:?}", other),
+    };
+
+    assert_eq!(sub.kind, SyntaxKind::SubroutineDec);
+    assert_eq!(
+        sub.tokens()[0].leading,
+        vec![
+            Trivia::Whitespace(" ".into()),
+            Trivia::LineComment("// comment".into()),
+            Trivia::Whitespace("\n  ".into()),
+        ]
+    );
+}
+
+#[test]
+fn format_test() {
+    let source = helpers::read_test_file("Unformatted.jack");
+    let tree = parse_tree(&source);
+
+    assert_eq!(
+        Formatter::new(&tree).format(),
+        helpers::read_test_file("Formatted.jack")
+    );
+}
+
+#[test]
+fn format_idempotence_test() {
+    for filename in TEST_FILES {
+        let source = helpers::read_test_file(filename);
+        let once = Formatter::new(&parse_tree(&source)).format();
+        let twice = Formatter::new(&parse_tree(&once)).format();
+
+        assert_eq!(once, twice, "formatting {} is not idempotent", filename);
+    }
+}
@@ -1,4 +1,4 @@
-use jackc::jack::{Keyword, Token, Tokenizer};
+use jackc::jack::{Keyword, SourceToken, Token, Tokenizer, Trivia};
 
 mod helpers;
 
@@ -478,3 +478,47 @@ fn square_game_jack_test() {
         ]
     );
 }
+
+#[test]
+fn trivia_test() {
+    let source = "/** Doc. */\nclass  Main { // comment\n  field int x007; }\n";
+    let (tokens, trailing) = Tokenizer::new(source).tokenize_with_trivia().unwrap();
+
+    assert_eq!(
+        tokens[0],
+        SourceToken {
+            leading: vec![
+                Trivia::BlockComment("/** Doc. */".into()),
+                Trivia::Whitespace("\n".into()),
+            ],
+            token: Token::Keyword(Keyword::Class),
+            text: "class".into(),
+        }
+    );
+
+    assert_eq!(
+        tokens[3],
+        SourceToken {
+            leading: vec![
+                Trivia::Whitespace(" ".into()),
+                Trivia::LineComment("// comment".into()),
+                Trivia::Whitespace("\n  ".into()),
+            ],
+            token: Token::Keyword(Keyword::Field),
+            text: "field".into(),
+        }
+    );
+
+    assert_eq!(trailing, vec![Trivia::Whitespace("\n".into())]);
+
+    let round_trip: String = tokens.iter().map(|t| t.to_string()).collect();
+    assert_eq!(round_trip + "\n", source);
+}
+
+#[test]
+fn invalid_int_const_test() {
+    assert!(Tokenizer::new("let x = 65536;").tokenize().is_err());
+    assert!(Tokenizer::new("let s = \"unterminated;")
+        .tokenize()
+        .is_err());
+}
@@ -1,6 +1,6 @@
-use jackc::asm;
+use jackc::asm::{self, Instruction, MemoryLayout};
 use jackc::hack::Emulator;
-use jackc::vm::{Command, Module, Segment, Translator};
+use jackc::vm::{self, Command, Module, OptimizeFor, Segment, Strategy, Translator};
 
 fn translate_and_assemble(name: &str, cmds: Vec<Command>, init: Option<String>) -> Vec<u16> {
     let modules = &[Module::new(name.to_owned(), cmds)];
@@ -12,6 +12,32 @@ fn translate_and_assemble(name: &str, cmds: Vec<Command>, init: Option<String>)
     asm::assemble(&insts).unwrap()
 }
 
+// Translates a program and runs it through the asm optimiser before assembling it, to check
+// that the optimised program leaves the expected values in RAM.
+fn check_optimized(
+    name: &str,
+    source: &str,
+    init: Option<&str>,
+    ram: &[(u16, u16)],
+    ticks: usize,
+    expected: &[(u16, u16)],
+) {
+    let modules = &[Module::new(name.to_owned(), vm::parse(source).unwrap())];
+
+    let mut translator = Translator::new(modules);
+    translator.init(init.map(|init| init.to_owned()));
+    let insts = asm::optimize(translator.translate().unwrap());
+    let rom = asm::assemble(&insts).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    emulator.ram.init(ram);
+    emulator.run(ticks);
+
+    for (addr, value) in expected {
+        assert_eq!(emulator.ram.get(*addr), *value, "RAM[{}] in {}", addr, name);
+    }
+}
+
 #[test]
 fn simple_add_test() {
     let rom = translate_and_assemble(
@@ -527,3 +553,1026 @@ fn statics_test() {
     assert_eq!(emulator.ram.get(261), -2i16 as u16);
     assert_eq!(emulator.ram.get(262), 8);
 }
+
+#[test]
+fn static_init_test() {
+    let mut modules = [
+        Module::new(
+            "Ball".into(),
+            vec![
+                Command::Function("Ball.static.init".into(), 0),
+                Command::Push(Segment::Constant, 3),
+                Command::Pop(Segment::Static, 0),
+                Command::Push(Segment::Constant, 0),
+                Command::Return,
+                Command::Function("Ball.speed".into(), 0),
+                Command::Push(Segment::Static, 0),
+                Command::Return,
+            ],
+        ),
+        Module::new(
+            "Main".into(),
+            vec![
+                Command::Function("Main.main".into(), 0),
+                Command::Call("Ball.speed".into(), 0),
+                Command::Return,
+            ],
+        ),
+        Module::new(
+            "Sys".into(),
+            vec![
+                Command::Function("Sys.init".into(), 0),
+                Command::Call("Main.main".into(), 0),
+                Command::Label("WHILE".into()),
+                Command::Goto("WHILE".into()),
+            ],
+        ),
+    ];
+    vm::link_static_inits(&mut modules).unwrap();
+
+    let mut translator = Translator::new(&modules);
+    let insts = translator.translate().unwrap();
+    let rom = asm::assemble(&insts).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(1000);
+
+    // The initialiser's return value must have been discarded, leaving only the frame
+    // for Sys.init and the return value of Main.main on the stack.
+    assert_eq!(emulator.ram.get(0), 262);
+    assert_eq!(emulator.ram.get(261), 3);
+}
+
+#[test]
+fn link_static_inits_test() {
+    let init = |class: &str| {
+        Module::new(
+            class.into(),
+            vec![
+                Command::Function(vm::static_init_name(class), 0),
+                Command::Push(Segment::Constant, 0),
+                Command::Return,
+            ],
+        )
+    };
+    let mut main = Module::new(
+        "Main".into(),
+        vec![
+            Command::Function("Main.main".into(), 0),
+            Command::Push(Segment::Constant, 0),
+            Command::Return,
+        ],
+    );
+    main.comments.push((1, "return 0".into()));
+
+    let mut modules = [init("Zebra"), main, init("Apple")];
+    vm::link_static_inits(&mut modules).unwrap();
+
+    // Calls are inserted in alphabetical order of class name, and only once.
+    let linked = modules.clone();
+    vm::link_static_inits(&mut modules).unwrap();
+    assert_eq!(modules, linked);
+    assert_eq!(
+        modules[1].cmds,
+        vec![
+            Command::Function("Main.main".into(), 0),
+            Command::Call("Apple.static.init".into(), 0),
+            Command::Pop(Segment::Temp, 0),
+            Command::Call("Zebra.static.init".into(), 0),
+            Command::Pop(Segment::Temp, 0),
+            Command::Push(Segment::Constant, 0),
+            Command::Return,
+        ]
+    );
+    assert_eq!(modules[1].comments, vec![(5, "return 0".into())]);
+
+    let mut modules = [init("Apple")];
+    assert_eq!(
+        vm::link_static_inits(&mut modules).unwrap_err().to_string(),
+        "static initialisers are called from `Main.main`, which isn't defined"
+    );
+}
+
+#[test]
+fn remove_unreachable_test() {
+    let modules = &[
+        Module::new(
+            "Math".into(),
+            vec![
+                Command::Function("Math.double".into(), 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Add,
+                Command::Return,
+                Command::Function("Math.triple".into(), 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Call("Math.double".into(), 1),
+                Command::Push(Segment::Argument, 0),
+                Command::Add,
+                Command::Return,
+                Command::Function("Math.unused".into(), 0),
+                Command::Call("Math.triple".into(), 0),
+                Command::Return,
+            ],
+        ),
+        Module::new(
+            "Sys".into(),
+            vec![
+                Command::Function("Sys.init".into(), 0),
+                Command::Push(Segment::Constant, 7),
+                Command::Call("Math.double".into(), 1),
+                Command::Label("WHILE".into()),
+                Command::Goto("WHILE".into()),
+            ],
+        ),
+    ];
+
+    let mut translator = Translator::new(modules);
+    let full = translator.translate().unwrap();
+    assert!(translator.removed().is_empty());
+
+    translator.remove_unreachable(true);
+    let insts = translator.translate().unwrap();
+    assert_eq!(translator.removed(), &["Math.triple", "Math.unused"]);
+    assert!(insts.len() < full.len());
+
+    let rom = asm::assemble(&insts).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(1000);
+
+    assert_eq!(emulator.ram.get(0), 262);
+    assert_eq!(emulator.ram.get(261), 14);
+
+    // A function called from outside the VM code is kept, along with everything it calls.
+    translator.root("Math.triple".into());
+    translator.translate().unwrap();
+    assert_eq!(translator.removed(), &["Math.unused"]);
+}
+
+#[test]
+fn remove_unreachable_no_init_test() {
+    let modules = &[Module::new(
+        "Main".into(),
+        vec![
+            Command::Push(Segment::Constant, 4),
+            Command::Call("Main.double".into(), 1),
+            Command::Label("END".into()),
+            Command::Goto("END".into()),
+            Command::Function("Main.double".into(), 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Add,
+            Command::Return,
+            Command::Function("Main.unused".into(), 0),
+            Command::Push(Segment::Constant, 0),
+            Command::Return,
+        ],
+    )];
+
+    let mut translator = Translator::new(modules);
+    translator.init(None);
+    translator.remove_unreachable(true);
+    translator.translate().unwrap();
+
+    assert_eq!(translator.removed(), &["Main.unused"]);
+}
+
+#[test]
+fn signed_comparison_test() {
+    let values: &[i16] = &[-32768, -32767, -20000, -1, 0, 1, 20000, 32766, 32767];
+
+    for op in &[Command::Lt, Command::Gt, Command::Eq] {
+        let mut cmds = Vec::new();
+        for x in values {
+            for y in values {
+                cmds.push(Command::Push(Segment::Constant, *x as u16));
+                cmds.push(Command::Push(Segment::Constant, *y as u16));
+                cmds.push(op.clone());
+            }
+        }
+
+        let rom = translate_and_assemble("Comparisons", cmds, None);
+        let mut emulator = Emulator::new(&rom);
+        emulator.run(100_000);
+
+        let mut addr = 256;
+        for x in values {
+            for y in values {
+                let expected = match op {
+                    Command::Lt => x < y,
+                    Command::Gt => x > y,
+                    _ => x == y,
+                };
+
+                assert_eq!(
+                    emulator.ram.get(addr),
+                    if expected { 0xffff } else { 0 },
+                    "{} {} {}",
+                    x,
+                    op,
+                    y
+                );
+                addr += 1;
+            }
+        }
+
+        assert_eq!(emulator.ram.get(0), addr);
+    }
+}
+
+#[test]
+fn memory_layout_test() {
+    let modules = &[
+        Module::new(
+            "Main".into(),
+            vec![
+                Command::Function("Main.fact".into(), 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Constant, 2),
+                Command::Lt,
+                Command::IfGoto("BASE".into()),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Call("Main.fact".into(), 1),
+                Command::Add,
+                Command::Return,
+                Command::Label("BASE".into()),
+                Command::Push(Segment::Constant, 1),
+                Command::Return,
+            ],
+        ),
+        Module::new(
+            "Sys".into(),
+            vec![
+                Command::Function("Sys.init".into(), 0),
+                Command::Push(Segment::Constant, 5),
+                Command::Call("Main.fact".into(), 1),
+                Command::Pop(Segment::Static, 0),
+                Command::Push(Segment::Static, 0),
+                Command::Push(Segment::Constant, 10),
+                Command::Eq,
+                Command::Pop(Segment::Static, 1),
+                Command::Label("WHILE".into()),
+                Command::Goto("WHILE".into()),
+            ],
+        ),
+    ];
+
+    let layout = "stack=1000,heap=3000,statics=500..510,scratch=20"
+        .parse::<MemoryLayout>()
+        .unwrap();
+
+    let mut translator = Translator::new(modules);
+    translator.layout(layout.clone()).unwrap();
+    let insts = translator.translate().unwrap();
+    let rom = asm::assemble_with_layout(&insts, &layout).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(2000);
+
+    // Main.fact sums rather than multiplies, so that it doesn't need Math.multiply.
+    assert_eq!(emulator.ram.get(500), 15);
+    assert_eq!(emulator.ram.get(501), 0);
+    assert_eq!(emulator.ram.get(0), 1005);
+    for addr in 13..16 {
+        assert_eq!(emulator.ram.get(addr), 0);
+    }
+
+    let layout = MemoryLayout {
+        heap_base: 1000,
+        ..layout
+    };
+    assert_eq!(
+        Translator::new(modules)
+            .layout(layout)
+            .unwrap_err()
+            .to_string(),
+        "the stack base (1000) must be below the heap base (1000)"
+    );
+}
+
+#[test]
+fn internal_labels_test() {
+    // Before internal labels were mangled, these would have clashed with the translator's own
+    // `START` and `EQ` labels.
+    let modules = &[Module::new(
+        "Main".into(),
+        vec![
+            Command::Push(Segment::Constant, 3),
+            Command::Push(Segment::Constant, 3),
+            Command::Call("EQ".into(), 2),
+            Command::Label("START".into()),
+            Command::Goto("START".into()),
+            Command::Function("EQ".into(), 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 1),
+            Command::Eq,
+            Command::Return,
+        ],
+    )];
+
+    let mut translator = Translator::new(modules);
+    translator.init(None);
+    let insts = translator.translate().unwrap();
+
+    assert!(insts
+        .iter()
+        .filter_map(|inst| match inst {
+            Instruction::Label(label) => Some(label),
+            _ => None,
+        })
+        .all(|label| label == "START" || label == "EQ" || label.starts_with('$')));
+
+    let rom = asm::assemble(&insts).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    emulator.run(1000);
+
+    assert_eq!(emulator.ram.get(0), 257);
+    assert_eq!(emulator.ram.get(256), 0xffff);
+}
+
+#[test]
+fn cached_top_test() {
+    let modules = &[Module::new(
+        "Main".into(),
+        vec![
+            Command::Function("Main.main".into(), 10),
+            Command::Push(Segment::Constant, 5),
+            Command::Neg,
+            Command::Pop(Segment::Local, 8),
+            Command::Push(Segment::Constant, 3),
+            Command::Push(Segment::Local, 8),
+            Command::Push(Segment::Constant, 32767),
+            Command::Not,
+            Command::Call("Main.sum".into(), 3),
+            Command::Pop(Segment::Static, 0),
+            Command::Push(Segment::Constant, 0),
+            Command::Label("LOOP".into()),
+            Command::Push(Segment::Constant, 1),
+            Command::Add,
+            Command::Pop(Segment::Local, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Push(Segment::Constant, 4),
+            Command::Lt,
+            Command::IfGoto("LOOP".into()),
+            Command::Pop(Segment::Static, 1),
+            Command::Label("HALT".into()),
+            Command::Goto("HALT".into()),
+            Command::Function("Main.sum".into(), 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 1),
+            Command::Sub,
+            Command::Push(Segment::Argument, 2),
+            Command::Or,
+            Command::Return,
+        ],
+    )];
+
+    let run = |strategy| {
+        let mut translator = Translator::new(modules);
+        translator.init(Some("Main.main".into()));
+        translator.strategy(strategy);
+        let rom = asm::assemble(&translator.translate().unwrap()).unwrap();
+
+        let mut emulator = Emulator::new(&rom);
+        emulator.run(2000);
+        (rom.len(), emulator.ram.get(16), emulator.ram.get(17))
+    };
+
+    let (memory_len, sum, count) = run(Strategy::Memory);
+    assert_eq!((sum, count), (8 | 0x8000, 4));
+
+    let (cached_len, cached_sum, cached_count) = run(Strategy::CachedTop);
+    assert_eq!((cached_sum, cached_count), (sum, count));
+    assert!(cached_len < memory_len);
+}
+
+#[test]
+fn tail_calls_test() {
+    let modules = &[
+        Module::new(
+            "Main".into(),
+            vec![
+                // Counts down from n, adding each number to the total.
+                Command::Function("Main.sum".into(), 0),
+                Command::Push(Segment::Argument, 0),
+                Command::IfGoto("RECURSE".into()),
+                Command::Push(Segment::Argument, 1),
+                Command::Return,
+                Command::Label("RECURSE".into()),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Sub,
+                Command::Push(Segment::Argument, 1),
+                Command::Push(Segment::Argument, 0),
+                Command::Add,
+                Command::Call("Main.sum".into(), 2),
+                Command::Return,
+                // Tail calls a function with more arguments, which in turn tail calls one with
+                // fewer, changing THIS along the way.
+                Command::Function("Main.one".into(), 1),
+                Command::Push(Segment::Constant, 4000),
+                Command::Pop(Segment::Pointer, 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Constant, 10),
+                Command::Push(Segment::Constant, 100),
+                Command::Call("Main.three".into(), 3),
+                Command::Return,
+                Command::Function("Main.three".into(), 2),
+                Command::Push(Segment::Argument, 0),
+                Command::Push(Segment::Argument, 1),
+                Command::Add,
+                Command::Push(Segment::Argument, 2),
+                Command::Add,
+                Command::Call("Main.id".into(), 1),
+                Command::Return,
+                Command::Function("Main.id".into(), 0),
+                Command::Push(Segment::Argument, 0),
+                Command::Return,
+            ],
+        ),
+        Module::new(
+            "Sys".into(),
+            vec![
+                Command::Function("Sys.init".into(), 1),
+                Command::Push(Segment::Constant, 3000),
+                Command::Pop(Segment::Pointer, 0),
+                Command::Push(Segment::Constant, 1000),
+                Command::Push(Segment::Constant, 0),
+                Command::Call("Main.sum".into(), 2),
+                Command::Pop(Segment::Static, 0),
+                Command::Push(Segment::Constant, 1),
+                Command::Call("Main.one".into(), 1),
+                Command::Pop(Segment::Static, 1),
+                Command::Push(Segment::Pointer, 0),
+                Command::Pop(Segment::Static, 2),
+                Command::Push(Segment::Constant, 1),
+                Command::Pop(Segment::Temp, 7),
+                Command::Label("HALT".into()),
+                Command::Goto("HALT".into()),
+            ],
+        ),
+    ];
+
+    let mut translator = Translator::new(modules);
+    translator.tail_calls(true);
+    let rom = asm::assemble(&translator.translate().unwrap()).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    let mut max_sp = 0;
+    for _ in 0..1_000_000 {
+        emulator.step();
+        max_sp = max_sp.max(emulator.ram.get(0));
+    }
+
+    assert_eq!(emulator.ram.get(12), 1);
+    assert_eq!(emulator.ram.get(16), 500_500u32 as u16);
+    assert_eq!(emulator.ram.get(17), 111);
+    assert_eq!(emulator.ram.get(18), 3000);
+    // Without tail calls, Main.sum alone would need 7 words for each of its 1001 frames.
+    assert!(max_sp < 300, "stack grew to {}", max_sp);
+}
+
+#[test]
+fn optimize_for_test() {
+    let modules = &[Module::new(
+        "Sys".into(),
+        vec![
+            Command::Function("Sys.init".into(), 0),
+            Command::Push(Segment::Constant, 20),
+            Command::Pop(Segment::Temp, 0),
+            Command::Label("LOOP".into()),
+            Command::Push(Segment::Static, 0),
+            Command::Push(Segment::Temp, 0),
+            Command::Call("Sys.add".into(), 2),
+            Command::Pop(Segment::Static, 0),
+            Command::Push(Segment::Temp, 0),
+            Command::Push(Segment::Constant, 1),
+            Command::Sub,
+            Command::Pop(Segment::Temp, 0),
+            Command::Push(Segment::Temp, 0),
+            Command::IfGoto("LOOP".into()),
+            Command::Push(Segment::Constant, 1),
+            Command::Pop(Segment::Temp, 7),
+            Command::Label("HALT".into()),
+            Command::Goto("HALT".into()),
+            Command::Function("Sys.add".into(), 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 1),
+            Command::Add,
+            Command::Return,
+        ],
+    )];
+
+    let run = |configure: &dyn Fn(&mut Translator)| {
+        let mut translator = Translator::new(modules);
+        configure(&mut translator);
+        let insts = translator.translate().unwrap();
+        let rom = asm::assemble(&insts).unwrap();
+
+        let mut emulator = Emulator::new(&rom);
+        let mut cycles = 0;
+        while emulator.ram.get(12) == 0 && cycles < 100_000 {
+            emulator.step();
+            cycles += 1;
+        }
+
+        assert_eq!(emulator.ram.get(16), 210);
+        (insts, cycles)
+    };
+
+    let (size, size_cycles) = run(&|_| {});
+    let (speed, speed_cycles) = run(&|t| t.optimize_for(OptimizeFor::Speed));
+    let (mixed, mixed_cycles) =
+        run(&|t| t.optimize_function_for("Sys.add".into(), OptimizeFor::Speed));
+
+    let calls_routine = |insts: &[Instruction]| insts.contains(&Instruction::A("$CALL".into()));
+    assert!(calls_routine(&size));
+    assert!(!calls_routine(&speed));
+    assert!(speed_cycles < size_cycles);
+    // Only the bootstrap code's call to Sys.init still goes through the shared routine.
+    assert!(calls_routine(&mixed));
+    assert!(mixed_cycles < size_cycles);
+    assert!(mixed_cycles - speed_cycles < 20);
+}
+
+#[test]
+fn static_frames_test() {
+    let modules = &[Module::new(
+        "Sys".into(),
+        vec![
+            Command::Function("Sys.init".into(), 1),
+            Command::Push(Segment::Constant, 21),
+            Command::Call("Sys.double".into(), 1),
+            Command::Pop(Segment::Local, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Push(Segment::Constant, 4),
+            Command::Call("Sys.sum".into(), 2),
+            Command::Pop(Segment::Static, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Pop(Segment::Static, 1),
+            Command::Push(Segment::Constant, 1),
+            Command::Pop(Segment::Temp, 7),
+            Command::Label("HALT".into()),
+            Command::Goto("HALT".into()),
+            Command::Function("Sys.double".into(), 1),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Add,
+            Command::Pop(Segment::Local, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Return,
+            // Adds n, n - 1, ..., 1 to the total, recursively.
+            Command::Function("Sys.sum".into(), 0),
+            Command::Push(Segment::Argument, 1),
+            Command::IfGoto("RECURSE".into()),
+            Command::Push(Segment::Argument, 0),
+            Command::Return,
+            Command::Label("RECURSE".into()),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 1),
+            Command::Add,
+            Command::Push(Segment::Argument, 1),
+            Command::Push(Segment::Constant, 1),
+            Command::Sub,
+            Command::Call("Sys.sum".into(), 2),
+            Command::Return,
+        ],
+    )];
+
+    let mut translator = Translator::new(modules);
+    translator.static_frames(true);
+    let insts = translator.translate().unwrap();
+    let rom = asm::assemble(&insts).unwrap();
+
+    let mut emulator = Emulator::new(&rom);
+    for _ in 0..10_000 {
+        emulator.step();
+    }
+
+    assert_eq!(emulator.ram.get(12), 1);
+    assert_eq!(emulator.ram.get(16), 52);
+    assert_eq!(emulator.ram.get(17), 42);
+    // Sys.init's local is followed by the argument and local of Sys.double, just below the heap.
+    assert!(insts.contains(&Instruction::A(2045.into())));
+    assert_eq!(emulator.ram.get(2046), 21);
+    assert_eq!(emulator.ram.get(2047), 42);
+
+    // The frames must leave room for the stack, including the margin for recursive calls.
+    let translate = |layout: &str, margin: usize| {
+        let mut translator = Translator::new(modules);
+        translator.static_frames(true);
+        translator.stack_margin(margin);
+        translator.layout(layout.parse().unwrap()).unwrap();
+        translator.translate()
+    };
+    assert_eq!(
+        translate("stack=256,heap=500", 256)
+            .unwrap_err()
+            .to_string(),
+        "no room for the static frames, as they need 3 words and the stack can need 272 more, \
+         but only 244 fit in RAM[256..500]"
+    );
+    assert!(translate("stack=256,heap=500", 200).is_ok());
+}
+
+#[test]
+fn static_frames_stack_test() {
+    let modules = &[Module::new(
+        "Sys".into(),
+        vec![
+            Command::Function("Sys.init".into(), 0),
+            Command::Push(Segment::Constant, 1),
+            Command::Push(Segment::Constant, 2),
+            Command::Push(Segment::Constant, 3),
+            Command::Push(Segment::Constant, 4),
+            Command::Push(Segment::Constant, 5),
+            Command::Call("Sys.triple".into(), 1),
+            Command::Add,
+            Command::Add,
+            Command::Add,
+            Command::Add,
+            Command::Pop(Segment::Static, 0),
+            Command::Push(Segment::Constant, 1),
+            Command::Pop(Segment::Temp, 7),
+            Command::Label("HALT".into()),
+            Command::Goto("HALT".into()),
+            Command::Function("Sys.triple".into(), 1),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Push(Segment::Argument, 0),
+            Command::Add,
+            Command::Add,
+            Command::Pop(Segment::Local, 0),
+            Command::Push(Segment::Local, 0),
+            Command::Return,
+        ],
+    )];
+
+    let translate = |heap: u16| {
+        let mut translator = Translator::new(modules);
+        translator.static_frames(true);
+        translator
+            .layout(MemoryLayout {
+                heap_base: heap,
+                ..MemoryLayout::new()
+            })
+            .unwrap();
+        translator.translate()
+    };
+
+    // The frames only take 2 words, but the stack gets much deeper than that, so it would run
+    // into them if they were allowed any closer.
+    assert_eq!(
+        translate(276).unwrap_err().to_string(),
+        "no room for the static frames, as they need 2 words and the stack can need 19 more, \
+         but only 20 fit in RAM[256..276]"
+    );
+
+    let rom = asm::assemble(&translate(277).unwrap()).unwrap();
+    let mut emulator = Emulator::new(&rom);
+    while emulator.ram.get(12) == 0 {
+        emulator.step();
+    }
+    assert_eq!(emulator.ram.get(16), 25);
+}
+
+// The programs from the tests above give the same results after going through the asm optimiser.
+#[test]
+fn asm_optimize_test() {
+    check_optimized(
+        "SimpleAdd",
+        "push constant 7
+         push constant 8
+         add",
+        None,
+        &[(0, 256)],
+        60,
+        &[(0, 257), (256, 15)],
+    );
+
+    check_optimized(
+        "Consts",
+        "push constant 32768
+         push constant 16384
+         add
+         push constant 65535
+         push constant 32767
+         and",
+        None,
+        &[(0, 256)],
+        60,
+        &[(0, 258), (256, 49152), (257, 32767)],
+    );
+
+    check_optimized(
+        "StackTest",
+        "push constant 17
+         push constant 17
+         eq
+         push constant 17
+         push constant 16
+         eq
+         push constant 16
+         push constant 17
+         eq
+         push constant 892
+         push constant 891
+         lt
+         push constant 891
+         push constant 892
+         lt
+         push constant 891
+         push constant 891
+         lt
+         push constant 32767
+         push constant 32766
+         gt
+         push constant 32766
+         push constant 32767
+         gt
+         push constant 32766
+         push constant 32766
+         gt
+         push constant 57
+         push constant 31
+         push constant 53
+         add
+         push constant 112
+         sub
+         neg
+         and
+         push constant 82
+         or
+         not",
+        None,
+        &[(0, 256)],
+        1000,
+        &[
+            (0, 266),
+            (256, -1i16 as u16),
+            (257, 0),
+            (258, 0),
+            (259, 0),
+            (260, -1i16 as u16),
+            (261, 0),
+            (262, -1i16 as u16),
+            (263, 0),
+            (264, 0),
+            (265, -91i16 as u16),
+        ],
+    );
+
+    check_optimized(
+        "BasicTest",
+        "push constant 10
+         pop local 0
+         push constant 21
+         push constant 22
+         pop argument 2
+         pop argument 1
+         push constant 36
+         pop this 6
+         push constant 42
+         push constant 45
+         pop that 5
+         pop that 2
+         push constant 510
+         pop temp 6
+         push local 0
+         push that 5
+         add
+         push argument 1
+         sub
+         push this 6
+         push this 6
+         add
+         sub
+         push temp 6
+         add",
+        None,
+        &[(0, 256), (1, 300), (2, 400), (3, 3000), (4, 3010)],
+        600,
+        &[
+            (0, 257),
+            (256, 472),
+            (11, 510),
+            (300, 10),
+            (401, 21),
+            (402, 22),
+            (3006, 36),
+            (3012, 42),
+            (3015, 45),
+        ],
+    );
+
+    check_optimized(
+        "PointerTest",
+        "push constant 3030
+         pop pointer 0
+         push constant 3040
+         pop pointer 1
+         push constant 32
+         pop this 2
+         push constant 46
+         pop that 6
+         push pointer 0
+         push pointer 1
+         add
+         push this 2
+         sub
+         push that 6
+         add",
+        None,
+        &[(0, 256)],
+        450,
+        &[(3, 3030), (4, 3040), (256, 6084), (3032, 32), (3046, 46)],
+    );
+
+    check_optimized(
+        "StaticTest",
+        "push constant 111
+         push constant 333
+         push constant 888
+         pop static 8
+         pop static 3
+         pop static 1
+         push static 3
+         push static 1
+         sub
+         push static 8
+         add",
+        None,
+        &[(0, 256)],
+        200,
+        &[(256, 1110)],
+    );
+
+    check_optimized(
+        "BasicLoop",
+        "push constant 0
+         pop local 0
+         label LOOP_START
+         push argument 0
+         push local 0
+         add
+         pop local 0
+         push argument 0
+         push constant 1
+         sub
+         pop argument 0
+         push argument 0
+         if-goto LOOP_START
+         push local 0",
+        None,
+        &[(0, 256), (1, 300), (2, 400), (400, 3)],
+        600,
+        &[(0, 257), (256, 6)],
+    );
+
+    check_optimized(
+        "FibonacciSeries",
+        "push argument 1
+         pop pointer 1
+         push constant 0
+         pop that 0
+         push constant 1
+         pop that 1
+         push argument 0
+         push constant 2
+         sub
+         pop argument 0
+         label MAIN_LOOP_START
+         push argument 0
+         if-goto COMPUTE_ELEMENT
+         goto END_PROGRAM
+         label COMPUTE_ELEMENT
+         push that 0
+         push that 1
+         add
+         pop that 2
+         push pointer 1
+         push constant 1
+         add
+         pop pointer 1
+         push argument 0
+         push constant 1
+         sub
+         pop argument 0
+         goto MAIN_LOOP_START
+         label END_PROGRAM",
+        None,
+        &[(0, 256), (1, 300), (2, 400), (400, 6), (401, 3000)],
+        1100,
+        &[
+            (3000, 0),
+            (3001, 1),
+            (3002, 1),
+            (3003, 2),
+            (3004, 3),
+            (3005, 5),
+        ],
+    );
+
+    check_optimized(
+        "SimpleFunction",
+        "function SimpleFunction.test 2
+         push local 0
+         push local 1
+         add
+         not
+         push argument 0
+         add
+         push argument 1
+         sub
+         return",
+        None,
+        &[
+            (0, 317),
+            (1, 317),
+            (2, 310),
+            (3, 3000),
+            (4, 4000),
+            (310, 1234),
+            (311, 37),
+            (312, 1000),
+            (313, 305),
+            (314, 300),
+            (315, 3010),
+            (316, 4010),
+        ],
+        300,
+        &[
+            (0, 311),
+            (1, 305),
+            (2, 300),
+            (3, 3010),
+            (4, 4010),
+            (310, 1196),
+        ],
+    );
+
+    check_optimized(
+        "Sys",
+        "function Sys.init 0
+         call Sys.main 0
+         pop temp 1
+         label LOOP
+         goto LOOP
+         function Sys.main 0
+         push constant 123
+         call Sys.add12 1
+         pop temp 0
+         push constant 246
+         return
+         function Sys.add12 3
+         push argument 0
+         push constant 12
+         add
+         return",
+        Some("Sys.init"),
+        &[
+            (0, 261),
+            (1, 261),
+            (2, 256),
+            (3, -1i16 as u16),
+            (4, -1i16 as u16),
+            (256, 1234),
+            (257, -1i16 as u16),
+            (258, -1i16 as u16),
+            (259, -1i16 as u16),
+            (260, -1i16 as u16),
+        ],
+        1000,
+        &[(0, 261), (1, 261), (2, 256), (5, 135), (6, 246)],
+    );
+
+    check_optimized(
+        "Comparisons",
+        "push constant 32767
+         neg
+         push constant 1
+         lt
+         push constant 20000
+         push constant 32767
+         neg
+         gt
+         push constant 1
+         neg
+         push constant 1
+         neg
+         eq",
+        None,
+        &[(0, 256)],
+        1000,
+        &[
+            (0, 259),
+            (256, -1i16 as u16),
+            (257, -1i16 as u16),
+            (258, -1i16 as u16),
+        ],
+    );
+}
@@ -0,0 +1,300 @@
+use jackc::jack::{Compiler, Parser, Tokenizer};
+use jackc::vm::{self, Command, Module, Segment, Validator};
+
+mod helpers;
+
+fn module(name: &str, source: &str) -> Module {
+    Module::new(name.to_owned(), vm::parse(source).unwrap())
+}
+
+fn validate(modules: &[Module]) -> Result<(), String> {
+    let mut validator = Validator::new(modules);
+    validator.init(None);
+    validator.validate().map_err(|err| err.to_string())
+}
+
+#[test]
+fn valid_program_test() {
+    let modules = &[
+        module(
+            "Main",
+            "function Main.main 0
+             label LOOP
+             call Util.twice 0
+             if-goto LOOP
+             goto END
+             label END
+             push constant 0
+             return",
+        ),
+        module(
+            "Util",
+            "function Util.twice 0
+             label LOOP
+             push static 0
+             push static 0
+             add
+             return",
+        ),
+    ];
+
+    assert_eq!(validate(modules), Ok(()));
+}
+
+#[test]
+fn label_errors_test() {
+    let modules = &[module(
+        "Main",
+        "function Main.main 0
+         label A
+         label A
+         goto B
+         return
+         function Main.other 0
+         label B
+         if-goto A
+         return",
+    )];
+
+    assert_eq!(
+        validate(modules),
+        Err("in function `Main.main`: label `A` is defined twice\n\
+             in function `Main.main`: `goto B` targets undefined label `B`\n\
+             in function `Main.other`: `if-goto A` targets undefined label `A`"
+            .to_owned())
+    );
+}
+
+#[test]
+fn name_errors_test() {
+    let modules = &[
+        module(
+            "Main",
+            "function Main.main 0
+             label $START
+             label 1ST
+             label LOOP:2.b
+             return
+             function Main.static.init 0
+             return
+             function $init 0
+             return
+             function Main.main$LOOP 0
+             return",
+        ),
+        module(
+            "Other",
+            "label SP
+             label Main.3
+             label Main.main
+             label END
+             function SCREEN 0
+             return
+             function Other.1 0
+             return",
+        ),
+        module("Last", "label END\nlabel START"),
+    ];
+
+    assert_eq!(
+        validate(modules),
+        Err("`$init` is not a valid function name\n\
+             `Main.main$LOOP` is not a valid function name\n\
+             function `SCREEN` clashes with a predefined symbol\n\
+             function `Other.1` clashes with the symbol for a static variable\n\
+             in function `Main.main`: `$START` is not a valid label name\n\
+             in function `Main.main`: `1ST` is not a valid label name\n\
+             in module `Other`: label `SP` clashes with a predefined symbol\n\
+             in module `Other`: label `Main.3` clashes with the symbol for a static variable\n\
+             in module `Other`: label `Main.main` clashes with a function of the same name\n\
+             in module `Last`: label `END` is also defined in module `Other`"
+            .to_owned())
+    );
+}
+
+#[test]
+fn empty_name_parts_test() {
+    let modules = &[module(
+        "Ball",
+        "function Ball. 0
+         label init
+         return
+         function .main 0
+         return
+         function Ball..move 0
+         label :LOOP
+         label .END
+         return
+         function Ball.move.fast 0
+         return",
+    )];
+
+    assert_eq!(
+        validate(modules),
+        Err("`Ball.` is not a valid function name\n\
+             `.main` is not a valid function name\n\
+             `Ball..move` is not a valid function name\n\
+             in function `Ball..move`: `:LOOP` is not a valid label name\n\
+             in function `Ball..move`: `.END` is not a valid label name"
+            .to_owned())
+    );
+}
+
+#[test]
+fn duplicate_function_test() {
+    let modules = &[
+        module("Main", "function Main.main 0\nreturn"),
+        module("Other", "function Main.main 0\nreturn"),
+    ];
+
+    assert_eq!(
+        validate(modules),
+        Err("function `Main.main` in `Other` is already defined in `Main`".to_owned())
+    );
+}
+
+#[test]
+fn undefined_call_test() {
+    let modules = &[module(
+        "Main",
+        "function Main.main 0
+         call Math.multiply 2
+         call Output.printInt 1
+         call Output.println 0
+         call Main.helper 0
+         return",
+    )];
+
+    assert_eq!(
+        validate(modules),
+        Err(
+            "in function `Main.main`: call to undefined function `Math.multiply`\n\
+             in function `Main.main`: call to undefined function `Output.printInt`\n\
+             in function `Main.main`: call to undefined function `Output.println`\n\
+             in function `Main.main`: call to undefined function `Main.helper`"
+                .to_owned()
+        )
+    );
+
+    let mut validator = Validator::new(modules);
+    validator.init(None);
+    validator.external("Math.multiply".into());
+    validator.external("Output.*".into());
+
+    assert_eq!(
+        validator.validate().unwrap_err().to_string(),
+        "in function `Main.main`: call to undefined function `Main.helper`"
+    );
+}
+
+#[test]
+fn os_external_test() {
+    let modules = &[module(
+        "Main",
+        "function Main.main 0
+         call Math.multiply 2
+         call Output.printInt 1
+         call Math.multiply 2
+         call Memory.peek 1
+         call Main.helper 0
+         return",
+    )];
+
+    let mut validator = Validator::new(modules);
+    validator.os_external(true);
+    validator.external("Memory.*".into());
+    assert_eq!(
+        validator.validate().unwrap_err().to_string(),
+        "in function `Main.main`: call to undefined function `Main.helper`"
+    );
+    // Functions allowed by name aren't counted as missing from the OS.
+    assert_eq!(
+        validator.undefined_os_functions(),
+        &["Sys.init", "Math.multiply", "Output.printInt"]
+    );
+
+    let modules = &[
+        module("Main", "function Main.main 0\ncall Math.abs 1\nreturn"),
+        module("Sys", "function Sys.init 0\ncall Main.main 0\nreturn"),
+    ];
+    let mut validator = Validator::new(modules);
+    validator.os_external(true);
+    assert!(validator.validate().is_ok());
+    assert_eq!(validator.undefined_os_functions(), &["Math.abs"]);
+}
+
+#[test]
+fn init_test() {
+    let modules = &[module("Main", "function Main.main 0\nreturn")];
+
+    assert_eq!(
+        Validator::new(modules).validate().unwrap_err().to_string(),
+        "init function `Sys.init` is not defined"
+    );
+
+    let mut validator = Validator::new(modules);
+    validator.init(Some("Main.main".into()));
+    assert!(validator.validate().is_ok());
+}
+
+#[test]
+fn pop_constant_test() {
+    let modules = &[module("Main", "push constant 1\npop constant 1")];
+
+    assert_eq!(
+        validate(modules),
+        Err("in module `Main`: `pop constant 1` is not allowed".to_owned())
+    );
+}
+
+#[test]
+fn static_area_test() {
+    let statics = |n| {
+        (0..n)
+            .map(|i| Command::Push(Segment::Static, i))
+            .collect::<Vec<_>>()
+    };
+
+    let modules = &[
+        Module::new("A".into(), statics(200)),
+        Module::new("B".into(), statics(40)),
+    ];
+    assert_eq!(validate(modules), Ok(()));
+
+    let modules = &[
+        Module::new("A".into(), statics(200)),
+        Module::new("B".into(), statics(41)),
+    ];
+    assert_eq!(
+        validate(modules),
+        Err(
+            "module `B` brings the total number of statics to 241, but only 240 fit in the \
+             static area"
+                .to_owned()
+        )
+    );
+
+    let mut validator = Validator::new(modules);
+    validator.init(None);
+    validator.layout("statics=16..1024,stack=1024".parse().unwrap());
+    assert!(validator.validate().is_ok());
+}
+
+#[test]
+fn compiled_program_test() {
+    let modules = ["SquareMain.jack", "Square.jack", "SquareGame.jack"]
+        .iter()
+        .map(|filename| {
+            let source = helpers::read_test_file(filename);
+            let tokens = Tokenizer::new(&source).tokenize().unwrap();
+            let class = Parser::new(tokens).parse().unwrap();
+            Compiler::new(&class).compile().unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let mut validator = Validator::new(&modules);
+    validator.init(Some("Main.main".into()));
+    validator.os_external(true);
+
+    validator.validate().unwrap();
+}
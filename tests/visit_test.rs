@@ -0,0 +1,84 @@
+use anyhow::Error;
+use jackc::jack::*;
+
+mod helpers;
+
+fn parse(filename: &str) -> Class {
+    let source = helpers::read_test_file(filename);
+    let tokens = Tokenizer::new(&source).tokenize().expect("tokenizer error");
+    Parser::new(tokens).parse().expect("parser error")
+}
+
+#[derive(Default)]
+struct CallCounter {
+    calls: Vec<String>,
+    statements: usize,
+}
+
+impl Visitor for CallCounter {
+    fn visit_statement(&mut self, stmt: &Statement) -> Result<(), Error> {
+        self.statements += 1;
+        walk_statement(self, stmt)
+    }
+
+    fn visit_subroutine_call(&mut self, call: &SubroutineCall) -> Result<(), Error> {
+        self.calls.push(call.subroutine.clone());
+        walk_subroutine_call(self, call)
+    }
+}
+
+#[test]
+fn visitor_test() {
+    let class = parse("Seven.jack");
+    let mut counter = CallCounter::default();
+    counter.visit_class(&class).unwrap();
+
+    assert_eq!(counter.calls, vec!["printInt"]);
+    assert_eq!(counter.statements, 2);
+}
+
+#[test]
+fn visitor_nested_test() {
+    let class = parse("Square.jack");
+    let mut counter = CallCounter::default();
+    counter.visit_class(&class).unwrap();
+
+    // Calls inside the bodies of ifs and whiles are visited too.
+    assert_eq!(
+        counter
+            .calls
+            .iter()
+            .filter(|c| *c == "drawRectangle")
+            .count(),
+        10
+    );
+    assert_eq!(
+        counter.calls.iter().filter(|c| *c == "setColor").count(),
+        10
+    );
+}
+
+struct Negator;
+
+impl VisitorMut for Negator {
+    fn visit_term_mut(&mut self, term: &mut Term) -> Result<(), Error> {
+        walk_term_mut(self, term)?;
+
+        if let Term::IntConst(n) = term {
+            *term = Term::Unary(UnaryOp::Minus, Box::new(Term::IntConst(*n)));
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn visitor_mut_test() {
+    let mut class = parse("Seven.jack");
+    Negator.visit_class_mut(&mut class).unwrap();
+
+    assert_eq!(
+        class.subs[0].body.statements[0].to_string(),
+        "do Output.printInt(-1 + (-2 * -3));"
+    );
+}
@@ -0,0 +1,114 @@
+use jackc::vm::{self, Command, Segment};
+
+#[test]
+fn parse_test() {
+    let source = "// Computes 7 + 8.\r\n\
+                  \r\n\
+                  function Main.main 0\r\n\
+                  \tpush constant 7   \r\n\
+                  push\tconstant\t8 // the second operand\r\n\
+                  \x20\x20add\r\n\
+                  \r\n\
+                  return";
+
+    assert_eq!(
+        vm::parse(source).unwrap(),
+        vec![
+            Command::Function("Main.main".into(), 0),
+            Command::Push(Segment::Constant, 7),
+            Command::Push(Segment::Constant, 8),
+            Command::Add,
+            Command::Return,
+        ]
+    );
+}
+
+#[test]
+fn parse_all_commands_test() {
+    let source = "
+        add
+        sub
+        neg
+        eq
+        gt
+        lt
+        and
+        or
+        not
+        pop local 3
+        push that 5
+        label LOOP
+        goto LOOP
+        if-goto END
+        function Foo.bar 2
+        call Foo.bar 1
+        return
+    ";
+
+    assert_eq!(
+        vm::parse(source).unwrap(),
+        vec![
+            Command::Add,
+            Command::Sub,
+            Command::Neg,
+            Command::Eq,
+            Command::Gt,
+            Command::Lt,
+            Command::And,
+            Command::Or,
+            Command::Not,
+            Command::Pop(Segment::Local, 3),
+            Command::Push(Segment::That, 5),
+            Command::Label("LOOP".into()),
+            Command::Goto("LOOP".into()),
+            Command::IfGoto("END".into()),
+            Command::Function("Foo.bar".into(), 2),
+            Command::Call("Foo.bar".into(), 1),
+            Command::Return,
+        ]
+    );
+}
+
+#[test]
+fn parse_error_test() {
+    let cases = &[
+        (
+            "push constant 1\npush temp 9",
+            "line 2: `temp 9` is out of range",
+        ),
+        ("pop pointer 2", "line 1: `pointer 2` is out of range"),
+        ("push constant x", "line 1: `x` is not a valid number"),
+        ("push heap 0", "line 1: `heap` is not a valid segment"),
+        ("add\n\nadd 1", "line 3: unexpected arguments after `add`"),
+        ("goto", "line 1: expected a label"),
+        (
+            "pushconstant 1",
+            "line 1: `pushconstant 1` is not a valid VM command",
+        ),
+    ];
+
+    for (source, expected) in cases {
+        let err = vm::parse(source).unwrap_err().to_string();
+        assert!(err.starts_with(expected), "`{}` gave `{}`", source, err);
+    }
+}
+
+#[test]
+fn display_round_trip_test() {
+    let cmds = vec![
+        Command::Function("Main.main".into(), 1),
+        Command::Push(Segment::Temp, 7),
+        Command::Pop(Segment::Pointer, 1),
+        Command::IfGoto("Main.main$END".into()),
+        Command::Call("Math.multiply".into(), 2),
+        Command::Return,
+    ];
+
+    let source = cmds
+        .iter()
+        .map(|cmd| cmd.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert_eq!(vm::parse(&source).unwrap(), cmds);
+}